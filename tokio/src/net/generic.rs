@@ -0,0 +1,170 @@
+//! Traits for writing code generic over the networking backend.
+
+use super::{TcpStream, UdpSocket};
+use crate::io::{AsyncRead, AsyncWrite};
+use std::future::Future;
+use std::io::Result;
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+/// A boxed future, as returned by the methods of the networking traits.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The minimal interface of a UDP socket.
+///
+/// The trait is implemented by the [`UdpSocket`] of `tokio::net`, which is
+/// the simulated socket with feature "sim" and the OS socket otherwise.
+/// Code that is generic over `AsyncUdpSocket` therefore runs unchanged on
+/// either backend, picked at compile time by the feature set of the final
+/// binary.
+///
+/// ```no_run
+/// use std::io::Result;
+/// use std::net::SocketAddr;
+/// use tokio::net::AsyncUdpSocket;
+///
+/// async fn ping<S: AsyncUdpSocket>(peer: SocketAddr) -> Result<Vec<u8>> {
+///     let socket = S::bind("0.0.0.0:0".parse().unwrap()).await?;
+///     socket.send_to(b"ping", peer).await?;
+///
+///     let mut buf = [0u8; 64];
+///     let (n, _) = socket.recv_from(&mut buf).await?;
+///     Ok(buf[..n].to_vec())
+/// }
+/// ```
+pub trait AsyncUdpSocket: Sized + Send + Sync {
+    /// Creates a new socket bound to `addr`.
+    fn bind(addr: SocketAddr) -> BoxFuture<'static, Result<Self>>;
+
+    /// Returns the local address this socket is bound to.
+    fn local_addr(&self) -> Result<SocketAddr>;
+
+    /// Sends `buf` to `target`, returning the number of bytes sent.
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, Result<usize>>;
+
+    /// Receives a single datagram into `buf`, returning the number of bytes
+    /// read and the origin of the datagram.
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, Result<(usize, SocketAddr)>>;
+}
+
+impl AsyncUdpSocket for UdpSocket {
+    fn bind(addr: SocketAddr) -> BoxFuture<'static, Result<Self>> {
+        Box::pin(UdpSocket::bind(addr))
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(UdpSocket::send_to(self, buf, target))
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, Result<(usize, SocketAddr)>> {
+        Box::pin(UdpSocket::recv_from(self, buf))
+    }
+}
+
+/// The minimal interface of a TCP stream.
+///
+/// Data is exchanged using the [`AsyncRead`] and [`AsyncWrite`] implementations
+/// of the stream. See [`AsyncUdpSocket`] for how the backend is chosen.
+pub trait AsyncTcpStream: AsyncRead + AsyncWrite + Unpin + Sized + Send + Sync {
+    /// Opens a connection to `addr`.
+    fn connect(addr: SocketAddr) -> BoxFuture<'static, Result<Self>>;
+
+    /// Returns the local address of this stream.
+    fn local_addr(&self) -> Result<SocketAddr>;
+
+    /// Returns the address of the remote peer of this stream.
+    fn peer_addr(&self) -> Result<SocketAddr>;
+}
+
+impl AsyncTcpStream for TcpStream {
+    fn connect(addr: SocketAddr) -> BoxFuture<'static, Result<Self>> {
+        Box::pin(TcpStream::connect(addr))
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        TcpStream::local_addr(self)
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        TcpStream::peer_addr(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn echo<S: AsyncUdpSocket>(addr: SocketAddr) -> Result<()> {
+        let socket = S::bind(addr).await?;
+        let mut buf = [0u8; 64];
+        let (n, from) = socket.recv_from(&mut buf).await?;
+        socket.send_to(&buf[..n], from).await?;
+        Ok(())
+    }
+
+    async fn ping<S: AsyncUdpSocket>(addr: SocketAddr, peer: SocketAddr) -> Result<Vec<u8>> {
+        let socket = S::bind(addr).await?;
+        socket.send_to(b"ping", peer).await?;
+        let mut buf = [0u8; 64];
+        let (n, from) = socket.recv_from(&mut buf).await?;
+        assert_eq!(from, peer);
+        Ok(buf[..n].to_vec())
+    }
+
+    #[cfg(not(feature = "sim"))]
+    #[test]
+    fn generic_udp_ping_os() {
+        let rt = crate::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap();
+
+        let content = rt
+            .block_on(async {
+                let server = UdpSocket::bind("127.0.0.1:0").await?;
+                let addr = server.local_addr()?;
+                drop(server);
+
+                let echo = crate::spawn(echo::<UdpSocket>(addr));
+                crate::task::yield_now().await;
+                let content = ping::<UdpSocket>("127.0.0.1:0".parse().unwrap(), addr).await?;
+                echo.await.unwrap()?;
+                Ok::<_, std::io::Error>(content)
+            })
+            .unwrap();
+        assert_eq!(content, b"ping");
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn generic_udp_ping() {
+        use crate::sim::net::{testutil, IOIntent};
+
+        let (rt, ctx) = testutil::node("generic");
+        let guard = rt.enter_context(ctx);
+
+        let server: SocketAddr = "192.168.2.1:7".parse().unwrap();
+        let client: SocketAddr = "192.168.2.1:1000".parse().unwrap();
+        rt.spawn(echo::<UdpSocket>(server));
+        rt.poll_until_idle();
+        let handle = rt.spawn(ping::<UdpSocket>(client, server));
+
+        for _ in 0..2 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let IOIntent::UdpSendPacket(msg) = intent {
+                    rt.process_udp(msg).unwrap();
+                }
+            }
+        }
+
+        let content = rt.block_or_idle_on(handle).unwrap().unwrap().unwrap();
+        assert_eq!(content, b"ping");
+
+        guard.leave();
+    }
+}
@@ -38,6 +38,9 @@ cfg_net! {
 
     mod udp;
     pub use udp::UdpSocket;
+
+    mod generic;
+    pub use generic::{AsyncTcpStream, AsyncUdpSocket, BoxFuture};
 }
 
 cfg_net_unix! {
@@ -1,5 +1,7 @@
 use crate::future::poll_fn;
 use crate::loom::sync::atomic::AtomicBool;
+#[cfg(feature = "sim")]
+use crate::loom::sync::atomic::AtomicUsize;
 use crate::loom::sync::{Arc, Mutex};
 use crate::park::{Park, Unpark};
 use crate::runtime::context::EnterGuard;
@@ -16,6 +18,8 @@ use std::collections::VecDeque;
 use std::fmt;
 use std::future::Future;
 use std::sync::atomic::Ordering::{AcqRel, Release};
+#[cfg(feature = "sim")]
+use std::sync::atomic::Ordering::Relaxed;
 use std::task::Poll::{Pending, Ready};
 use std::time::Duration;
 
@@ -111,6 +115,14 @@ struct Shared {
 
     /// This scheduler only has one worker.
     worker_metrics: WorkerMetrics,
+
+    /// Total number of task polls.
+    #[cfg(feature = "sim")]
+    task_polls: AtomicUsize,
+
+    /// Number of task polls during the last `poll_until_idle`.
+    #[cfg(feature = "sim")]
+    idle_polls: AtomicUsize,
 }
 
 /// Thread-local context.
@@ -143,6 +155,10 @@ impl BasicScheduler {
                 config,
                 scheduler_metrics: SchedulerMetrics::new(),
                 worker_metrics: WorkerMetrics::new(),
+                #[cfg(feature = "sim")]
+                task_polls: AtomicUsize::new(0),
+                #[cfg(feature = "sim")]
+                idle_polls: AtomicUsize::new(0),
             }),
         };
 
@@ -167,6 +183,11 @@ impl BasicScheduler {
         &self.spawner
     }
 
+    #[cfg(feature = "sim")]
+    pub(super) fn clock(&self) -> crate::time::clock::Clock {
+        self.spawner.shared.handle_inner.clock.clone()
+    }
+
     #[cfg(feature = "sim")]
     pub(super) fn time_handle(&self) -> crate::time::driver::Handle {
         self.spawner
@@ -236,13 +257,42 @@ cfg_sim! {
 
         #[track_caller]
         pub(super) fn poll_until_idle(&self) {
-            loop {
-                if let Some(core) = self.take_core() {
-                    return core.poll_until_idle();
-                } else {
-                    panic!("Could not take core in simulation context");
-                }
+            let shared = &self.spawner.shared;
+            let before = shared.task_polls.load(Relaxed);
+
+            if let Some(core) = self.take_core() {
+                core.poll_until_idle();
+            } else {
+                panic!("Could not take core in simulation context");
             }
+
+            let polls = shared.task_polls.load(Relaxed) - before;
+            shared.idle_polls.store(polls, Relaxed);
+        }
+
+        /// Returns the total number of task polls, and the number of
+        /// task polls during the last `poll_until_idle`.
+        pub(super) fn task_polls(&self) -> (usize, usize) {
+            let shared = &self.spawner.shared;
+            (shared.task_polls.load(Relaxed), shared.idle_polls.load(Relaxed))
+        }
+
+        /// Returns whether any task is scheduled to be polled.
+        pub(super) fn has_ready_tasks(&self) -> bool {
+            let remote = self.spawner.shared.queue.lock()
+                .as_ref()
+                .map_or(false, |queue| !queue.is_empty());
+
+            let local = match self.core.take() {
+                Some(core) => {
+                    let ready = !core.tasks.is_empty();
+                    self.core.set(core);
+                    ready
+                }
+                None => false,
+            };
+
+            remote || local
         }
 
         pub(super) fn block_or_idle_on<F: Future>(&self, future: F) -> Result<F::Output, RuntimeIdle> {
@@ -361,6 +411,8 @@ impl Context {
     /// thread-local context.
     fn run_task<R>(&self, mut core: Box<Core>, f: impl FnOnce() -> R) -> (Box<Core>, R) {
         core.metrics.incr_poll_count();
+        #[cfg(feature = "sim")]
+        self.spawner.shared.task_polls.fetch_add(1, Relaxed);
         self.enter(core, || crate::coop::budget(f))
     }
 
@@ -696,16 +748,17 @@ cfg_sim! {
                             return (core, ());
                         }
 
-                        // Get and increment the current tick
-                        let tick = core.tick;
+                        // Increment the current tick
                         core.tick = core.tick.wrapping_add(1);
 
-                        // if tick % core.spawner.shared.config.global_queue_interval == 0
-                        let entry = if tick % 5 == 0 {
-                            core.spawner.pop().or_else(|| core.tasks.pop_front())
-                        } else {
-                            core.tasks.pop_front().or_else(|| core.spawner.pop())
-                        };
+                        // Tasks scheduled from outside the runtime join the back of
+                        // the local queue. Thereby all ready tasks are polled in FIFO
+                        // order, and a task calling `yield_now` is rescheduled behind
+                        // all tasks that are already ready.
+                        while let Some(task) = core.spawner.pop() {
+                            core.tasks.push_back(task);
+                        }
+                        let entry = core.tasks.pop_front();
 
                         let task = match entry {
                             Some(entry) => entry,
@@ -773,16 +826,14 @@ cfg_sim! {
                             return (core, None);
                         }
 
-                        // Get and increment the current tick
-                        let tick = core.tick;
+                        // Increment the current tick
                         core.tick = core.tick.wrapping_add(1);
 
-                        // if tick % core.spawner.shared.config.global_queue_interval == 0
-                        let entry = if tick % 5 == 0 {
-                            core.spawner.pop().or_else(|| core.tasks.pop_front())
-                        } else {
-                            core.tasks.pop_front().or_else(|| core.spawner.pop())
-                        };
+                        // Poll all ready tasks in FIFO order, as in `poll_until_idle`.
+                        while let Some(task) = core.spawner.pop() {
+                            core.tasks.push_back(task);
+                        }
+                        let entry = core.tasks.pop_front();
 
                         let task = match entry {
                             Some(entry) => entry,
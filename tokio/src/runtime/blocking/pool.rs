@@ -111,7 +111,7 @@ where
     R: Send + 'static,
 {
     let rt = context::current();
-    rt.spawn_blocking(func)
+    rt.as_inner().spawn_blocking(&rt, func)
 }
 
 cfg_fs! {
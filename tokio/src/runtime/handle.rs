@@ -185,6 +185,10 @@ impl Handle {
     /// Runs the provided function on an executor dedicated to blocking.
     /// operations.
     ///
+    /// With feature "sim", the function runs as a task of the runtime instead
+    /// if a [`SimContext`](crate::sim::SimContext) is entered on a current
+    /// thread runtime, so it completes within the current simulation step.
+    ///
     /// # Examples
     ///
     /// ```
@@ -207,6 +211,14 @@ impl Handle {
         F: FnOnce() -> R + Send + 'static,
         R: Send + 'static,
     {
+        // A simulation must not depend on the timing of real threads.
+        #[cfg(feature = "sim")]
+        {
+            if matches!(self.spawner, Spawner::Basic(_)) && crate::sim::SimContext::is_entered() {
+                return self.spawn(async move { func() });
+            }
+        }
+
         self.as_inner().spawn_blocking(self, func)
     }
 
@@ -310,6 +310,7 @@ cfg_rt! {
     }
 
     cfg_sim! {
+        use crate::time::clock::Clock;
         use crate::time::driver::Handle as TimeHandle;
 
         #[allow(unused)]
@@ -322,6 +323,13 @@ cfg_rt! {
                 }
             }
 
+            fn clock(&self) -> Clock {
+                match self {
+                    Self::CurrentThread(ref sched) => sched.clock(),
+                    _ => unreachable!(),
+                }
+            }
+
             fn poll_time_events(&self) {
                 self.time_handle().process_now()
             }
@@ -337,6 +345,20 @@ cfg_rt! {
                 }
             }
 
+            fn task_polls(&self) -> (usize, usize) {
+                match self {
+                    Self::CurrentThread(ref sched) => sched.task_polls(),
+                    _ => unreachable!()
+                }
+            }
+
+            fn has_ready_tasks(&self) -> bool {
+                match self {
+                    Self::CurrentThread(ref sched) => sched.has_ready_tasks(),
+                    _ => unreachable!()
+                }
+            }
+
             fn block_or_idle_on<F: Future>(&self, f: F) -> Result<F::Output, RuntimeIdle> {
                 match self {
                     Self::CurrentThread(ref sched) => sched.block_or_idle_on(f),
@@ -640,7 +662,7 @@ cfg_rt! {
     }
 
     cfg_sim! {
-        use crate::sim::{SimContext, time::SimTime, net::{UdpMessage, TcpMessage, TcpConnectMessage}};
+        use crate::sim::{SimBarrier, SimContext, SimMetrics, time::SimTime, net::{UdpMessage, TcpMessage, TcpConnectMessage}};
         use basic_scheduler::RuntimeIdle;
 
         ///
@@ -727,6 +749,15 @@ cfg_rt! {
                 })
             }
 
+            /// Processes the notification that a send UDP packet was not delivered.
+            pub fn process_udp_unreachable(&self, msg: UdpMessage) -> Result<(), UdpMessage> {
+                use crate::sim::net::IOContext;
+
+                IOContext::with_current(|ctx| {
+                    ctx.process_udp_unreachable(msg)
+                })
+            }
+
             /// Processes an arriving UDP packet.
             #[must_use]
             pub fn process_tcp_connect(&self, msg: TcpConnectMessage) -> Result<(), TcpConnectMessage> {
@@ -758,6 +789,15 @@ cfg_rt! {
                 })
             }
 
+            /// Processes a TCP packet, send by this node, that found no receiving stream.
+            pub fn synthesize_rst(&self, msg: TcpMessage) -> Result<(), TcpMessage> {
+                use crate::sim::net::IOContext;
+
+                IOContext::with_current(|ctx| {
+                    ctx.synthesize_rst(msg)
+                })
+            }
+
             /// Processes an IO tick.
             pub fn io_tick(&self) {
                 use crate::sim::net::IOContext;
@@ -789,10 +829,129 @@ cfg_rt! {
                 ret
             }
 
+            ///
+            /// Returns the simulation time at which the time driver
+            /// of this runtime was created.
+            ///
+            #[must_use]
+            pub fn start_time(&self) -> SimTime {
+                self.kind.time_handle().start_time()
+            }
+
+            ///
+            /// Returns the simulation time elapsed since the time driver
+            /// of this runtime was created.
+            ///
+            #[must_use]
+            pub fn elapsed(&self) -> Duration {
+                self.kind.time_handle().elapsed()
+            }
+
+            ///
+            /// Sets the rate at which [`advance`](Runtime::advance) moves the
+            /// simulation time.
+            ///
+            /// With a rate of `2.0`, advancing by one second moves the simulation
+            /// time by two seconds, firing all timers in between. The rate does not
+            /// apply to times set explicitly using [`SimTime::set_now`], nor to the
+            /// deadlines of steps scheduled on a [`SimScheduler`](crate::sim::SimScheduler).
+            /// The rate defaults to `1.0`.
+            ///
+            /// # Panics
+            ///
+            /// This function panics if `rate` is not a positive, finite number.
+            ///
+            pub fn set_clock_rate(&self, rate: f64) {
+                self.kind.clock().set_rate(rate)
+            }
+
+            ///
+            /// Returns the rate at which [`advance`](Runtime::advance) moves the
+            /// simulation time.
+            ///
+            #[must_use]
+            pub fn clock_rate(&self) -> f64 {
+                self.kind.clock().rate()
+            }
+
+            ///
+            /// Advances the simulation time by `duration`, firing all timers
+            /// in between.
+            ///
+            /// Timers are processed one deadline at a time, in order. After each
+            /// deadline the local scheduler is polled until idle, so that tasks
+            /// woken by an earlier timer run before a later timer fires.
+            ///
+            /// The duration is scaled by the [clock rate](Runtime::set_clock_rate).
+            ///
+            pub fn advance(&self, duration: Duration) {
+                let duration = self.kind.clock().scale(duration);
+                self.advance_to(SimTime::now() + duration);
+            }
+
+            ///
+            /// Advances the simulation time to `target`, firing all timers
+            /// in between. In contrast to `advance`, the clock rate does not apply.
+            ///
+            pub(crate) fn advance_to(&self, target: SimTime) {
+                self.poll_until_idle();
+                while SimContext::with_current(|ctx| {
+                    ctx.time.as_ref().and_then(|time| time.process_up_to(target))
+                }).is_some() {
+                    self.poll_until_idle();
+                }
+
+                SimTime::set_now(target);
+                self.poll_until_idle();
+            }
+
+            ///
+            /// Runs all remaining timers to completion, regardless of their deadline,
+            /// returning the final simulation time.
+            ///
+            /// The simulation time is advanced from deadline to deadline, like
+            /// [`advance`](Runtime::advance), until no timers remain. Timers registered
+            /// by woken tasks are run as well, so this call does not return while a
+            /// periodic timer, like an `Interval`, is still in use. Use
+            /// [`run_all_timers_until`](Runtime::run_all_timers_until) in that case.
+            ///
+            pub fn run_all_timers(&self) -> SimTime {
+                self.run_all_timers_until(SimTime::MAX);
+                SimTime::now()
+            }
+
+            ///
+            /// Runs all timers with a deadline up to `limit`, returning whether
+            /// timers remain.
+            ///
+            /// Like [`run_all_timers`](Runtime::run_all_timers), timers registered
+            /// by woken tasks are run as well, as long as their deadline does not
+            /// exceed `limit`. The simulation time is left at the deadline of the
+            /// last timer that was run.
+            ///
+            pub fn run_all_timers_until(&self, limit: SimTime) -> bool {
+                loop {
+                    self.poll_until_idle();
+                    let next = SimContext::with_current(|ctx| {
+                        ctx.time.as_ref().and_then(|time| time.next_time_poll())
+                    });
+                    match next {
+                        Some(next) if next <= limit => self.advance_to(next.max(SimTime::now())),
+                        Some(_) => return true,
+                        None => return false,
+                    }
+                }
+            }
+
             ///
             /// Polls all tasks on the local scheduler until all of them
             /// are either completed or idle without chance of further progress.
             ///
+            /// Closures passed to `spawn_blocking` run as tasks of the local
+            /// scheduler, so they have completed once this call returns.
+            /// Simulations are only supported on the current-thread runtime,
+            /// there is no pool of blocking threads to wait for.
+            ///
             pub fn poll_until_idle(&self) {
                 let _enter = self.enter();
                 self.kind.poll_until_idle();
@@ -800,6 +959,107 @@ cfg_rt! {
                 drop(_enter);
             }
 
+            ///
+            /// Polls all tasks until idle and collects all items that are
+            /// currently available on the given channel, in order.
+            ///
+            /// This call does not wait for further items, so an empty result
+            /// means that nothing was produced in this step.
+            ///
+            pub fn drain<T>(&self, rx: &mut crate::sync::mpsc::UnboundedReceiver<T>) -> Vec<T> {
+                self.poll_until_idle();
+
+                let mut items = Vec::new();
+                while let Ok(item) = rx.try_recv() {
+                    items.push(item);
+                }
+                items
+            }
+
+            ///
+            /// Polls all tasks until idle and collects all items that are
+            /// currently available on the given bounded channel, in order.
+            ///
+            /// Since draining frees capacity, senders blocked on a full channel
+            /// are polled again, so that their items are collected as well.
+            ///
+            pub fn drain_bounded<T>(&self, rx: &mut crate::sync::mpsc::Receiver<T>) -> Vec<T> {
+                let mut items = Vec::new();
+                loop {
+                    self.poll_until_idle();
+
+                    let len = items.len();
+                    while let Ok(item) = rx.try_recv() {
+                        items.push(item);
+                    }
+
+                    if items.len() == len {
+                        return items;
+                    }
+                }
+            }
+
+            ///
+            /// Polls all tasks until idle and returns whether all participants
+            /// of the barrier rendezvoused in the meantime.
+            ///
+            /// If this returns `false`, the runtime is idle while some participants
+            /// are still missing. Use [`SimBarrier::arrived`] to inspect how many
+            /// tasks are waiting.
+            ///
+            pub fn run_until_barrier(&self, barrier: &SimBarrier) -> bool {
+                let completed = barrier.completed();
+                self.poll_until_idle();
+                barrier.completed() > completed
+            }
+
+            ///
+            /// Returns a snapshot of the timer and task counters of the runtime.
+            ///
+            /// The timer counters refer to the currently entered [`SimContext`].
+            ///
+            pub fn sim_metrics(&self) -> SimMetrics {
+                let (timer_slots, timers_fired) = SimContext::with_current(|ctx| {
+                    ctx.time
+                        .as_ref()
+                        .map_or((0, 0), |time| (time.pending_slots(), time.timers_fired()))
+                });
+                let (task_polls, idle_polls) = self.kind.task_polls();
+
+                SimMetrics {
+                    timer_slots,
+                    timers_fired,
+                    task_polls,
+                    idle_polls,
+                }
+            }
+
+            ///
+            /// Returns whether the simulation is quiescent, so that no further
+            /// progress is possible without external input.
+            ///
+            /// In contrast to an idle runtime, a quiescent runtime has no pending
+            /// timers in the currently entered [`SimContext`] and no network activity
+            /// that would produce intents. Tasks still waiting in this state will
+            /// only ever continue, if another node sends them data.
+            ///
+            pub fn is_quiescent(&self) -> bool {
+                use crate::sim::net::IOContext;
+
+                if self.kind.has_ready_tasks() {
+                    return false;
+                }
+
+                let timers = SimContext::with_current(|ctx| {
+                    ctx.time.as_ref().and_then(|time| time.next_time_poll())
+                });
+                if timers.is_some() {
+                    return false;
+                }
+
+                !IOContext::try_with_current(|ctx| ctx.has_pending_intents()).unwrap_or(false)
+            }
+
             ///
             /// This call is similar to ``block_on` with the difference
             /// that this call returns an error should all tasks be idle
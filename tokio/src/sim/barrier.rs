@@ -0,0 +1,97 @@
+use crate::sync::{Barrier, BarrierWaitResult};
+
+/// A barrier whose rendezvous can be observed by the simulation harness.
+///
+/// A `SimBarrier` behaves like a [`Barrier`], but additionally exposes how many
+/// tasks have arrived. Since simulated tasks only make progress while the
+/// runtime is polled, the harness can use
+/// [`Runtime::run_until_barrier`](crate::runtime::Runtime::run_until_barrier)
+/// to detect whether all participants reached a checkpoint, without any
+/// time passing.
+///
+/// ```
+/// use tokio::sim::{SimBarrier, SimContext};
+/// use std::sync::Arc;
+///
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// # let guard = rt.enter_context(SimContext::empty().with_time(String::from("barrier")));
+/// let barrier = Arc::new(SimBarrier::new(3));
+/// for _ in 0..3 {
+///     let barrier = barrier.clone();
+///     rt.spawn(async move {
+///         // ...
+///         barrier.wait().await;
+///     });
+/// }
+///
+/// assert!(rt.run_until_barrier(&barrier));
+/// # guard.leave();
+/// ```
+#[derive(Debug)]
+pub struct SimBarrier {
+    inner: Barrier,
+}
+
+impl SimBarrier {
+    /// Creates a new barrier that blocks until `n` tasks called [`wait`](SimBarrier::wait).
+    pub fn new(n: usize) -> SimBarrier {
+        SimBarrier {
+            inner: Barrier::new(n),
+        }
+    }
+
+    /// Does not resolve until all tasks have rendezvoused here.
+    ///
+    /// See [`Barrier::wait`] for details.
+    pub async fn wait(&self) -> BarrierWaitResult {
+        self.inner.wait().await
+    }
+
+    /// Returns the number of tasks that are waiting for the
+    /// remaining participants.
+    pub fn arrived(&self) -> usize {
+        self.inner.arrived()
+    }
+
+    /// Returns how often all participants have rendezvoused.
+    pub fn completed(&self) -> usize {
+        self.inner.completed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sim::SimContext;
+    use std::sync::Arc;
+
+    #[test]
+    fn three_tasks_rendezvous() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("barrier")));
+
+        let barrier = Arc::new(SimBarrier::new(3));
+        let spawn =
+            |barrier: Arc<SimBarrier>| rt.spawn(async move { barrier.wait().await.is_leader() });
+
+        let mut handles = vec![spawn(barrier.clone()), spawn(barrier.clone())];
+        assert!(!rt.run_until_barrier(&barrier));
+        assert_eq!(barrier.arrived(), 2);
+        assert_eq!(barrier.completed(), 0);
+
+        handles.push(spawn(barrier.clone()));
+        assert!(rt.run_until_barrier(&barrier));
+        assert_eq!(barrier.arrived(), 0);
+        assert_eq!(barrier.completed(), 1);
+
+        let leaders = handles
+            .into_iter()
+            .map(|handle| rt.block_or_idle_on(handle).unwrap().unwrap())
+            .filter(|is_leader| *is_leader)
+            .count();
+        assert_eq!(leaders, 1);
+
+        guard.leave();
+    }
+}
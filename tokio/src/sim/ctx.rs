@@ -1,7 +1,11 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 thread_local! {
-    pub(crate) static IOCTX: RefCell<SimContext> = const { RefCell::new(SimContext::empty()) }
+    pub(crate) static IOCTX: RefCell<SimContext> = const { RefCell::new(SimContext::empty()) };
+
+    // The next id of the entered context. It is kept outside of IOCTX,
+    // so that ids can be allocated while the context is borrowed.
+    static IDS: Cell<usize> = const { Cell::new(0) }
 }
 
 /// The IO Contexxt
@@ -13,6 +17,9 @@ pub struct SimContext {
 
     /// The IO Context
     pub time: Option<TimeContext>,
+
+    ids: usize,
+    clock_resolution: std::time::Duration,
 }
 
 impl SimContext {
@@ -23,9 +30,20 @@ impl SimContext {
             io: None,
 
             time: None,
+            ids: 0,
+            clock_resolution: std::time::Duration::from_secs(0),
         }
     }
 
+    /// Returns whether a simulation context was entered on this thread.
+    ///
+    /// A context that is currently borrowed counts as entered.
+    pub(crate) fn is_entered() -> bool {
+        IOCTX
+            .try_with(|c| c.try_borrow().map_or(true, |ctx| ctx.time.is_some()))
+            .unwrap_or(false)
+    }
+
     /// fetch the current context
     pub fn with_current<R>(f: impl FnOnce(&mut SimContext) -> R) -> R {
         IOCTX.with(|v| f(&mut *v.borrow_mut()))
@@ -49,30 +67,144 @@ impl SimContext {
         Self {
             io: Some(IOContext::new(ether, v4)),
             time: None,
+            ids: 0,
+            clock_resolution: std::time::Duration::from_secs(0),
+        }
+    }
+
+    /// Enables or disables debug output of the simulation context.
+    ///
+    /// Debug output is disabled by default.
+    #[cfg(feature = "net")]
+    pub fn set_debug(&mut self, debug: bool) {
+        if let Some(io) = self.io.as_mut() {
+            io.set_debug(debug);
         }
     }
 
+    /// Sets the resolution of the clock read by [`SimTime::quantized_now`].
+    ///
+    /// The simulation time itself still advances continuously, but code
+    /// reading the quantized clock observes it in steps of `resolution`.
+    /// A resolution of zero disables the quantization, which is the default.
+    pub fn set_clock_resolution(&mut self, resolution: std::time::Duration) {
+        self.clock_resolution = resolution;
+    }
+
+    /// Returns the resolution of the clock read by [`SimTime::quantized_now`].
+    pub fn clock_resolution(&self) -> std::time::Duration {
+        self.clock_resolution
+    }
+
     /// Resets the SimContext after module restart.
     pub fn reset(&mut self) {
         self.io.as_mut().map(|io| io.reset());
         self.time.as_mut().map(|time| time.reset());
+        self.ids = 0;
+        if self.is_current() {
+            IDS.with(|ids| ids.set(0));
+        }
+    }
+
+    /// Allocates a new id, unique within the current context.
+    ///
+    /// Ids are allocated in increasing order, starting at 0 for a new
+    /// context and after [`reset`](SimContext::reset). Thereby simulated
+    /// entities like timers and TCP connections are identified
+    /// deterministically, even if multiple simulations run within the
+    /// same process.
+    ///
+    /// In contrast to [`with_current`](SimContext::with_current), this
+    /// does not borrow the current context, so it can be called anywhere.
+    pub fn next_id() -> usize {
+        IDS.with(|ids| {
+            let id = ids.get();
+            ids.set(id + 1);
+            id
+        })
+    }
+
+    fn is_current(&self) -> bool {
+        IOCTX
+            .try_with(|c| std::ptr::eq(c.as_ptr(), self))
+            .unwrap_or(false)
+    }
+
+    /// Captures the state of the context, together with [`SimTime::now`].
+    ///
+    /// The snapshot contains the registered sockets, their buffers and the
+    /// deadlines of all pending timers, but not the wakers of waiting tasks.
+    pub fn snapshot(&self) -> SimSnapshot {
+        SimSnapshot {
+            now: SimTime::now(),
+
+            #[cfg(feature = "net")]
+            io: self.io.as_ref().map(|io| io.snapshot()),
+
+            time: self.time.as_ref().map(|time| time.snapshot()),
+        }
+    }
+
+    /// Restores a state captured by [`snapshot`](SimContext::snapshot).
+    ///
+    /// Timers are re-armed at their deadlines, but wakers cannot be
+    /// restored. Instead all tasks waiting on the current state are woken,
+    /// so that they register themselves again once polled.
+    pub fn restore(&mut self, snapshot: &SimSnapshot) {
+        SimTime::set_now(snapshot.now);
+
+        #[cfg(feature = "net")]
+        if let (Some(io), Some(snapshot)) = (self.io.as_mut(), snapshot.io.as_ref()) {
+            io.restore(snapshot);
+        }
+
+        if let (Some(time), Some(snapshot)) = (self.time.as_ref(), snapshot.time.as_ref()) {
+            time.restore(snapshot);
+        }
     }
 
     /// Swaps out the current context
     pub(crate) fn swap(other: &mut SimContext) {
         IOCTX.with(|c| {
-            std::mem::swap(other, &mut *c.borrow_mut());
+            let current = &mut *c.borrow_mut();
+            std::mem::swap(other, current);
+
+            // The id allocator moves with the context.
+            other.ids = IDS.with(|ids| ids.replace(current.ids));
         });
     }
 }
 
+/// The state of a [`SimContext`] at a point in time.
+///
+/// Created by [`SimContext::snapshot`] and applied with [`SimContext::restore`].
+/// A snapshot can be restored multiple times, to explore alternate futures
+/// from the same state.
+#[derive(Debug)]
+pub struct SimSnapshot {
+    now: SimTime,
+
+    #[cfg(feature = "net")]
+    io: Option<IOContext>,
+
+    time: Option<TimerQueueSnapshot>,
+}
+
+impl SimSnapshot {
+    /// The simulation time at which the snapshot was taken.
+    pub fn time(&self) -> SimTime {
+        self.now
+    }
+}
+
 cfg_net! {
     use super::net::IOContext;
     use std::net::Ipv4Addr;
 }
 
 cfg_time! {
-    use super::time::TimeContext;
+    use super::time::driver::TimerQueueSnapshot;
+    use super::time::{SimTime, TimeContext};
 }
 
 use std::fmt;
@@ -102,3 +234,273 @@ impl fmt::Display for SimContext {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sim::net::{testutil, UdpSocket};
+    use crate::sync::mpsc;
+    use crate::time::{sleep, Duration, SimTime};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn drain_channel_pipeline() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("drain")));
+
+        let (up_tx, mut up_rx) = mpsc::unbounded_channel::<usize>();
+        let (down_tx, mut down_rx) = mpsc::unbounded_channel();
+        rt.spawn(async move {
+            while let Some(v) = up_rx.recv().await {
+                down_tx.send(v * 2).unwrap();
+            }
+        });
+
+        assert!(rt.drain(&mut down_rx).is_empty());
+
+        for i in 0..5 {
+            up_tx.send(i).unwrap();
+        }
+        assert_eq!(rt.drain(&mut down_rx), vec![0, 2, 4, 6, 8]);
+        assert!(rt.drain(&mut down_rx).is_empty());
+
+        up_tx.send(21).unwrap();
+        assert_eq!(rt.drain(&mut down_rx), vec![42]);
+
+        let (tx, mut rx) = mpsc::channel(2);
+        rt.spawn(async move {
+            for i in 0..5 {
+                tx.send(i).await.unwrap();
+            }
+        });
+        assert_eq!(rt.drain_bounded(&mut rx), vec![0, 1, 2, 3, 4]);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn restore_discards_send() {
+        let (rt, ctx) = testutil::node("snapshot");
+        let guard = rt.enter_context(ctx);
+
+        let sleeper = rt.spawn(async {
+            sleep(Duration::from_secs(5)).await;
+            SimTime::now()
+        });
+        let socket = rt.spawn(async { UdpSocket::bind("192.168.2.1:100").await.unwrap() });
+        let socket = rt.block_or_idle_on(socket).unwrap().unwrap();
+        let snapshot = SimContext::with_current(|ctx| ctx.snapshot());
+
+        rt.advance(Duration::from_secs(1));
+        let send = rt.spawn(async move {
+            socket.send_to(b"lost", "192.168.2.2:100").await.unwrap();
+            socket
+        });
+        let socket = rt.block_or_idle_on(send).unwrap().unwrap();
+
+        SimContext::with_current(|ctx| ctx.restore(&snapshot));
+        assert!(rt.yield_intents().is_empty());
+        assert_eq!(SimTime::now(), snapshot.time());
+        let next = SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().next_time_poll());
+        assert_eq!(next, Some(SimTime::from_secs(5)));
+
+        // The restored socket can still be used.
+        let send = rt.spawn(async move {
+            socket.send_to(b"sent", "192.168.2.2:100").await.unwrap();
+        });
+        rt.block_or_idle_on(send).unwrap().unwrap();
+        assert_eq!(rt.yield_intents().len(), 1);
+
+        rt.advance(Duration::from_secs(5));
+        let woken = rt.block_or_idle_on(sleeper).unwrap().unwrap();
+        assert_eq!(woken, SimTime::from_secs(5));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn quiescent_vs_waiting_on_timer() {
+        let (rt, ctx) = testutil::node("quiescent");
+        let guard = rt.enter_context(ctx);
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+        let task = rt.spawn(async move {
+            sleep(Duration::from_secs(1)).await;
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.send_to(b"ping", "192.168.2.2:100").await.unwrap();
+            rx.recv().await
+        });
+        assert!(!rt.is_quiescent());
+
+        // Idle, but waiting on a timer.
+        rt.poll_until_idle();
+        assert!(!rt.is_quiescent());
+
+        // Idle, but the datagram was not yet yielded.
+        rt.advance(Duration::from_secs(1));
+        assert!(!rt.is_quiescent());
+        assert_eq!(rt.yield_intents().len(), 1);
+
+        // Only external input can wake the task.
+        assert!(rt.is_quiescent());
+        tx.send(()).unwrap();
+        assert!(!rt.is_quiescent());
+        assert_eq!(rt.block_or_idle_on(task).unwrap().unwrap(), Some(()));
+        assert!(rt.is_quiescent());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn ids_restart_per_simulation() {
+        for ident in &["first", "second"] {
+            let rt = Builder::new_current_thread().enable_time().build().unwrap();
+            let ctx = SimContext::empty().with_time(String::from(*ident));
+            let guard = rt.enter_context(ctx);
+
+            let ids = (0..3)
+                .map(|_| sleep(Duration::from_secs(1)).id)
+                .collect::<Vec<_>>();
+            assert_eq!(ids, vec![0, 1, 2]);
+
+            let mut ctx = guard.leave();
+            ctx.reset();
+
+            // Ids can be allocated while the context is borrowed.
+            let guard = rt.enter_context(ctx);
+            let id = SimContext::with_current(|_| sleep(Duration::from_secs(1)).id);
+            assert_eq!(id, 0);
+            guard.leave();
+        }
+    }
+
+    #[test]
+    fn yield_now_interleaves_tasks() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("yield")));
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for name in ['a', 'b'] {
+            let log = log.clone();
+            rt.spawn(async move {
+                for i in 0..4 {
+                    log.lock().unwrap().push((name, i));
+                    crate::task::yield_now().await;
+                }
+            });
+        }
+
+        rt.poll_until_idle();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ('a', 0),
+                ('b', 0),
+                ('a', 1),
+                ('b', 1),
+                ('a', 2),
+                ('b', 2),
+                ('a', 3),
+                ('b', 3)
+            ]
+        );
+        // Yielding does not advance the simulation time.
+        assert_eq!(SimTime::now(), SimTime::ZERO);
+        assert_eq!(rt.sim_metrics().idle_polls, 10);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn spawn_blocking_completes_in_step() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("blocking")));
+
+        let handle = rt.spawn(async {
+            let sum = crate::task::spawn_blocking(|| (1..=100u32).sum::<u32>())
+                .await
+                .unwrap();
+            let panicked = crate::task::spawn_blocking(|| panic!("blocking"))
+                .await
+                .unwrap_err();
+            (sum, panicked.is_panic())
+        });
+        rt.poll_until_idle();
+
+        // Both closures ran within a single step, without advancing the time.
+        assert_eq!(rt.block_or_idle_on(handle).unwrap().unwrap(), (5050, true));
+        assert_eq!(SimTime::now(), SimTime::ZERO);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn spawn_blocking_without_context_uses_threads() {
+        let rt = Builder::new_current_thread().build().unwrap();
+
+        // Without a simulation, closures still run on the blocking pool.
+        let caller = std::thread::current().id();
+        let thread = rt.block_on(async {
+            crate::task::spawn_blocking(|| std::thread::current().id())
+                .await
+                .unwrap()
+        });
+        assert_ne!(thread, caller);
+    }
+
+    #[test]
+    fn poll_until_idle_completes_blocking_work() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("blocking")));
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let task_log = log.clone();
+        rt.spawn(async move {
+            // The handle is dropped, so nothing awaits the closure.
+            let _ = crate::task::spawn_blocking(move || task_log.lock().unwrap().push("task"));
+        });
+        let rt_log = log.clone();
+        let _ = rt.spawn_blocking(move || rt_log.lock().unwrap().push("runtime"));
+
+        // The closure spawned by the task is queued behind the other one.
+        rt.poll_until_idle();
+        assert_eq!(*log.lock().unwrap(), vec!["runtime", "task"]);
+        assert!(rt.is_quiescent());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn quantized_clock_steps() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let mut ctx = SimContext::empty().with_time(String::from("resolution"));
+        ctx.set_clock_resolution(Duration::from_millis(1));
+        let guard = rt.enter_context(ctx);
+        let start = SimTime::now().truncate_to(Duration::from_millis(1));
+
+        let readings = Arc::new(Mutex::new(Vec::new()));
+        let log = readings.clone();
+        rt.spawn(async move {
+            for _ in 0..5 {
+                log.lock().unwrap().push(SimTime::quantized_now());
+                sleep(Duration::from_micros(400)).await;
+            }
+        });
+        rt.run_all_timers();
+
+        // The readings at 0us, 400us and 800us fall into the same window.
+        let ms = |ms| start + Duration::from_millis(ms);
+        assert_eq!(
+            *readings.lock().unwrap(),
+            vec![ms(0), ms(0), ms(0), ms(1), ms(1)]
+        );
+        assert_eq!(SimTime::now(), start + Duration::from_micros(2000));
+
+        let mut ctx = guard.leave();
+        ctx.set_clock_resolution(Duration::from_secs(0));
+        let guard = rt.enter_context(ctx);
+        assert_eq!(SimTime::quantized_now(), SimTime::now());
+        guard.leave();
+    }
+}
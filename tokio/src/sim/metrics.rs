@@ -0,0 +1,251 @@
+//! Measurements of simulated runtimes and scenarios.
+
+use std::time::Duration;
+
+/// A snapshot of the internal counters of a simulated runtime.
+///
+/// Created by [`Runtime::sim_metrics`](crate::runtime::Runtime::sim_metrics).
+/// The timer counters refer to the time context of the currently entered
+/// [`SimContext`](super::SimContext), the task counters to the runtime itself.
+/// Comparing two snapshots helps to diagnose slow scenarios, e.g. a timer
+/// explosion or tasks that are woken without making progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SimMetrics {
+    /// The number of distinct pending timer deadlines.
+    pub timer_slots: usize,
+    /// The total number of timers fired.
+    pub timers_fired: usize,
+    /// The total number of task polls.
+    pub task_polls: usize,
+    /// The number of task polls during the last call to
+    /// [`Runtime::poll_until_idle`](crate::runtime::Runtime::poll_until_idle).
+    pub idle_polls: usize,
+}
+
+/// The number of linear sub-buckets per power of two.
+const SUB_BUCKETS: u64 = 8;
+const SUB_BITS: u32 = 3;
+
+/// A histogram of durations with logarithmic buckets.
+///
+/// Each power of two is split into eight linear buckets, so that a
+/// recorded duration is reported with a relative error of at most 12.5%.
+/// Durations below 16ns, as well as the smallest and largest recorded
+/// duration, are kept exactly.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tokio::net::UdpSocket;
+/// use tokio::sim::metrics::Histogram;
+/// use tokio::time::{measure, Duration};
+///
+/// # async fn dox(socket: UdpSocket) -> std::io::Result<()> {
+/// let mut rtts = Histogram::new();
+/// let mut buf = [0; 64];
+/// for _ in 0..1000 {
+///     socket.send(b"ping").await?;
+///     let (result, rtt) = measure(socket.recv(&mut buf)).await;
+///     result?;
+///     rtts.record(rtt);
+/// }
+/// assert!(rtts.percentile(99.0).unwrap() < Duration::from_millis(25));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    /// Creates an empty histogram.
+    pub fn new() -> Histogram {
+        Histogram::default()
+    }
+
+    /// Records a single duration.
+    ///
+    /// Durations that exceed `u64::MAX` nanoseconds are recorded as such.
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = duration
+            .as_secs()
+            .checked_mul(1_000_000_000)
+            .and_then(|n| n.checked_add(u64::from(duration.subsec_nanos())))
+            .unwrap_or(u64::MAX);
+
+        let idx = bucket_index(nanos);
+        if self.buckets.len() <= idx {
+            self.buckets.resize(idx + 1, 0);
+        }
+        self.buckets[idx] += 1;
+
+        self.min = if self.count == 0 {
+            nanos
+        } else {
+            self.min.min(nanos)
+        };
+        self.max = self.max.max(nanos);
+        self.count += 1;
+    }
+
+    /// Returns the number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the smallest recorded duration.
+    pub fn min(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_nanos(self.min))
+        }
+    }
+
+    /// Returns the largest recorded duration.
+    pub fn max(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_nanos(self.max))
+        }
+    }
+
+    /// Returns the duration below or at which `p` percent of all
+    /// recorded durations lie, or `None` if the histogram is empty.
+    ///
+    /// The result is the upper bound of the bucket containing the
+    /// percentile, limited to the largest recorded duration. `p` is
+    /// clamped to the range `0.0..=100.0`.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let p = if p.is_nan() {
+            0.0
+        } else {
+            p.max(0.0).min(100.0)
+        };
+        let rank = ((p / 100.0) * self.count as f64).ceil() as u64;
+        let rank = rank.max(1).min(self.count);
+
+        let mut seen = 0;
+        for (idx, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                let upper = bucket_upper(idx).min(self.max).max(self.min);
+                return Some(Duration::from_nanos(upper));
+            }
+        }
+        unreachable!("the ranks are bounded by the number of records")
+    }
+}
+
+fn bucket_index(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS {
+        return nanos as usize;
+    }
+
+    // Buckets are grouped by the position of the highest bit, and split
+    // by the following bits.
+    let exp = 63 - nanos.leading_zeros();
+    let shift = exp - SUB_BITS;
+    let sub = (nanos >> shift) & (SUB_BUCKETS - 1);
+    ((u64::from(shift) + 1) * SUB_BUCKETS + sub) as usize
+}
+
+/// Returns the largest duration in nanoseconds, that falls into the bucket.
+fn bucket_upper(idx: usize) -> u64 {
+    let idx = idx as u64;
+    if idx < SUB_BUCKETS {
+        return idx;
+    }
+
+    let shift = idx / SUB_BUCKETS - 1;
+    let sub = idx % SUB_BUCKETS;
+    let lower = (SUB_BUCKETS + sub) << shift;
+    lower.saturating_add((1 << shift) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sim::SimContext;
+    use crate::time::sleep;
+
+    #[test]
+    fn count_timers_and_polls() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("metrics")));
+
+        for i in 1..=5 {
+            rt.spawn(async move {
+                sleep(Duration::from_secs(i)).await;
+                sleep(Duration::from_secs(10)).await;
+            });
+        }
+        rt.poll_until_idle();
+
+        let before = rt.sim_metrics();
+        assert_eq!(before.timer_slots, 5);
+        assert_eq!(before.idle_polls, 5);
+
+        // Fires the first sleep of each task.
+        rt.advance(Duration::from_secs(5));
+        let after = rt.sim_metrics();
+        assert_eq!(after.timers_fired - before.timers_fired, 5);
+        assert_eq!(after.task_polls - before.task_polls, 5);
+        assert_eq!(after.timer_slots, 5);
+
+        // Fires the second sleep of each task.
+        rt.advance(Duration::from_secs(100));
+        let end = rt.sim_metrics();
+        assert_eq!(end.timers_fired - after.timers_fired, 5);
+        assert_eq!(end.timer_slots, 0);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn histogram_percentiles() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(50.0), None);
+
+        // Small durations are kept exactly.
+        for nanos in (1..=10).rev() {
+            histogram.record(Duration::from_nanos(nanos));
+        }
+        assert_eq!(histogram.count(), 10);
+        assert_eq!(histogram.percentile(0.0), Some(Duration::from_nanos(1)));
+        assert_eq!(histogram.percentile(50.0), Some(Duration::from_nanos(5)));
+        assert_eq!(histogram.percentile(90.0), Some(Duration::from_nanos(9)));
+        assert_eq!(histogram.percentile(100.0), Some(Duration::from_nanos(10)));
+
+        // Larger durations are reported with a relative error of at most 12.5%.
+        let mut histogram = Histogram::new();
+        for ms in 1..=100 {
+            histogram.record(Duration::from_millis(ms));
+        }
+        for &(p, ms) in &[(50.0, 50), (90.0, 90), (99.0, 99)] {
+            let exact = Duration::from_millis(ms);
+            let reported = histogram.percentile(p).unwrap();
+            assert!(reported >= exact, "p{}: {:?}", p, reported);
+            assert!(reported <= exact + exact / 8, "p{}: {:?}", p, reported);
+        }
+        assert_eq!(histogram.percentile(100.0), histogram.max());
+        assert_eq!(histogram.max(), Some(Duration::from_millis(100)));
+        assert_eq!(histogram.min(), Some(Duration::from_millis(1)));
+
+        // Bucket bounds are continuous.
+        for idx in 1..(64 * SUB_BUCKETS as usize - 16) {
+            assert_eq!(bucket_index(bucket_upper(idx - 1) + 1), idx);
+            assert_eq!(bucket_index(bucket_upper(idx)), idx);
+        }
+    }
+}
@@ -12,3 +12,15 @@ cfg_net! {
 
 mod ctx;
 pub use ctx::*;
+
+mod barrier;
+pub use barrier::SimBarrier;
+
+mod scheduler;
+pub use scheduler::SimScheduler;
+
+mod scenario;
+pub use scenario::{Scenario, ScenarioStep};
+
+pub mod metrics;
+pub use metrics::SimMetrics;
@@ -176,6 +176,13 @@ cfg_net! {
                 return MaybeReady(sealed::State::Ready(Some(addr)));
             }
 
+            // Then check the hosts of the simulation context
+            if let Some((host, port)) = split_host_port(self) {
+                if let Some(addrs) = lookup_sim_host(host, port) {
+                    return MaybeReady(sealed::State::Resolved(addrs.into_iter()));
+                }
+            }
+
             // Run DNS lookup on the blocking pool
             let s = self.to_owned();
 
@@ -214,6 +221,10 @@ cfg_net! {
                 return MaybeReady(sealed::State::Ready(Some(addr)));
             }
 
+            if let Some(addrs) = lookup_sim_host(host, port) {
+                return MaybeReady(sealed::State::Resolved(addrs.into_iter()));
+            }
+
             let host = host.to_owned();
 
             MaybeReady(sealed::State::Blocking(spawn_blocking(move || {
@@ -222,6 +233,18 @@ cfg_net! {
         }
     }
 
+    // Splits a "host:port" string, as required for DNS lookups.
+    fn split_host_port(s: &str) -> Option<(&str, u16)> {
+        let idx = s.rfind(':')?;
+        let port = s[idx + 1..].parse().ok()?;
+        Some((&s[..idx], port))
+    }
+
+    // Resolves the host using the hosts of the current IO Context, if any.
+    fn lookup_sim_host(host: &str, port: u16) -> Option<Vec<SocketAddr>> {
+        super::IOContext::try_with_current(|ctx| ctx.lookup(host, port)).flatten()
+    }
+
     // ===== impl (String, u16) =====
 
     impl ToSocketAddrs for (String, u16) {}
@@ -284,6 +307,7 @@ pub(crate) mod sealed {
         #[derive(Debug)]
         pub(super) enum State {
             Ready(Option<SocketAddr>),
+            Resolved(vec::IntoIter<SocketAddr>),
             Blocking(JoinHandle<io::Result<vec::IntoIter<SocketAddr>>>),
         }
 
@@ -303,6 +327,10 @@ pub(crate) mod sealed {
                         let iter = OneOrMore::One(i.take().into_iter());
                         Poll::Ready(Ok(iter))
                     }
+                    State::Resolved(ref mut i) => {
+                        let iter = std::mem::replace(i, Vec::new().into_iter());
+                        Poll::Ready(Ok(OneOrMore::More(iter)))
+                    }
                     State::Blocking(ref mut rx) => {
                         let res = ready!(Pin::new(rx).poll(cx))?.map(OneOrMore::More);
 
@@ -1,11 +1,19 @@
 use crate::io::ReadBuf;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct SocketIncomingBuffer {
     buffers: VecDeque<PartialBuffer>,
     len: usize,
     limit: usize,
+
+    /// The stream offset of the next byte that is expected.
+    next_seq: u64,
+    /// Segments that arrived before all prior data was received.
+    early: BTreeMap<u64, Vec<u8>>,
+    /// Whether no further data will be added, so that reads on an
+    /// empty buffer signal EOF.
+    sealed: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -26,10 +34,62 @@ impl SocketIncomingBuffer {
             buffers: VecDeque::with_capacity(8),
             len: 0,
             limit: limit as usize,
+
+            next_seq: 0,
+            early: BTreeMap::new(),
+            sealed: false,
+        }
+    }
+
+    /// Marks the end of the data, once the peer closed its end or the
+    /// read side was shut down.
+    pub(crate) fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Returns whether the buffer was sealed.
+    pub(crate) fn is_sealed(&self) -> bool {
+        self.sealed
+    }
+
+    /// Returns the stream offset up to which all data was received.
+    pub(crate) fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Adds a segment starting at the stream offset `seq`.
+    ///
+    /// Segments are reassembled in stream order. Segments that arrive
+    /// early are held back until the gap before them is filled, while
+    /// already received data is discarded.
+    pub(crate) fn insert(&mut self, seq: u64, buf: Vec<u8>) {
+        if seq > self.next_seq {
+            self.early.insert(seq, buf);
+            return;
+        }
+        self.append(seq, buf);
+
+        while let Some(&seq) = self.early.keys().next() {
+            if seq > self.next_seq {
+                break;
+            }
+            let buf = self.early.remove(&seq).unwrap();
+            self.append(seq, buf);
+        }
+    }
+
+    fn append(&mut self, seq: u64, mut buf: Vec<u8>) {
+        let end = seq + buf.len() as u64;
+        if end <= self.next_seq {
+            return;
         }
+
+        buf.drain(..(self.next_seq - seq) as usize);
+        self.next_seq = end;
+        self.add(buf);
     }
 
-    pub(crate) fn add(&mut self, buf: Vec<u8>) {
+    fn add(&mut self, buf: Vec<u8>) {
         self.len += buf.len();
         if self.len > self.limit {
             // TODO: Temprarily this will not be active but soon
@@ -50,9 +110,18 @@ impl SocketIncomingBuffer {
         self.buffers.is_empty()
     }
 
-    /// Returns the number of bytes still missing
-    pub(crate) fn read_buf(&mut self, buf: &mut ReadBuf<'_>) -> usize {
-        let mut required = buf.remaining();
+    pub(crate) fn clear(&mut self) {
+        self.buffers.clear();
+        self.early.clear();
+        self.len = 0;
+    }
+
+    /// Returns the number of bytes read, or `None` if no data is available yet.
+    ///
+    /// Reading nothing from a sealed buffer returns `Some(0)`, signaling EOF.
+    pub(crate) fn read_buf(&mut self, buf: &mut ReadBuf<'_>) -> Option<usize> {
+        let requested = buf.remaining();
+        let mut required = requested;
 
         while !self.buffers.is_empty() && required > 0 {
             let n = required.min(self.buffers[0].remaining());
@@ -68,11 +137,13 @@ impl SocketIncomingBuffer {
             required -= n;
         }
 
-        required
+        self.eof_or_read(requested - required)
     }
 
-    /// Returns the number of bytes read
-    pub(crate) fn read(&mut self, buf: &mut [u8]) -> usize {
+    /// Returns the number of bytes read, or `None` if no data is available yet.
+    ///
+    /// Reading nothing from a sealed buffer returns `Some(0)`, signaling EOF.
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
         let mut required = buf.len();
         let mut offset = 0;
 
@@ -93,7 +164,15 @@ impl SocketIncomingBuffer {
             offset += n;
         }
 
-        buf.len() - required
+        self.eof_or_read(buf.len() - required)
+    }
+
+    fn eof_or_read(&self, n: usize) -> Option<usize> {
+        if n > 0 || self.sealed {
+            Some(n)
+        } else {
+            None
+        }
     }
 
     pub(crate) fn peek(&mut self, buf: &mut [u8]) -> usize {
@@ -118,6 +197,17 @@ impl SocketIncomingBuffer {
 
         buf.len() - required
     }
+
+    cfg_test_util! {
+        /// Returns all queued bytes, without consuming them.
+        pub(crate) fn peek_all(&self) -> Vec<u8> {
+            let mut bytes = Vec::with_capacity(self.len);
+            for buffer in &self.buffers {
+                bytes.extend_from_slice(&buffer.buffer[buffer.consumed..]);
+            }
+            bytes
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -125,33 +215,43 @@ pub(crate) struct SocketOutgoingBuffer {
     packets: Vec<Vec<u8>>,
     len: usize,
     limit: usize,
+    mss: usize,
+
+    /// The stream offset of the next yielded byte.
+    next_seq: u64,
 }
 
 impl SocketOutgoingBuffer {
-    pub(crate) fn new(limit: u32) -> SocketOutgoingBuffer {
+    pub(crate) fn new(limit: u32, mss: u32) -> SocketOutgoingBuffer {
         SocketOutgoingBuffer {
             packets: Vec::new(),
             len: 0,
             limit: limit as usize,
+            mss: (mss as usize).max(1),
+
+            next_seq: 0,
         }
     }
 
     pub(crate) fn write<'a: 'b, 'b>(&mut self, mut buf: &'a [u8]) -> Result<(), &'b [u8]> {
         while buf.len() > 0 && self.limit > self.len {
             let pkt = if let Some(pkt) = self.packets.last_mut() {
-                if pkt.len() < 1024 {
+                if pkt.len() < self.mss {
                     pkt
                 } else {
-                    self.packets.push(Vec::with_capacity(1024));
+                    self.packets.push(Vec::with_capacity(self.mss));
                     self.packets.last_mut().unwrap()
                 }
             } else {
-                self.packets.push(Vec::with_capacity(1024));
+                self.packets.push(Vec::with_capacity(self.mss));
                 self.packets.last_mut().unwrap()
             };
 
             // the number of bytes to be written
-            let n = buf.len().min(1024 - pkt.len()).min(self.limit - self.len);
+            let n = buf
+                .len()
+                .min(self.mss - pkt.len())
+                .min(self.limit - self.len);
             let offset = pkt.len();
 
             // Extended buffer
@@ -169,11 +269,60 @@ impl SocketOutgoingBuffer {
         }
     }
 
-    pub(crate) fn yield_packets(&mut self) -> Vec<Vec<u8>> {
-        self.len = 0;
+    pub(crate) fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.len >= self.limit
+    }
+
+    /// Returns the stream offset after all yielded data.
+    pub(crate) fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Yields up to `max` buffered packets, together with their stream offset.
+    ///
+    /// Packets that exceed `max` remain buffered for the next call.
+    pub(crate) fn yield_packets(&mut self, max: usize) -> Vec<(u64, Vec<u8>)> {
+        let n = max.min(self.packets.len());
+        let swap = self.packets.drain(..n).collect::<Vec<_>>();
+        self.len -= swap.iter().map(Vec::len).sum::<usize>();
+
+        swap.into_iter()
+            .map(|packet| {
+                let seq = self.next_seq;
+                self.next_seq += packet.len() as u64;
+                (seq, packet)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_buffer_signals_eof() {
+        let mut incoming = SocketIncomingBuffer::new(1024);
+        let mut buf = [0u8; 4];
+        assert_eq!(incoming.read(&mut buf), None);
+
+        incoming.insert(0, b"hello".to_vec());
+        assert_eq!(incoming.read(&mut buf), Some(4));
+        assert_eq!(&buf, b"hell");
+
+        // Buffered data is still read after sealing.
+        incoming.seal();
+        assert_eq!(incoming.read(&mut buf), Some(1));
+        assert_eq!(buf[0], b'o');
+        assert_eq!(incoming.read(&mut buf), Some(0));
 
-        let mut swap = Vec::new();
-        std::mem::swap(&mut swap, &mut self.packets);
-        swap
+        let mut storage = [0u8; 4];
+        let mut read_buf = ReadBuf::new(&mut storage);
+        assert_eq!(incoming.read_buf(&mut read_buf), Some(0));
+        assert!(read_buf.filled().is_empty());
     }
 }
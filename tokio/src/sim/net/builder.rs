@@ -0,0 +1,187 @@
+//! Configuration of the network interfaces of a simulated node.
+
+use super::interface::{Interface, InterfaceAddr, InterfaceFlags, InterfaceStatus};
+use super::IOContext;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// A builder for an [`IOContext`] with custom network interfaces.
+///
+/// The builder configures a primary ethernet interface `en0` from the
+/// given MAC and ip addresses, and optionally a loopback interface.
+/// Further interfaces can be added as is.
+///
+/// ```
+/// use std::net::Ipv4Addr;
+/// use tokio::net::IOContext;
+///
+/// let io = IOContext::builder()
+///     .mac([1, 2, 3, 4, 5, 6])
+///     .ipv4(Ipv4Addr::new(192, 168, 2, 1), Ipv4Addr::new(255, 255, 255, 0))
+///     .ipv6("2001:db8::1".parse().unwrap(), 64)
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct IOContextBuilder {
+    mac: Option<[u8; 6]>,
+    addrs: Vec<InterfaceAddr>,
+    interfaces: Vec<Interface>,
+    loopback: bool,
+}
+
+impl IOContextBuilder {
+    /// Creates a new builder for a node with only a loopback interface.
+    pub fn new() -> Self {
+        Self {
+            mac: None,
+            addrs: Vec::new(),
+            interfaces: Vec::new(),
+            loopback: true,
+        }
+    }
+
+    /// Sets the MAC address of the `en0` interface.
+    pub fn mac(mut self, mac: [u8; 6]) -> Self {
+        self.mac = Some(mac);
+        self
+    }
+
+    /// Adds an ipv4 address to the `en0` interface.
+    pub fn ipv4(mut self, addr: Ipv4Addr, netmask: Ipv4Addr) -> Self {
+        self.addrs.push(InterfaceAddr::Inet { addr, netmask });
+        self
+    }
+
+    /// Adds an ipv6 address to the `en0` interface.
+    pub fn ipv6(mut self, addr: Ipv6Addr, prefixlen: usize) -> Self {
+        self.addrs.push(InterfaceAddr::Inet6 {
+            addr,
+            prefixlen,
+            scope_id: None,
+        });
+        self
+    }
+
+    /// Adds an additional interface.
+    pub fn add_interface(mut self, interface: Interface) -> Self {
+        self.interfaces.push(interface);
+        self
+    }
+
+    /// Enables or disables the loopback interface.
+    ///
+    /// The loopback interface is enabled by default.
+    pub fn loopback(mut self, loopback: bool) -> Self {
+        self.loopback = loopback;
+        self
+    }
+
+    /// Creates the [`IOContext`].
+    ///
+    /// The `en0` interface is only created if either a MAC or an ip
+    /// address was configured.
+    pub fn build(self) -> IOContext {
+        let mut interfaces = Vec::with_capacity(self.interfaces.len() + 2);
+        if self.loopback {
+            interfaces.push(Interface::loopback());
+        }
+
+        if self.mac.is_some() || !self.addrs.is_empty() {
+            let mut addrs = Vec::with_capacity(self.addrs.len() + 1);
+            if let Some(addr) = self.mac {
+                addrs.push(InterfaceAddr::Ether { addr });
+            }
+            addrs.extend(self.addrs);
+
+            interfaces.push(Interface {
+                name: "en0".to_string(),
+                flags: InterfaceFlags::en0(),
+                addrs,
+                status: InterfaceStatus::Active,
+                prio: 10,
+            });
+        }
+
+        interfaces.extend(self.interfaces);
+        IOContext::from_interfaces(interfaces)
+    }
+}
+
+impl Default for IOContextBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{TcpListener, UdpSocket};
+    use crate::runtime::Builder;
+    use crate::sim::net::SimNetError;
+    use crate::sim::SimContext;
+    use std::io::Result;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn dual_stack_node() {
+        let v4 = Ipv4Addr::new(192, 168, 2, 1);
+        let v6: Ipv6Addr = "2001:db8::1".parse().unwrap();
+
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let mut ctx = SimContext::empty().with_time(String::from("builder"));
+        ctx.io = Some(
+            IOContext::builder()
+                .mac([1, 2, 3, 4, 5, 6])
+                .ipv4(v4, Ipv4Addr::new(255, 255, 255, 0))
+                .ipv6(v6, 64)
+                .build(),
+        );
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async move {
+            let udp_v4 = UdpSocket::bind((v4, 100)).await?;
+            let udp_v6 = UdpSocket::bind((v6, 100)).await?;
+            let tcp_v6 = TcpListener::bind((v6, 80)).await?;
+            Result::Ok((
+                udp_v4.local_addr()?,
+                udp_v6.local_addr()?,
+                tcp_v6.local_addr()?,
+            ))
+        });
+
+        let (udp_v4, udp_v6, tcp_v6) = rt.block_or_idle_on(handle).unwrap().unwrap().unwrap();
+        assert_eq!(udp_v4, SocketAddr::from((v4, 100)));
+        assert_eq!(udp_v6, SocketAddr::from((v6, 100)));
+        assert_eq!(tcp_v6, SocketAddr::from((v6, 80)));
+
+        let mac = IOContext::with_current(|ctx| ctx.get_mac_address()).unwrap();
+        assert_eq!(mac, Some([1, 2, 3, 4, 5, 6]));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn without_loopback() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let mut ctx = SimContext::empty().with_time(String::from("builder"));
+        ctx.io = Some(
+            IOContext::builder()
+                .loopback(false)
+                .ipv4(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(255, 0, 0, 0))
+                .build(),
+        );
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let err = UdpSocket::bind("127.0.0.1:100").await.unwrap_err();
+            let socket = UdpSocket::bind("0.0.0.0:100").await.unwrap();
+            (SimNetError::from_io(&err), socket.local_addr().unwrap())
+        });
+
+        let (err, addr) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(err, Some(SimNetError::AddrNotAvailable));
+        assert_eq!(addr, "10.0.0.1:100".parse().unwrap());
+
+        guard.leave();
+    }
+}
@@ -0,0 +1,273 @@
+//! Packet capture of the simulated network stack.
+//!
+//! A capture records all packets that leave or arrive at an
+//! [`IOContext`](super::IOContext) once started with
+//! [`start_capture`](super::IOContext::start_capture). Similarly, a delivery
+//! log records all messages delivered to sockets or streams once started
+//! with [`start_delivery_log`](super::IOContext::start_delivery_log).
+
+use super::{IOIntent, TcpConnectMessage, TcpMessage, UdpMessage};
+use crate::time::SimTime;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+
+/// The direction of a captured packet, relative to the capturing node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaptureDirection {
+    /// The packet was send by the node.
+    Outgoing,
+    /// The packet was received by the node.
+    Incoming,
+}
+
+/// The type of a captured packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaptureKind {
+    /// A UDP datagram.
+    Udp,
+    /// The initiation of a TCP handshake.
+    TcpSyn,
+    /// The acknowledgement of a TCP handshake.
+    TcpSynAck,
+    /// A TCP packet containing stream data.
+    TcpData,
+}
+
+/// A single captured packet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CaptureRecord {
+    /// The time the packet was send or received.
+    pub time: SimTime,
+    /// The direction of the packet.
+    pub direction: CaptureDirection,
+    /// The type of the packet.
+    pub kind: CaptureKind,
+    /// The senders address.
+    pub src: SocketAddr,
+    /// The receivers address.
+    pub dest: SocketAddr,
+    /// The length of the payload in bytes.
+    pub len: usize,
+}
+
+/// A single message delivered to a socket or stream.
+///
+/// In contrast to a [`CaptureRecord`], only messages that were accepted by
+/// a socket or stream are recorded. This excludes TCP packets without
+/// content, which close streams or probe them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeliveryRecord {
+    /// The time the message was delivered.
+    pub time: SimTime,
+    /// The senders address.
+    pub src: SocketAddr,
+    /// The receivers address.
+    pub dest: SocketAddr,
+    /// The position of the delivery within all deliveries of the context.
+    ///
+    /// This orders deliveries at the same point in simulation time.
+    pub seq: u64,
+}
+
+impl CaptureRecord {
+    pub(super) fn outgoing(intent: &IOIntent) -> Option<CaptureRecord> {
+        let now = SimTime::now();
+        match intent {
+            IOIntent::UdpSendPacket(msg) => Some(Self::udp(now, CaptureDirection::Outgoing, msg)),
+            IOIntent::TcpConnect(msg) => {
+                Some(Self::tcp_connect(now, CaptureDirection::Outgoing, msg))
+            }
+            IOIntent::TcpSendPacket(msg, delay) => Some(Self::tcp(
+                now.checked_add(*delay).unwrap_or(SimTime::MAX),
+                CaptureDirection::Outgoing,
+                msg,
+            )),
+            _ => None,
+        }
+    }
+
+    pub(super) fn udp(time: SimTime, direction: CaptureDirection, msg: &UdpMessage) -> Self {
+        Self {
+            time,
+            direction,
+            kind: CaptureKind::Udp,
+            src: msg.src_addr,
+            dest: msg.dest_addr,
+            len: msg.content.len(),
+        }
+    }
+
+    pub(super) fn tcp(time: SimTime, direction: CaptureDirection, msg: &TcpMessage) -> Self {
+        Self {
+            time,
+            direction,
+            kind: CaptureKind::TcpData,
+            src: msg.src_addr,
+            dest: msg.dest_addr,
+            len: msg.content.len(),
+        }
+    }
+
+    pub(super) fn tcp_connect(
+        time: SimTime,
+        direction: CaptureDirection,
+        msg: &TcpConnectMessage,
+    ) -> Self {
+        let (kind, src, dest) = match *msg {
+            TcpConnectMessage::ClientInitiate { client, server } => {
+                (CaptureKind::TcpSyn, client, server)
+            }
+            TcpConnectMessage::ServerAcknowledge { client, server } => {
+                (CaptureKind::TcpSynAck, server, client)
+            }
+        };
+        Self {
+            time,
+            direction,
+            kind,
+            src,
+            dest,
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Display for CaptureRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = match self.direction {
+            CaptureDirection::Outgoing => "OUT",
+            CaptureDirection::Incoming => "IN ",
+        };
+        let kind = match self.kind {
+            CaptureKind::Udp => "UDP",
+            CaptureKind::TcpSyn => "TCP SYN",
+            CaptureKind::TcpSynAck => "TCP SYN-ACK",
+            CaptureKind::TcpData => "TCP DATA",
+        };
+        write!(
+            f,
+            "{} {} {} {} -> {} len={}",
+            self.time, direction, kind, self.src, self.dest, self.len
+        )
+    }
+}
+
+/// Writes the records as a textual packet log, one record per line.
+pub fn write_capture<W: io::Write>(out: &mut W, records: &[CaptureRecord]) -> io::Result<()> {
+    for record in records {
+        writeln!(out, "{}", record)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::sim::net::{testutil, IOContext, TcpListener, TcpStream, UdpSocket};
+
+    #[test]
+    fn capture_tcp_exchange() {
+        let (rt, ctx) = testutil::node("capture");
+        let guard = rt.enter_context(ctx);
+        IOContext::with_current(|ctx| ctx.start_capture());
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await.unwrap();
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            buf
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+            stream
+        });
+
+        for _ in 0..4 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                match intent {
+                    IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                    IOIntent::TcpSendPacket(msg, _) => rt.process_tcp_packet(msg).unwrap(),
+                    _ => {}
+                }
+            }
+        }
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap();
+        assert_eq!(&rt.block_or_idle_on(server).unwrap().unwrap(), b"hello");
+
+        let records = IOContext::with_current(|ctx| ctx.export_capture());
+        let mut kinds = records
+            .iter()
+            .filter(|r| r.direction == CaptureDirection::Incoming)
+            .map(|r| r.kind)
+            .collect::<Vec<_>>();
+        kinds.dedup();
+        assert_eq!(
+            kinds,
+            vec![
+                CaptureKind::TcpSyn,
+                CaptureKind::TcpSynAck,
+                CaptureKind::TcpData
+            ]
+        );
+
+        let data = records.iter().find(|r| r.kind == CaptureKind::TcpData);
+        assert_eq!(data.map(|r| r.len), Some(5));
+
+        let mut log = Vec::new();
+        write_capture(&mut log, &records).unwrap();
+        assert_eq!(
+            String::from_utf8(log).unwrap().lines().count(),
+            records.len()
+        );
+
+        guard.leave();
+    }
+
+    #[test]
+    fn reply_delivered_after_request() {
+        let (rt, ctx) = testutil::node("delivery_log");
+        let guard = rt.enter_context(ctx);
+        IOContext::with_current(|ctx| ctx.start_delivery_log());
+
+        let server: SocketAddr = "192.168.2.1:7".parse().unwrap();
+        let client: SocketAddr = "192.168.2.1:100".parse().unwrap();
+        rt.spawn(async move {
+            let socket = UdpSocket::bind(server).await.unwrap();
+            let mut buf = [0u8; 8];
+            let (n, from) = socket.recv_from(&mut buf).await.unwrap();
+            socket.send_to(&buf[..n], from).await.unwrap();
+        });
+        let handle = rt.spawn(async move {
+            let socket = UdpSocket::bind(client).await.unwrap();
+            socket.send_to(b"request", server).await.unwrap();
+            let mut buf = [0u8; 8];
+            socket.recv_from(&mut buf).await.unwrap();
+        });
+
+        for _ in 0..4 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let IOIntent::UdpSendPacket(msg) = intent {
+                    rt.process_udp(msg).unwrap();
+                }
+            }
+        }
+        rt.block_or_idle_on(handle).unwrap().unwrap();
+
+        let log = IOContext::with_current(|ctx| ctx.delivery_log().to_vec());
+        let request = log.iter().position(|r| r.src == client && r.dest == server);
+        let reply = log.iter().position(|r| r.src == server && r.dest == client);
+        assert_eq!((request, reply), (Some(0), Some(1)));
+        assert!(log[0].seq < log[1].seq);
+        assert!(log[0].time <= log[1].time);
+
+        guard.leave();
+    }
+}
@@ -0,0 +1,134 @@
+//! Network error types.
+
+use std::error;
+use std::fmt;
+use std::io::{self, ErrorKind};
+
+/// Errors encountered by the simulated network stack.
+///
+/// All fallible network operations keep returning [`io::Result`] for drop-in
+/// compatibility with `tokio::net`. The contained error can be recovered
+/// from the [`io::Error`] using [`SimNetError::from_io`], or alternatively
+/// via [`io::Error::get_ref`] and a downcast.
+///
+/// ```no_run
+/// use tokio::net::{SimNetError, UdpSocket};
+///
+/// # async fn dox() -> std::io::Result<()> {
+/// let socket = UdpSocket::bind("0.0.0.0:0").await?;
+/// match socket.send(b"ping").await {
+///     Err(e) if SimNetError::from_io(&e) == Some(SimNetError::NoPeer) => { /* ... */ }
+///     _ => {}
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SimNetError {
+    /// No [`IOContext`](super::IOContext) is bound to the current thread.
+    NoContext,
+    /// The simulation context has dropped the handle of a `UdpSocket`.
+    SocketDropped,
+    /// The simulation context has dropped the handle of a `TcpListener`.
+    ListenerDropped,
+    /// The simulation context has dropped the handle of a `TcpStream`.
+    StreamDropped,
+    /// The socket is not connected to a peer.
+    NoPeer,
+    /// A broadcast was send without the `SO_BROADCAST` option enabled.
+    BroadcastDisabled,
+    /// The handshake of a connection was not acknowledged in time.
+    ConnectionTimedOut,
+    /// The requested address is already bound.
+    AddrInUse,
+    /// The requested address is not provided by any interface.
+    AddrNotAvailable,
+    /// The interface associated with the address is inactive.
+    InterfaceInactive,
+    /// The interface associated with the address is down.
+    InterfaceDown,
+    /// The address does not match the ip version of the socket.
+    IpVersionMismatch,
+    /// No interface provides the address.
+    InterfaceNotFound,
+    /// A read or write did not make progress within the socket timeout.
+    TimedOut,
+    /// The connected peer is not reachable on the destination port.
+    ConnectionRefused,
+    /// The stream was shut down for writing.
+    WriteClosed,
+    /// The connection was reset.
+    ///
+    /// This happens when the simulation context is reset, the peer answers
+    /// with a reset, a keepalive probe goes unanswered, or a write stalls
+    /// for longer than the write stall timeout.
+    ConnectionReset,
+    /// The interface of the bound address is no longer up.
+    ///
+    /// Since `ErrorKind::NetworkUnreachable` is not available on the minimum
+    /// supported Rust version, this converts into [`ErrorKind::Other`].
+    NetworkUnreachable,
+    /// The datagram exceeds the maximum datagram size of the node.
+    MessageSize,
+}
+
+impl SimNetError {
+    /// Returns the [`ErrorKind`] used when converting into an [`io::Error`].
+    pub fn kind(&self) -> ErrorKind {
+        use SimNetError::*;
+        match self {
+            ConnectionTimedOut => ErrorKind::NotConnected,
+            AddrInUse => ErrorKind::AddrInUse,
+            AddrNotAvailable => ErrorKind::AddrNotAvailable,
+            InterfaceInactive | InterfaceDown | InterfaceNotFound => ErrorKind::NotFound,
+            TimedOut => ErrorKind::TimedOut,
+            ConnectionRefused => ErrorKind::ConnectionRefused,
+            WriteClosed => ErrorKind::BrokenPipe,
+            ConnectionReset => ErrorKind::ConnectionReset,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// Extracts the [`SimNetError`] from an [`io::Error`] if it was
+    /// created by the simulated network stack.
+    pub fn from_io(err: &io::Error) -> Option<SimNetError> {
+        err.get_ref()?.downcast_ref::<SimNetError>().copied()
+    }
+}
+
+impl error::Error for SimNetError {}
+
+impl fmt::Display for SimNetError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SimNetError::*;
+        let descr = match self {
+            NoContext => "no SimContext bound",
+            SocketDropped => "simulation context has dropped UdpSocket",
+            ListenerDropped => "simulation context has dropped TcpListener",
+            StreamDropped => "simulation context has dropped TcpStream",
+            NoPeer => "socket is not connected to a peer",
+            BroadcastDisabled => "cannot send broadcast without broadcast flag enabled",
+            ConnectionTimedOut => "connection timed out",
+            AddrInUse => "address already in use",
+            AddrNotAvailable => "address not available",
+            InterfaceInactive => "interface inactive",
+            InterfaceDown => "interface down",
+            IpVersionMismatch => "address does not match the ip version of the socket",
+            InterfaceNotFound => "no interface provides the address",
+            TimedOut => "operation timed out",
+            ConnectionRefused => "connection refused",
+            WriteClosed => "stream was shut down for writing",
+            ConnectionReset => "connection reset",
+            NetworkUnreachable => "network unreachable",
+            MessageSize => "message too long",
+        };
+        write!(fmt, "{}", descr)
+    }
+}
+
+impl From<SimNetError> for io::Error {
+    fn from(err: SimNetError) -> io::Error {
+        io::Error::new(err.kind(), err)
+    }
+}
@@ -0,0 +1,167 @@
+//! Length-prefixed framing over byte streams.
+//!
+//! A frame consists of a 4-byte big-endian length, followed by
+//! exactly that many bytes of payload.
+
+use crate::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind, Result};
+
+/// The maximum length of a frame accepted by [`read_u32_frame`].
+pub const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+/// Reads a single length-prefixed frame, returning its payload.
+///
+/// Returns an error of kind [`ErrorKind::UnexpectedEof`] if the stream ends
+/// before the frame was completely read, including the case that the stream
+/// ends before the length prefix. Frames longer than [`MAX_FRAME_LEN`] are
+/// rejected with an error of kind [`ErrorKind::InvalidData`].
+pub async fn read_u32_frame<R: AsyncRead + Unpin>(r: &mut R) -> Result<Vec<u8>> {
+    let len = r.read_u32().await?;
+    if len > MAX_FRAME_LEN {
+        return Err(Error::new(ErrorKind::InvalidData, "frame too long"));
+    }
+
+    // The buffer grows with the received data, instead of trusting the prefix.
+    let mut buf = Vec::new();
+    r.take(u64::from(len)).read_to_end(&mut buf).await?;
+    if buf.len() < len as usize {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+/// Writes `frame` as a single length-prefixed frame.
+///
+/// Returns an error of kind [`ErrorKind::InvalidInput`] if the frame
+/// is too long for its length to be represented by a `u32`.
+pub async fn write_u32_frame<W: AsyncWrite + Unpin>(w: &mut W, frame: &[u8]) -> Result<()> {
+    let len = u32::try_from(frame.len())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "frame too long"))?;
+    w.write_u32(len).await?;
+    w.write_all(frame).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+    use crate::sim::net::{testutil, TcpListener, TcpStream};
+    use crate::task::JoinHandle;
+
+    fn connect(rt: &Runtime) -> (JoinHandle<Result<TcpStream>>, TcpStream) {
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (stream, _) = listener.accept().await?;
+            Result::Ok(stream)
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(TcpStream::connect("127.0.0.1:80"));
+        testutil::route(rt);
+        let client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let (rt, ctx) = testutil::node("framing");
+        let guard = rt.enter_context(ctx);
+        let (server, mut client) = connect(&rt);
+
+        let server = rt.spawn(async move {
+            let mut stream = server.await.unwrap()?;
+            let mut frames = Vec::new();
+            for _ in 0..3 {
+                frames.push(read_u32_frame(&mut stream).await?);
+            }
+            Result::Ok(frames)
+        });
+        rt.spawn(async move {
+            for frame in [&b"hello"[..], b"", &[7u8; 3000]] {
+                write_u32_frame(&mut client, frame).await.unwrap();
+            }
+        });
+        testutil::route(&rt);
+
+        let frames = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(frames, vec![b"hello".to_vec(), Vec::new(), vec![7u8; 3000]]);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn frame_split_across_packets() {
+        let (rt, ctx) = testutil::node("framing");
+        let guard = rt.enter_context(ctx);
+        let (server, mut client) = connect(&rt);
+
+        let server = rt.spawn(async move {
+            let mut stream = server.await.unwrap()?;
+            read_u32_frame(&mut stream).await
+        });
+
+        // Deliver the frame in three packets, splitting the length prefix.
+        let mut packets = vec![&[0u8, 0][..], &[0, 5, b'h', b'e'], b"llo"];
+        packets.reverse();
+        while let Some(packet) = packets.pop() {
+            let write = rt.spawn(async move {
+                client.write_all(packet).await.unwrap();
+                client
+            });
+            testutil::route(&rt);
+            client = rt.block_or_idle_on(write).unwrap().unwrap();
+            assert_eq!(server.is_finished(), packets.is_empty());
+        }
+
+        let frame = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(frame, b"hello");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn truncated_frame_is_unexpected_eof() {
+        let (rt, ctx) = testutil::node("framing");
+        let guard = rt.enter_context(ctx);
+        let (server, mut client) = connect(&rt);
+
+        let server = rt.spawn(async move {
+            let mut stream = server.await.unwrap()?;
+            Result::Ok(read_u32_frame(&mut stream).await.map_err(|e| e.kind()))
+        });
+        rt.spawn(async move {
+            client.write_all(&[0, 0, 0, 10, 1, 2, 3, 4]).await.unwrap();
+            AsyncWriteExt::shutdown(&mut client).await.unwrap();
+        });
+        testutil::route(&rt);
+
+        let result = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(result, Err(ErrorKind::UnexpectedEof));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn oversized_frame_is_invalid_data() {
+        let (rt, ctx) = testutil::node("framing");
+        let guard = rt.enter_context(ctx);
+        let (server, mut client) = connect(&rt);
+
+        let server = rt.spawn(async move {
+            let mut stream = server.await.unwrap()?;
+            Result::Ok(read_u32_frame(&mut stream).await.map_err(|e| e.kind()))
+        });
+        rt.spawn(async move {
+            // The prefix is rejected without waiting for the payload.
+            client.write_u32(MAX_FRAME_LEN + 1).await.unwrap();
+            client
+        });
+        testutil::route(&rt);
+
+        let result = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(result, Err(ErrorKind::InvalidData));
+
+        guard.leave();
+    }
+}
@@ -0,0 +1,80 @@
+//! Generation of network messages from unstructured bytes, for fuzzing.
+
+use super::{TcpKeepalive, TcpMessage, UdpMessage};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+fn arbitrary_addr(u: &mut Unstructured<'_>) -> Result<SocketAddr> {
+    let addr = if u.arbitrary::<bool>()? {
+        SocketAddr::from((Ipv6Addr::from(u.arbitrary::<[u8; 16]>()?), u.arbitrary()?))
+    } else {
+        SocketAddr::from((Ipv4Addr::from(u.arbitrary::<[u8; 4]>()?), u.arbitrary()?))
+    };
+    Ok(addr)
+}
+
+impl<'a> Arbitrary<'a> for UdpMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(UdpMessage {
+            src_addr: arbitrary_addr(u)?,
+            dest_addr: arbitrary_addr(u)?,
+            ttl: u.arbitrary()?,
+            content: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for TcpKeepalive {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.arbitrary::<bool>()? {
+            TcpKeepalive::Probe
+        } else {
+            TcpKeepalive::Ack
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for TcpMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(TcpMessage {
+            src_addr: arbitrary_addr(u)?,
+            dest_addr: arbitrary_addr(u)?,
+            ttl: u.arbitrary()?,
+            seq: u.arbitrary()?,
+            keepalive: u.arbitrary()?,
+            content: u.arbitrary()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::net::{testutil, IOContext};
+
+    #[test]
+    fn generate_and_process_messages() {
+        let (rt, ctx) = testutil::node("fuzz");
+        let guard = rt.enter_context(ctx);
+
+        let bytes = (0..=255u8).cycle().take(4096).collect::<Vec<_>>();
+        let generate = || {
+            let mut u = Unstructured::new(&bytes);
+            let udp = UdpMessage::arbitrary(&mut u).unwrap();
+            let tcp = TcpMessage::arbitrary(&mut u).unwrap();
+            (udp, tcp)
+        };
+
+        // The same bytes produce the same messages.
+        let (udp, tcp) = generate();
+        assert_eq!((udp.clone(), tcp.clone()), generate());
+
+        // Arbitrary messages must not panic the network stack.
+        IOContext::with_current(|ctx| {
+            let _ = ctx.process_udp(udp);
+            let _ = ctx.process_tcp_packet(tcp);
+        });
+
+        guard.leave();
+    }
+}
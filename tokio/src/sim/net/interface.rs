@@ -214,8 +214,21 @@ impl InterfaceAddr {
                 let mask_u32 = u32::from_be_bytes(netmask.octets());
                 mask_u32 & ip_u32 == mask_u32 & addr_u32
             }
-            Self::Inet6 { .. } if ip.is_ipv6() => {
-                todo!()
+            Self::Inet6 {
+                addr, prefixlen, ..
+            } if ip.is_ipv6() => {
+                let ip = if let IpAddr::V6(v) = ip {
+                    v
+                } else {
+                    unreachable!()
+                };
+
+                let ip_u128 = u128::from_be_bytes(ip.octets());
+                let addr_u128 = u128::from_be_bytes(addr.octets());
+                let mask_u128 = u128::MAX
+                    .checked_shl(128 - (*prefixlen).min(128) as u32)
+                    .unwrap_or(0);
+                mask_u128 & ip_u128 == mask_u128 & addr_u128
             }
             _ => false,
         }
@@ -0,0 +1,85 @@
+//! Bandwidth shared by the TCP streams of a node.
+
+use super::SimTime;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// A link with a fixed bandwidth, shared by all TCP streams on a local address.
+///
+/// Links are configured with [`set_link`](super::IOContext::set_link). While
+/// the link is idle, each round of [`yield_intents`](super::IOContext::yield_intents)
+/// lets the streams with buffered data take turns sending one packet, until
+/// the link is busy transmitting. Since streams without data leave their turn
+/// to the others, the bandwidth is divided with max-min fairness. Remaining
+/// packets are send once the link is idle again, which is announced by an
+/// [`IoTick`](super::IOIntent::IoTick).
+///
+/// Streams on a link ignore the congestion window and the serialization delay
+/// of the node.
+#[derive(Debug, Clone)]
+pub struct Link {
+    bandwidth: u64,
+    busy_until: SimTime,
+    last: Option<(SocketAddr, SocketAddr)>,
+    sent: BTreeMap<(SocketAddr, SocketAddr), u64>,
+}
+
+impl Link {
+    pub(super) fn new(bandwidth: u64) -> Link {
+        Link {
+            bandwidth,
+            busy_until: SimTime::MIN,
+            last: None,
+            sent: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the bandwidth of the link in bytes per second.
+    pub fn bandwidth(&self) -> u64 {
+        self.bandwidth
+    }
+
+    /// Returns the number of bytes the stream from `local` to `peer`
+    /// has send over this link.
+    pub fn bytes_sent(&self, local: SocketAddr, peer: SocketAddr) -> u64 {
+        self.sent.get(&(local, peer)).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of bytes send over this link by all streams.
+    pub fn total_bytes_sent(&self) -> u64 {
+        self.sent.values().sum()
+    }
+
+    /// Returns the point in time at which the link becomes idle.
+    pub(super) fn busy_until(&self) -> SimTime {
+        self.busy_until
+    }
+
+    pub(super) fn is_idle(&self) -> bool {
+        self.busy_until <= SimTime::now()
+    }
+
+    /// Picks the stream whose turn it is, out of the sorted streams with
+    /// buffered data.
+    pub(super) fn next_stream(
+        &self,
+        backlogged: &[(SocketAddr, SocketAddr)],
+    ) -> Option<(SocketAddr, SocketAddr)> {
+        let next = self
+            .last
+            .and_then(|last| backlogged.iter().find(|key| **key > last));
+        next.or_else(|| backlogged.first()).copied()
+    }
+
+    /// Occupies the link with a packet of `len` bytes send by `key`.
+    pub(super) fn transmit(&mut self, key: (SocketAddr, SocketAddr), len: usize) {
+        let nanos = len as u128 * 1_000_000_000 / u128::from(self.bandwidth);
+        let duration = Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX));
+
+        self.busy_until = self.busy_until.max(SimTime::now()) + duration;
+        self.last = Some(key);
+        *self.sent.entry(key).or_default() += len as u64;
+    }
+}
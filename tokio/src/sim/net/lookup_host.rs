@@ -12,6 +12,11 @@ cfg_net! {
     /// This API is not intended to cover all DNS use cases. Anything beyond the
     /// basic use case should be done with a specialized library.
     ///
+    /// Hosts registered with [`IOContext::add_host`](super::IOContext::add_host)
+    /// resolve to all registered addresses, ipv4 addresses before ipv6 addresses
+    /// unless configured otherwise. All other names are resolved by the
+    /// operating system.
+    ///
     /// # Examples
     ///
     /// To resolve a DNS entry:
@@ -36,3 +41,48 @@ cfg_net! {
         addr::to_socket_addrs(host).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::net::{testutil, IOContext};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    #[test]
+    fn lookup_registered_host() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 168, 2, 1));
+        let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+
+        let (rt, ctx) = testutil::node("lookup_host");
+        let guard = rt.enter_context(ctx);
+        IOContext::with_current(|ctx| {
+            ctx.add_host("server", v6);
+            ctx.add_host("server", v4);
+        });
+
+        let lookup = || async {
+            let by_str = lookup_host("server:80").await.unwrap().collect::<Vec<_>>();
+            let by_tuple = lookup_host(("server", 80))
+                .await
+                .unwrap()
+                .collect::<Vec<_>>();
+            assert_eq!(by_str, by_tuple);
+            by_str
+        };
+
+        let addrs = rt.block_or_idle_on(rt.spawn(lookup())).unwrap().unwrap();
+        assert_eq!(
+            addrs,
+            vec![SocketAddr::new(v4, 80), SocketAddr::new(v6, 80)]
+        );
+
+        IOContext::with_current(|ctx| ctx.set_prefer_ipv6(true));
+        let addrs = rt.block_or_idle_on(rt.spawn(lookup())).unwrap().unwrap();
+        assert_eq!(
+            addrs,
+            vec![SocketAddr::new(v6, 80), SocketAddr::new(v4, 80)]
+        );
+
+        guard.leave();
+    }
+}
@@ -0,0 +1,121 @@
+//! A scripted byte stream, independent of the simulated network.
+
+use crate::io::{AsyncRead, AsyncWrite, ReadBuf};
+use std::io::Result;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// An in-memory stream that serves a fixed script of bytes.
+///
+/// Reads consume the script in order and signal EOF once it was fully read,
+/// while all writes are collected in a sink. Since a `MockStream` does not use
+/// an [`IOContext`](super::IOContext), it can be used to test codecs in
+/// isolation from the network model.
+///
+/// ```
+/// use tokio::net::framing::{read_u32_frame, write_u32_frame};
+/// use tokio::net::MockStream;
+///
+/// # let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// # rt.block_on(async {
+/// let mut stream = MockStream::new(vec![0, 0, 0, 2, b'h', b'i']);
+/// assert_eq!(read_u32_frame(&mut stream).await?, b"hi");
+///
+/// write_u32_frame(&mut stream, b"ok").await?;
+/// assert_eq!(stream.written(), &[0, 0, 0, 2, b'o', b'k']);
+/// # std::io::Result::Ok(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockStream {
+    script: Vec<u8>,
+    pos: usize,
+    written: Vec<u8>,
+}
+
+impl MockStream {
+    /// Creates a new stream, serving `read_script` to readers.
+    pub fn new(read_script: Vec<u8>) -> MockStream {
+        MockStream {
+            script: read_script,
+            pos: 0,
+            written: Vec::new(),
+        }
+    }
+
+    /// Returns the number of script bytes that were already read.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the part of the script that was not yet read.
+    pub fn remaining(&self) -> &[u8] {
+        &self.script[self.pos..]
+    }
+
+    /// Returns all bytes written to the stream.
+    pub fn written(&self) -> &[u8] {
+        &self.written
+    }
+}
+
+impl AsyncRead for MockStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<()>> {
+        let n = buf.remaining().min(self.script.len() - self.pos);
+        let start = self.pos;
+        buf.put_slice(&self.script[start..start + n]);
+        self.pos += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for MockStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        self.written.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sim::net::framing::{read_u32_frame, write_u32_frame};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn decode_framed_script() {
+        let rt = Builder::new_current_thread().build().unwrap();
+        let mut stream = MockStream::new(vec![0, 0, 0, 5, b'h', b'e', b'l', b'l', b'o', 0, 0]);
+
+        rt.block_on(async {
+            let frame = read_u32_frame(&mut stream).await.unwrap();
+            assert_eq!(frame, b"hello");
+            assert_eq!(stream.position(), 9);
+
+            // The script ends within the next length prefix.
+            let result = read_u32_frame(&mut stream).await;
+            assert_eq!(result.map_err(|e| e.kind()), Err(ErrorKind::UnexpectedEof));
+            assert!(stream.remaining().is_empty());
+
+            write_u32_frame(&mut stream, b"ok").await.unwrap();
+        });
+        assert_eq!(stream.written(), &[0, 0, 0, 2, b'o', b'k']);
+    }
+}
@@ -1,8 +1,10 @@
 use super::time::SimTime;
-use std::collections::{HashMap, VecDeque};
+use super::SimContext;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::convert::TryFrom;
 use std::future::Future;
-use std::io::{Error, ErrorKind};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::io::{Error, ErrorKind, IoSlice};
+use std::net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll, Waker};
@@ -14,12 +16,18 @@ pub(crate) use std::io::Result;
 pub mod interface;
 use interface::*;
 
+pub mod capture;
+use capture::{CaptureDirection, CaptureRecord, DeliveryRecord};
+
 pub mod unix;
 pub mod windows;
 
 mod addr;
 pub use addr::*;
 
+mod error;
+pub use error::SimNetError;
+
 mod buffer;
 use buffer::SocketIncomingBuffer;
 use buffer::SocketOutgoingBuffer;
@@ -40,20 +48,47 @@ pub use tcp::stream::TcpStream;
 
 mod interest;
 
+mod ready;
+pub use ready::{ready_any, Readable};
+
+#[path = "../../net/generic.rs"]
+mod generic;
+pub use generic::{AsyncTcpStream, AsyncUdpSocket, BoxFuture};
+
+mod router;
+pub use router::Router;
+
+mod link;
+pub use link::Link;
+
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+
+mod builder;
+pub use builder::IOContextBuilder;
+
+pub mod framing;
+
+mod mock;
+pub use mock::MockStream;
+
+#[cfg(any(test, feature = "test-util"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod testutil;
+
 /// Gets the mac address.
 pub fn get_mac_address() -> Result<Option<[u8; 6]>> {
-    IOContext::try_with_current(|ctx| ctx.get_mac_address())
-        .unwrap_or(Err(Error::new(ErrorKind::Other, "No SimContext bound")))
+    IOContext::with_current_or_err(|ctx| ctx.get_mac_address())
 }
 
 /// Gets the ip addr
 pub fn get_ip() -> Option<IpAddr> {
-    IOContext::with_current(|ctx| ctx.get_ip())
+    IOContext::try_with_current(|ctx| ctx.get_ip()).flatten()
 }
 
 /// A action that must be managed by the simulation core since it supercedes
 /// the limits of the current network node.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum IOIntent {
     /// The intent to forward a udp packet onto the network layer.
     UdpSendPacket(UdpMessage),
@@ -65,7 +100,8 @@ pub enum IOIntent {
 
     /// The intent to forward a tcp packet onto the network layer.
     ///
-    /// Contains the message and the intented send delay.
+    /// Contains the message and the intented send delay. A message
+    /// without content signals the end of the stream to the peer.
     TcpSendPacket(TcpMessage, Duration),
 
     /// A indication that the context whould be reactivied once the current workload
@@ -84,14 +120,23 @@ pub enum IOIntent {
 
 // # IO Interest
 
+/// A condition a task is waiting on, before it can make progress.
+///
+/// TCP interests are identified by the local and the peer address of the stream.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) enum IOInterest {
+pub enum IOInterest {
+    /// The interest in a datagram arriving at a UDP socket.
     UdpRead(SocketAddr),
+    /// The interest in sending a datagram on a UDP socket.
     UdpWrite(SocketAddr),
 
+    /// The interest in an incoming connection on a TCP listener.
     TcpAccept(SocketAddr),
+    /// The interest in the establishment of an outgoing TCP connection.
     TcpConnect((SocketAddr, SocketAddr)),
+    /// The interest in data arriving on a TCP stream.
     TcpRead((SocketAddr, SocketAddr)),
+    /// The interest in buffer space on a TCP stream.
     TcpWrite((SocketAddr, SocketAddr)),
 }
 
@@ -101,6 +146,32 @@ pub(super) struct IOInterestGuard {
     interest: IOInterest,
 }
 
+/// Wakes and removes all interests matching `f`, in registration order.
+fn wake_interests(interests: &mut Vec<IOInterestGuard>, f: impl Fn(&IOInterest) -> bool) {
+    interests.retain(|guard| {
+        if f(&guard.interest) {
+            guard.waker.wake_by_ref();
+            false
+        } else {
+            true
+        }
+    });
+}
+
+// Registers `waker` for `interest`, unless it already is, so that
+// repeated polls with the same waker do not accumulate registrations.
+fn register_interest(interests: &mut Vec<IOInterestGuard>, interest: IOInterest, waker: &Waker) {
+    let registered = interests
+        .iter()
+        .any(|guard| guard.interest == interest && guard.waker.will_wake(waker));
+    if !registered {
+        interests.push(IOInterestGuard {
+            interest,
+            waker: waker.clone(),
+        });
+    }
+}
+
 impl Future for IOInterest {
     type Output = Result<()>;
 
@@ -111,21 +182,15 @@ impl Future for IOInterest {
             // == UDP ==
             IOInterest::UdpRead(ref sock) => IOContext::with_current(|ctx| {
                 if let Some(handle) = ctx.udp_sockets.get_mut(sock) {
-                    if handle.incoming.is_empty() {
-                        handle.interests.push(IOInterestGuard {
-                            interest: self.clone(),
-                            waker: cx.waker().clone(),
-                        });
+                    if handle.incoming.is_empty() && handle.error.is_none() {
+                        register_interest(&mut handle.interests, self.clone(), cx.waker());
 
                         Poll::Pending
                     } else {
                         Poll::Ready(Ok(()))
                     }
                 } else {
-                    Poll::Ready(Err(Error::new(
-                        ErrorKind::Other,
-                        "Simulation context has dropped UdpSocket",
-                    )))
+                    Poll::Ready(Err(SimNetError::SocketDropped.into()))
                 }
             }),
             IOInterest::UdpWrite(_) => Poll::Ready(Ok(())),
@@ -134,20 +199,14 @@ impl Future for IOInterest {
             IOInterest::TcpAccept(ref sock) => IOContext::with_current(|ctx| {
                 if let Some(handle) = ctx.tcp_listeners.get_mut(sock) {
                     if handle.incoming.is_empty() {
-                        handle.interests.push(IOInterestGuard {
-                            interest: self.clone(),
-                            waker: cx.waker().clone(),
-                        });
+                        register_interest(&mut handle.interests, self.clone(), cx.waker());
 
                         Poll::Pending
                     } else {
                         Poll::Ready(Ok(()))
                     }
                 } else {
-                    Poll::Ready(Err(Error::new(
-                        ErrorKind::Other,
-                        "Simulation context has dropped TcpListener",
-                    )))
+                    Poll::Ready(Err(SimNetError::ListenerDropped.into()))
                 }
             }),
 
@@ -158,68 +217,97 @@ impl Future for IOInterest {
                     } else {
                         if handle.connection_failed {
                             handle.connection_failed = false;
+                            handle.initiated = false;
 
-                            Poll::Ready(Err(Error::new(
-                                ErrorKind::NotConnected,
-                                "Connection timed out",
-                            )))
+                            Poll::Ready(Err(SimNetError::ConnectionTimedOut.into()))
                         } else {
-                            let (addr, peer) = *addr_peer;
-
-                            let msg = TcpConnectMessage::ClientInitiate {
-                                client: addr,
-                                server: peer,
-                            };
-
-                            ctx.intents.push(IOIntent::TcpConnect(msg));
-                            ctx.intents.push(IOIntent::TcpConnectTimeout(
-                                msg,
-                                handle.config.connect_timeout,
-                            ));
-
-                            handle.interests.push(IOInterestGuard {
-                                interest: self.clone(),
-                                waker: cx.waker().clone(),
-                            });
+                            register_interest(&mut handle.interests, self.clone(), cx.waker());
+
+                            // Repeated polls must not emit duplicate handshakes,
+                            // or stack timeouts that fail a later successful attempt.
+                            if !handle.initiated {
+                                handle.initiated = true;
+
+                                let (addr, peer) = *addr_peer;
+                                let msg = TcpConnectMessage::ClientInitiate {
+                                    client: addr,
+                                    server: peer,
+                                };
+                                let timeout = handle.config.connect_timeout;
+
+                                ctx.push_intent(IOIntent::TcpConnect(msg));
+                                ctx.push_intent(IOIntent::TcpConnectTimeout(msg, timeout));
+                            }
 
                             Poll::Pending
                         }
                     }
                 } else {
-                    Poll::Ready(Err(Error::new(
-                        ErrorKind::Other,
-                        "Simulation context has dropped TcpStream",
-                    )))
+                    Poll::Ready(Err(ctx.tcp_stream_error(*addr_peer)))
                 }
             }),
 
             // Stream operations
             IOInterest::TcpRead(ref addr_peer) => IOContext::with_current(|ctx| {
                 if let Some(handle) = ctx.tcp_streams.get_mut(addr_peer) {
-                    if handle.incoming.is_empty() {
-                        handle.interests.push(IOInterestGuard {
-                            interest: self.clone(),
-                            waker: cx.waker().clone(),
-                        });
+                    if handle.incoming.is_empty() && !handle.is_read_closed() {
+                        register_interest(&mut handle.interests, self.clone(), cx.waker());
 
                         Poll::Pending
                     } else {
                         Poll::Ready(Ok(()))
                     }
                 } else {
-                    Poll::Ready(Err(Error::new(
-                        ErrorKind::Other,
-                        "Simulation context has dropped TcpStream",
-                    )))
+                    Poll::Ready(Err(ctx.tcp_stream_error(*addr_peer)))
+                }
+            }),
+            IOInterest::TcpWrite(ref addr_peer) => IOContext::with_current(|ctx| {
+                if let Some(handle) = ctx.tcp_streams.get_mut(addr_peer) {
+                    // A full send buffer is drained by the next IO tick.
+                    if handle.outgoing.is_full() && !handle.write_closed {
+                        ctx.tick_wakeups.push(cx.waker().clone());
+                        Poll::Pending
+                    } else {
+                        Poll::Ready(Ok(()))
+                    }
+                } else {
+                    Poll::Ready(Err(ctx.tcp_stream_error(*addr_peer)))
                 }
             }),
-            IOInterest::TcpWrite(_) => Poll::Ready(Ok(())),
         }
     }
 }
 
+impl IOInterest {
+    /// Removes the registrations of this interest made by `waker`.
+    pub(super) fn cancel(&self, waker: &Waker) {
+        IOContext::try_with_current(|ctx| {
+            let interests = match *self {
+                IOInterest::UdpRead(ref sock) | IOInterest::UdpWrite(ref sock) => {
+                    ctx.udp_sockets.get_mut(sock).map(|h| &mut h.interests)
+                }
+                IOInterest::TcpAccept(ref sock) => {
+                    ctx.tcp_listeners.get_mut(sock).map(|h| &mut h.interests)
+                }
+                IOInterest::TcpConnect(ref addr_peer)
+                | IOInterest::TcpRead(ref addr_peer)
+                | IOInterest::TcpWrite(ref addr_peer) => {
+                    ctx.tcp_streams.get_mut(addr_peer).map(|h| &mut h.interests)
+                }
+            };
+
+            if let Some(interests) = interests {
+                interests.retain(|guard| !(guard.interest == *self && guard.waker.will_wake(waker)));
+            }
+        });
+    }
+}
+
 // # IO Context
 
+/// The largest payload of an IPv4 UDP datagram.
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
 /// A context managing a simulated network node.
 #[derive(Debug)]
 pub struct IOContext {
@@ -227,50 +315,101 @@ pub struct IOContext {
     pub(self) interfaces: Vec<Interface>,
 
     /// Outgoing
-    pub(self) intents: Vec<IOIntent>,
+    pub(self) intents: VecDeque<IOIntent>,
+    pub(self) tx_queue_limit: Option<usize>,
+    pub(self) max_datagram_size: usize,
+    pub(self) serialization_delay: Duration,
+    pub(self) bandwidth: Option<u64>,
+    pub(self) links: BTreeMap<IpAddr, Link>,
+    pub(self) tx_wakeups: Vec<Waker>,
+    pub(self) intent_wakeups: Vec<Waker>,
+    pub(self) observer: Option<IntentObserver>,
+    pub(self) capture: Option<Vec<CaptureRecord>>,
+    pub(self) delivery_log: Option<Vec<DeliveryRecord>>,
+    pub(self) deliveries: u64,
 
     /// Registry
-    pub(super) udp_sockets: HashMap<SocketAddr, UdpSocketHandle>,
+    pub(super) udp_sockets: BTreeMap<SocketAddr, UdpSocketHandle>,
 
-    pub(self) tcp_listeners: HashMap<SocketAddr, TcpListenerHandle>,
-    pub(self) tcp_streams: HashMap<(SocketAddr, SocketAddr), TcpStreamHandle>,
-    pub(self) tcp_next_port: u16,
+    pub(self) tcp_listeners: BTreeMap<SocketAddr, TcpListenerHandle>,
+    pub(self) tcp_streams: BTreeMap<(SocketAddr, SocketAddr), TcpStreamHandle>,
+    pub(self) tcp_reset: BTreeSet<(SocketAddr, SocketAddr)>,
+    pub(self) next_ephemeral_port: u16,
 
     pub(self) tick_wakeups: Vec<Waker>,
     pub(self) next_io_tick: SimTime,
+    pub(self) next_keepalive_tick: SimTime,
+
+    /// Name resolution
+    pub(self) hosts: HashMap<String, Vec<IpAddr>>,
+    pub(self) prefer_ipv6: bool,
+
+    /// Diagnostics
+    pub(self) debug: bool,
+    pub(self) dropped_udp: usize,
+}
+
+/// The number of resources registered with an [`IOContext`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ResourceCounts {
+    /// The number of bound UDP sockets.
+    pub udp: usize,
+    /// The number of TCP listeners.
+    pub tcp_listeners: usize,
+    /// The number of open TCP streams, including streams that are still connecting.
+    ///
+    /// Dropped streams that are still closing gracefully are not included.
+    pub tcp_streams: usize,
 }
 
 impl IOContext {
     /// An empty IO Context, just a dummy
     pub fn empty() -> Self {
-        Self {
-            interfaces: Vec::new(),
-            intents: Vec::new(),
-
-            udp_sockets: HashMap::new(),
-            tcp_listeners: HashMap::new(),
-            tcp_streams: HashMap::new(),
-            tcp_next_port: 0,
-
-            tick_wakeups: Vec::new(),
-            next_io_tick: SimTime::MIN,
-        }
+        Self::from_interfaces(Vec::new())
     }
 
     /// Creates a new IO Context.
     pub fn new(ether: [u8; 6], v4: Ipv4Addr) -> Self {
-        Self {
-            interfaces: vec![Interface::loopback(), Interface::en0(ether, v4)],
+        Self::from_interfaces(vec![Interface::loopback(), Interface::en0(ether, v4)])
+    }
 
-            intents: Vec::new(),
+    /// Creates a builder for an IO Context with a custom set of interfaces.
+    pub fn builder() -> IOContextBuilder {
+        IOContextBuilder::new()
+    }
 
-            udp_sockets: HashMap::new(),
-            tcp_listeners: HashMap::new(),
-            tcp_streams: HashMap::new(),
-            tcp_next_port: 1024,
+    fn from_interfaces(interfaces: Vec<Interface>) -> Self {
+        Self {
+            interfaces,
+
+            intents: VecDeque::new(),
+            tx_queue_limit: None,
+            max_datagram_size: MAX_DATAGRAM_SIZE,
+            serialization_delay: Duration::from_millis(5),
+            bandwidth: None,
+            links: BTreeMap::new(),
+            tx_wakeups: Vec::new(),
+            intent_wakeups: Vec::new(),
+            observer: None,
+            capture: None,
+            delivery_log: None,
+            deliveries: 0,
+
+            udp_sockets: BTreeMap::new(),
+            tcp_listeners: BTreeMap::new(),
+            tcp_streams: BTreeMap::new(),
+            tcp_reset: BTreeSet::new(),
+            next_ephemeral_port: EPHEMERAL_PORT_MIN,
 
             tick_wakeups: Vec::new(),
             next_io_tick: SimTime::MIN,
+            next_keepalive_tick: SimTime::MIN,
+
+            hosts: HashMap::new(),
+            prefer_ipv6: false,
+
+            debug: false,
+            dropped_udp: 0,
         }
     }
 
@@ -294,22 +433,198 @@ impl IOContext {
 
     /// Returns the ip of the given IO Context
     pub fn get_ip(&mut self) -> Option<IpAddr> {
-        let bind = self.bind_addr(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
-        match bind {
-            Ok(v) => Some(v.ip()),
-            Err(_) => None,
+        self.primary_ip()
+    }
+
+    /// Returns the primary ip of the node.
+    ///
+    /// This is the ip that is used when binding to an unspecified address.
+    pub fn primary_ip(&self) -> Option<IpAddr> {
+        self.default_ip()
+    }
+
+    /// Sets the primary ip of the node.
+    ///
+    /// The interface providing the ip is preferred over all other interfaces,
+    /// and the ip over all other addresses of this interface, when binding
+    /// to an unspecified address.
+    pub fn set_primary_ip(&mut self, ip: IpAddr) -> Result<()> {
+        let idx = self
+            .interfaces
+            .iter()
+            .position(|interface| {
+                interface
+                    .addrs
+                    .iter()
+                    .any(|iaddr| iaddr.next_ip() == Some(ip))
+            })
+            .ok_or(SimNetError::AddrNotAvailable)?;
+
+        for interface in &mut self.interfaces {
+            interface.prio = interface.prio.max(1);
         }
+
+        let interface = &mut self.interfaces[idx];
+        interface.prio = 0;
+        let pos = interface
+            .addrs
+            .iter()
+            .position(|iaddr| iaddr.next_ip() == Some(ip))
+            .expect("interface was found by address");
+        let iaddr = interface.addrs.remove(pos);
+        interface.addrs.insert(0, iaddr);
+
+        Ok(())
+    }
+
+    /// Brings the interface with the given name up or down.
+    ///
+    /// Sockets bound to a downed interface remain registered, but sending
+    /// fails with [`SimNetError::NetworkUnreachable`] until the interface
+    /// is brought up again.
+    pub fn set_interface_up(&mut self, name: &str, up: bool) -> Result<()> {
+        let interface = self
+            .interfaces
+            .iter_mut()
+            .find(|interface| interface.name == name)
+            .ok_or(SimNetError::InterfaceNotFound)?;
+
+        interface.flags.up = up;
+        Ok(())
+    }
+
+    // Returns whether the interface providing the address is active and up.
+    fn is_link_up(&self, ip: IpAddr) -> bool {
+        self.interfaces.iter().any(|interface| {
+            interface.status == InterfaceStatus::Active
+                && interface.flags.up
+                && interface.addrs.iter().any(|iaddr| iaddr.matches_ip(ip))
+        })
     }
 
     ///
     /// Resets the context after a module restart.
     ///
+    /// Operations on TCP streams opened before the reset fail with
+    /// [`SimNetError::ConnectionReset`].
+    ///
     pub fn reset(&mut self) {
         self.intents.clear();
         self.udp_sockets.clear();
         self.tcp_listeners.clear();
-        self.tcp_streams.clear();
-        self.tcp_next_port = 1024;
+        for (key, mut handle) in std::mem::take(&mut self.tcp_streams) {
+            // Waiting tasks observe the reset once polled again.
+            handle
+                .interests
+                .drain(..)
+                .for_each(|guard| guard.waker.wake());
+            if !handle.dropped {
+                self.tcp_reset.insert(key);
+            }
+        }
+        self.next_ephemeral_port = EPHEMERAL_PORT_MIN;
+    }
+
+    ///
+    /// Returns whether the next call of [`yield_intents`](IOContext::yield_intents)
+    /// would produce intents.
+    ///
+    /// This includes buffered stream data and pending io ticks.
+    ///
+    pub fn has_pending_intents(&self) -> bool {
+        !self.intents.is_empty()
+            || !self.tick_wakeups.is_empty()
+            || self.tcp_streams.values().any(|handle| {
+                !handle.outgoing.is_empty()
+                    || (handle.write_closed && !handle.fin_sent)
+                    || handle.keepalive_ack
+            })
+    }
+
+    /// Copies the state of the context, without the wakers of waiting tasks.
+    ///
+    /// The intent observer is not part of the copy.
+    pub(crate) fn snapshot(&self) -> IOContext {
+        let mut udp_sockets = self.udp_sockets.clone();
+        udp_sockets
+            .values_mut()
+            .for_each(|handle| handle.interests.clear());
+        let mut tcp_listeners = self.tcp_listeners.clone();
+        tcp_listeners
+            .values_mut()
+            .for_each(|handle| handle.interests.clear());
+        let mut tcp_streams = self.tcp_streams.clone();
+        tcp_streams
+            .values_mut()
+            .for_each(|handle| handle.interests.clear());
+
+        IOContext {
+            interfaces: self.interfaces.clone(),
+
+            intents: self.intents.clone(),
+            tx_queue_limit: self.tx_queue_limit,
+            max_datagram_size: self.max_datagram_size,
+            serialization_delay: self.serialization_delay,
+            bandwidth: self.bandwidth,
+            links: self.links.clone(),
+            tx_wakeups: Vec::new(),
+            intent_wakeups: Vec::new(),
+            observer: None,
+            capture: self.capture.clone(),
+            delivery_log: self.delivery_log.clone(),
+            deliveries: self.deliveries,
+
+            udp_sockets,
+
+            tcp_listeners,
+            tcp_streams,
+            tcp_reset: self.tcp_reset.clone(),
+            next_ephemeral_port: self.next_ephemeral_port,
+
+            tick_wakeups: Vec::new(),
+            next_io_tick: self.next_io_tick,
+            next_keepalive_tick: self.next_keepalive_tick,
+
+            hosts: self.hosts.clone(),
+            prefer_ipv6: self.prefer_ipv6,
+
+            debug: self.debug,
+            dropped_udp: self.dropped_udp,
+        }
+    }
+
+    /// Replaces the state of the context with a snapshot.
+    ///
+    /// All tasks waiting on the current state are woken, so that they
+    /// register themselves again once polled. The intent observer is kept.
+    pub(crate) fn restore(&mut self, snapshot: &IOContext) {
+        let observer = self.observer.take();
+        let previous = std::mem::replace(self, snapshot.snapshot());
+        self.observer = observer;
+
+        let interests = previous
+            .udp_sockets
+            .into_iter()
+            .flat_map(|(_, handle)| handle.interests)
+            .chain(
+                previous
+                    .tcp_listeners
+                    .into_iter()
+                    .flat_map(|(_, handle)| handle.interests),
+            )
+            .chain(
+                previous
+                    .tcp_streams
+                    .into_iter()
+                    .flat_map(|(_, handle)| handle.interests),
+            )
+            .map(|guard| guard.waker);
+
+        interests
+            .chain(previous.tx_wakeups)
+            .chain(previous.intent_wakeups)
+            .chain(previous.tick_wakeups)
+            .for_each(Waker::wake);
     }
 
     /// with_current
@@ -328,214 +643,905 @@ impl IOContext {
         }
     }
 
-    /// yield_intents
-    pub fn yield_intents(&mut self) -> Vec<IOIntent> {
-        let mut swap = Vec::new();
-        std::mem::swap(&mut swap, &mut self.intents);
-
-        // # TCP message creation
-        let mut delay = Duration::ZERO;
-
-        for (_, handle) in self.tcp_streams.iter_mut() {
-            for packet in handle.outgoing.yield_packets() {
-                swap.push(IOIntent::TcpSendPacket(
-                    TcpMessage {
-                        content: packet,
-                        ttl: handle.config.ttl,
-                        dest_addr: handle.peer_addr,
-                        src_addr: handle.local_addr,
-                    },
-                    delay,
-                ));
-
-                delay += Duration::from_millis(5);
-            }
-        }
-
-        // # Check for IoTick
-        let tick_time = SimTime::now() + delay;
-        if !self.tick_wakeups.is_empty() && tick_time > self.next_io_tick {
-            swap.push(IOIntent::IoTick(tick_time));
-            self.next_io_tick = tick_time;
-        }
-
-        swap
+    /// Like [`with_current`](IOContext::with_current), but returns
+    /// [`SimNetError::NoContext`] instead of panicking if no IO Context is bound.
+    pub fn with_current_or_err<R>(f: impl FnOnce(&mut IOContext) -> Result<R>) -> Result<R> {
+        Self::try_with_current(f).unwrap_or_else(|| Err(SimNetError::NoContext.into()))
     }
 
-    /// io_tick
-    pub fn io_tick(&mut self) {
-        self.tick_wakeups.drain(..).for_each(|w| w.wake());
-        self.next_io_tick = SimTime::MIN;
+    ///
+    /// Registers an observer that is invoked synchronously for each
+    /// produced intent, replacing any previous observer.
+    ///
+    /// The observer is called after the intent was added to the pending
+    /// intents, so it can be used for live packet capture while the
+    /// intents are still yielded as usual.
+    ///
+    pub fn on_intent(&mut self, observer: Box<dyn FnMut(&IOIntent)>) {
+        self.observer = Some(IntentObserver(observer));
     }
 
     ///
-    /// Processes a UDP packet.
+    /// Registers `addr` as an address of the host `name`.
     ///
-    pub fn process_udp(&mut self, msg: UdpMessage) -> std::result::Result<(), UdpMessage> {
-        let sock = msg.dest_addr;
-
-        match msg.dest_addr.ip() {
-            IpAddr::V4(ip) if ip.is_broadcast() || ip.is_loopback() => {
-                // all socket received
-                let mut recv = false;
-                for (_, handle) in self
-                    .udp_sockets
-                    .iter_mut()
-                    .filter(|(k, _)| k.port() == msg.dest_addr.port())
-                {
-                    handle.incoming.push_back(msg.clone());
-                    handle.interests.drain(..).for_each(|w| w.waker.wake());
-                    recv = true;
-                    if ip.is_loopback() {
-                        break;
-                    }
-                }
-                if recv {
-                    Ok(())
-                } else {
-                    Err(msg)
-                }
-            }
-            _ => {
-                if let Some(handle) = self.udp_sockets.get_mut(&sock) {
-                    handle.incoming.push_back(msg);
-                    handle.interests.drain(..).for_each(|w| w.waker.wake());
-                    Ok(())
-                } else {
-                    println!("Dropping UDP Message :  {:?}", msg);
-                    Err(msg)
-                }
-            }
+    /// Host lookups of `name` by [`lookup_host`] and all functions taking
+    /// [`ToSocketAddrs`] resolve to all registered addresses. Names that are
+    /// not registered are resolved by the operating system.
+    ///
+    pub fn add_host(&mut self, name: impl Into<String>, addr: IpAddr) {
+        let addrs = self.hosts.entry(name.into()).or_default();
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
         }
     }
 
     ///
-    /// Processes a TCP Connection Message.
+    /// Removes all addresses registered for the host `name`.
     ///
-    pub fn process_tcp_connect(
-        &mut self,
-        msg: TcpConnectMessage,
-    ) -> std::result::Result<(), TcpConnectMessage> {
-        match msg {
-            // Server side code
-            TcpConnectMessage::ClientInitiate { client, server } => {
-                // look for listener
-                if let Some(handle) = self.tcp_listeners.get_mut(&server) {
-                    handle.incoming.push_back(TcpListenerPendingConnection {
-                        local_addr: server,
-                        peer_addr: client,
-                    });
+    pub fn remove_host(&mut self, name: &str) {
+        self.hosts.remove(name);
+    }
 
-                    // Wake up
-                    let mut i = 0;
-                    while i < handle.interests.len() {
-                        if matches!(handle.interests[i].interest, IOInterest::TcpAccept(_)) {
-                            let w = handle.interests.swap_remove(i);
-                            w.waker.wake();
-                        } else {
-                            i += 1;
-                        }
-                    }
+    ///
+    /// Sets whether host lookups yield ipv6 addresses before ipv4 addresses.
+    ///
+    /// By default ipv4 addresses are yielded first. Within each ip version
+    /// addresses are yielded in the order they were registered.
+    ///
+    pub fn set_prefer_ipv6(&mut self, prefer_ipv6: bool) {
+        self.prefer_ipv6 = prefer_ipv6;
+    }
 
-                    // Ack to client
-                    self.intents
-                        .push(IOIntent::TcpConnect(TcpConnectMessage::ServerAcknowledge {
-                            client,
-                            server,
-                        }));
-                    Ok(())
-                } else {
-                    Err(msg)
-                }
-            }
-            // Client side code
-            TcpConnectMessage::ServerAcknowledge { client, server } => {
-                if let Some(handle) = self.tcp_streams.get_mut(&(client, server)) {
-                    handle.acked = true;
+    ///
+    /// Resolves the host `name` to all registered addresses with the given port,
+    /// in priority order.
+    ///
+    /// Returns `None` if the host is not registered.
+    ///
+    pub fn lookup(&self, name: &str, port: u16) -> Option<Vec<SocketAddr>> {
+        let mut addrs = self
+            .hosts
+            .get(name)?
+            .iter()
+            .map(|ip| SocketAddr::new(*ip, port))
+            .collect::<Vec<_>>();
+        // Stable, so the registration order is kept within each version.
+        addrs.sort_by_key(|addr| addr.is_ipv4() == self.prefer_ipv6);
+        Some(addrs)
+    }
 
-                    let mut i = 0;
-                    while i < handle.interests.len() {
-                        if matches!(handle.interests[i].interest, IOInterest::TcpConnect(_)) {
-                            let w = handle.interests.swap_remove(i);
-                            w.waker.wake();
-                        } else {
-                            i += 1;
-                        }
-                    }
-                    Ok(())
-                } else {
-                    Err(msg)
-                }
-            }
-        }
+    ///
+    /// Enables or disables debug output of this context.
+    ///
+    /// When enabled, dropped sockets and unroutable datagrams
+    /// are logged to stderr. Disabled by default.
+    ///
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
     }
 
     ///
-    /// Processa a tcp packet
+    /// Returns the number of sockets, listeners and streams that are
+    /// currently registered with this context.
     ///
-    pub fn process_tcp_packet(&mut self, msg: TcpMessage) -> std::result::Result<(), TcpMessage> {
-        if let Some(handle) = self.tcp_streams.get_mut(&(msg.dest_addr, msg.src_addr)) {
-            handle.incoming.add(msg.content);
-
-            let mut i = 0;
-            while i < handle.interests.len() {
-                if matches!(handle.interests[i].interest, IOInterest::TcpRead(_)) {
-                    let w = handle.interests.swap_remove(i);
-                    w.waker.wake();
-                } else {
-                    i += 1;
-                }
-            }
-            Ok(())
-        } else {
-            Err(msg)
+    /// Since all resources deregister when dropped, this can be used to
+    /// detect leaked handles between steps of a simulation.
+    ///
+    pub fn open_resource_count(&self) -> ResourceCounts {
+        ResourceCounts {
+            udp: self.udp_sockets.len(),
+            tcp_listeners: self.tcp_listeners.len(),
+            tcp_streams: self
+                .tcp_streams
+                .values()
+                .filter(|handle| !handle.dropped)
+                .count(),
         }
     }
 
     ///
-    /// Processes a timeout
+    /// Returns the number of UDP datagrams that could not be
+    /// delivered to any socket.
     ///
-    pub fn process_tcp_connect_timeout(
-        &mut self,
-        msg: TcpConnectMessage,
-    ) -> std::result::Result<(), TcpConnectMessage> {
-        match msg {
-            TcpConnectMessage::ClientInitiate { client, server } => {
-                if let Some(handle) = self.tcp_streams.get_mut(&(client, server)) {
-                    // If no connection was established
-                    if !handle.acked {
-                        handle.connection_failed = true;
+    pub fn dropped_udp_count(&self) -> usize {
+        self.dropped_udp
+    }
 
-                        let mut i = 0;
-                        while i < handle.interests.len() {
-                            if matches!(handle.interests[i].interest, IOInterest::TcpConnect(_)) {
-                                let w = handle.interests.swap_remove(i);
-                                w.waker.wake();
-                            } else {
-                                i += 1;
-                            }
-                        }
-                    }
-                    Ok(())
-                } else {
-                    Err(msg)
-                }
+    ///
+    /// Returns all interests that tasks are currently waiting on,
+    /// keyed by the local address of the socket they refer to.
+    ///
+    /// Each interest is listed once, even if multiple tasks wait on it.
+    /// Combined with `Runtime::is_quiescent` this shows what a stalled
+    /// simulation is waiting for. Tasks blocked on a full TCP send
+    /// buffer wait for the next io tick and are not listed.
+    ///
+    pub fn blocked_interests(&self) -> Vec<(SocketAddr, IOInterest)> {
+        let udp = self
+            .udp_sockets
+            .iter()
+            .flat_map(|(addr, handle)| handle.interests.iter().map(move |g| (*addr, g)));
+        let listeners = self
+            .tcp_listeners
+            .iter()
+            .flat_map(|(addr, handle)| handle.interests.iter().map(move |g| (*addr, g)));
+        let streams = self
+            .tcp_streams
+            .iter()
+            .flat_map(|(key, handle)| handle.interests.iter().map(move |g| (key.0, g)));
+
+        let mut blocked: Vec<(SocketAddr, IOInterest)> = Vec::new();
+        for (addr, guard) in udp.chain(listeners).chain(streams) {
+            let entry = (addr, guard.interest.clone());
+            if !blocked.contains(&entry) {
+                blocked.push(entry);
             }
-            _ => unreachable!(),
         }
+        blocked
     }
-}
-
-// === UDP ===
 
-impl IOContext {
-    /// Grants the information over the udp sockets.
-    pub fn udp_sockets(&self) -> Vec<UdpSocketInfo> {
-        self.udp_sockets.iter().map(|(_, v)| v.info()).collect()
+    fn drop_udp_message(&mut self, msg: UdpMessage) -> std::result::Result<(), UdpMessage> {
+        self.dropped_udp += 1;
+        if self.debug {
+            eprintln!("Dropping UDP Message :  {:?}", msg);
+        }
+        Err(msg)
+    }
+
+    ///
+    /// Starts capturing all packets send or received by this context.
+    ///
+    /// Restarting a running capture discards all records.
+    ///
+    pub fn start_capture(&mut self) {
+        self.capture = Some(Vec::new());
+    }
+
+    ///
+    /// Returns all records captured since the last export.
+    ///
+    /// The capture keeps running. Returns an empty list if no
+    /// capture was started.
+    ///
+    pub fn export_capture(&mut self) -> Vec<CaptureRecord> {
+        self.capture
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    ///
+    /// Starts logging all messages delivered to sockets or streams of this context.
+    ///
+    /// Restarting a running log discards all records.
+    ///
+    pub fn start_delivery_log(&mut self) {
+        self.delivery_log = Some(Vec::new());
+    }
+
+    ///
+    /// Returns all messages delivered since the log was started, in delivery order.
+    ///
+    /// Returns an empty list if no log was started.
+    ///
+    pub fn delivery_log(&self) -> &[DeliveryRecord] {
+        self.delivery_log.as_deref().unwrap_or(&[])
+    }
+
+    fn log_delivery(&mut self, src: SocketAddr, dest: SocketAddr) {
+        let seq = self.deliveries;
+        self.deliveries += 1;
+        if let Some(log) = self.delivery_log.as_mut() {
+            log.push(DeliveryRecord {
+                time: SimTime::now(),
+                src,
+                dest,
+                seq,
+            });
+        }
+    }
+
+    fn capture_incoming(
+        &mut self,
+        record: impl FnOnce(SimTime, CaptureDirection) -> CaptureRecord,
+    ) {
+        if let Some(capture) = self.capture.as_mut() {
+            capture.push(record(SimTime::now(), CaptureDirection::Incoming));
+        }
+    }
+
+    ///
+    /// Limits the number of intents that are queued until the next call
+    /// of [`yield_intents`](IOContext::yield_intents), modelling the finite
+    /// transmit buffer of a NIC.
+    ///
+    /// Once the limit is reached, non-blocking UDP sends and TCP writes fail
+    /// with [`ErrorKind::WouldBlock`], while asynchronous sends and writes wait
+    /// until the intents were yielded. `None` (the default) disables the limit.
+    ///
+    pub fn set_tx_queue_limit(&mut self, limit: Option<usize>) {
+        self.tx_queue_limit = limit;
+    }
+
+    ///
+    /// Returns the limit of queued intents, if any.
+    ///
+    pub fn tx_queue_limit(&self) -> Option<usize> {
+        self.tx_queue_limit
+    }
+
+    ///
+    /// Sets the maximum size of the payload of a single UDP datagram.
+    ///
+    /// Sending a larger datagram fails with [`SimNetError::MessageSize`],
+    /// instead of queuing the packet. The size defaults to 65507 bytes,
+    /// the largest payload of an IPv4 UDP datagram.
+    ///
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.max_datagram_size = size;
+    }
+
+    ///
+    /// Returns the maximum size of the payload of a single UDP datagram.
+    ///
+    pub fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+
+    ///
+    /// Sets the delay between successive TCP packets yielded by
+    /// [`yield_intents`](IOContext::yield_intents).
+    ///
+    /// The delay defaults to 5ms. It is not applied if a bandwidth
+    /// was configured.
+    ///
+    pub fn set_serialization_delay(&mut self, delay: Duration) {
+        self.serialization_delay = delay;
+    }
+
+    ///
+    /// Returns the delay between successive TCP packets.
+    ///
+    pub fn serialization_delay(&self) -> Duration {
+        self.serialization_delay
+    }
+
+    ///
+    /// Sets the bandwidth of the node in bytes per second.
+    ///
+    /// If set, the delay between successive TCP packets yielded by
+    /// [`yield_intents`](IOContext::yield_intents) is the time needed to
+    /// transmit the preceding packet, instead of the serialization delay.
+    /// `None` (the default) disables the bandwidth model.
+    ///
+    pub fn set_bandwidth(&mut self, bytes_per_sec: Option<u64>) {
+        self.bandwidth = bytes_per_sec.filter(|&bw| bw > 0);
+    }
+
+    ///
+    /// Returns the bandwidth of the node in bytes per second, if any.
+    ///
+    pub fn bandwidth(&self) -> Option<u64> {
+        self.bandwidth
+    }
+
+    ///
+    /// Places all TCP streams with the local address `addr` on a [`Link`]
+    /// with a bandwidth of `bytes_per_sec`, shared fairly between the streams.
+    ///
+    /// `None` removes the link, including its accounting.
+    ///
+    pub fn set_link(&mut self, addr: IpAddr, bytes_per_sec: Option<u64>) {
+        match bytes_per_sec.filter(|&bw| bw > 0) {
+            Some(bw) => {
+                self.links.insert(addr, Link::new(bw));
+            }
+            None => {
+                self.links.remove(&addr);
+            }
+        }
+    }
+
+    ///
+    /// Returns the [`Link`] of the local address `addr`, if any.
+    ///
+    pub fn link(&self, addr: IpAddr) -> Option<&Link> {
+        self.links.get(&addr)
+    }
+
+    fn tx_queue_full(&self) -> bool {
+        self.tx_queue_limit
+            .map_or(false, |limit| self.intents.len() >= limit)
+    }
+
+    fn push_intent(&mut self, intent: IOIntent) {
+        self.intents.push_back(intent);
+        let intent = self.intents.back().expect("just pushed");
+        notify(&mut self.observer, &mut self.capture, intent);
+        self.wake_intent_waiters();
+    }
+
+    /// Wakes the tasks waiting in [`next_intent`](IOContext::next_intent),
+    /// once intents were queued or stream data was buffered.
+    pub(self) fn wake_intent_waiters(&mut self) {
+        for waker in self.intent_wakeups.drain(..) {
+            waker.wake();
+        }
+    }
+
+    ///
+    /// Waits for the next intent produced by the current IO Context,
+    /// removing it from the queue of pending intents.
+    ///
+    /// This allows the simulation core to be written as an async task,
+    /// reacting to intents as they are produced. Once no intents are
+    /// queued, the packets of buffered TCP data are created as done by
+    /// [`yield_intents`](IOContext::yield_intents).
+    ///
+    pub async fn next_intent() -> IOIntent {
+        crate::future::poll_fn(|cx| Self::with_current(|ctx| ctx.poll_next_intent(cx))).await
+    }
+
+    ///
+    /// Removes the oldest pending intent, or registers `cx` to be woken
+    /// once an intent is produced.
+    ///
+    pub fn poll_next_intent(&mut self, cx: &mut Context<'_>) -> Poll<IOIntent> {
+        if self.intents.is_empty() && self.has_pending_intents() {
+            let intents = self.yield_intents();
+            self.intents.extend(intents);
+        }
+
+        let intent = match self.intents.pop_front() {
+            Some(intent) => intent,
+            None => {
+                if !self.intent_wakeups.iter().any(|w| w.will_wake(cx.waker())) {
+                    self.intent_wakeups.push(cx.waker().clone());
+                }
+                return Poll::Pending;
+            }
+        };
+
+        // The transmit queue shrank, so blocked senders can continue.
+        for waker in self.tx_wakeups.drain(..) {
+            waker.wake();
+        }
+        Poll::Ready(intent)
+    }
+
+    /// yield_intents
+    pub fn yield_intents(&mut self) -> Vec<IOIntent> {
+        let mut swap = Vec::from(std::mem::take(&mut self.intents));
+
+        // The transmit queue was drained, so blocked senders can continue.
+        for waker in self.tx_wakeups.drain(..) {
+            waker.wake();
+        }
+
+        // # TCP message creation
+        let produced = swap.len();
+        let mut delay = Duration::ZERO;
+
+        let (serialization_delay, bandwidth) = (self.serialization_delay, self.bandwidth);
+        let stagger = |len: usize| match bandwidth {
+            Some(bw) => {
+                let nanos = len as u128 * 1_000_000_000 / u128::from(bw);
+                Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+            }
+            None => serialization_delay,
+        };
+
+        // Streams on a shared link take turns sending a packet while the link is idle.
+        for (ip, link) in self.links.iter_mut() {
+            while link.is_idle() {
+                let backlogged = self
+                    .tcp_streams
+                    .iter()
+                    .filter(|(key, handle)| key.0.ip() == *ip && !handle.outgoing.is_empty())
+                    .map(|(key, _)| *key)
+                    .collect::<Vec<_>>();
+                let key = match link.next_stream(&backlogged) {
+                    Some(key) => key,
+                    None => break,
+                };
+
+                let handle = self.tcp_streams.get_mut(&key).unwrap();
+                for (seq, packet) in handle.outgoing.yield_packets(1) {
+                    link.transmit(key, packet.len());
+                    swap.push(IOIntent::TcpSendPacket(
+                        TcpMessage {
+                            content: packet,
+                            seq,
+                            ttl: handle.config.ttl,
+                            dest_addr: handle.peer_addr,
+                            src_addr: handle.local_addr,
+                            keepalive: None,
+                        },
+                        Duration::from_secs(0),
+                    ));
+                }
+            }
+        }
+
+        let mut dead = Vec::new();
+        for (key, handle) in self.tcp_streams.iter_mut() {
+            // Streams with a congestion window send at most `cwnd` packets per
+            // round. Since the simulation has no acknowledgements, the window
+            // doubles after each round that used it fully.
+            let max = handle.cwnd.unwrap_or(usize::MAX);
+            let packets = if self.links.contains_key(&key.0.ip()) {
+                Vec::new()
+            } else {
+                handle.outgoing.yield_packets(max)
+            };
+            if let Some(cwnd) = handle.cwnd.as_mut() {
+                if packets.len() == *cwnd {
+                    *cwnd = cwnd.saturating_mul(2);
+                }
+            }
+
+            // Writes make progress again, so a later stall starts anew.
+            if !handle.outgoing.is_full() {
+                handle.write_stalled = None;
+            }
+
+            for (seq, packet) in packets {
+                let len = packet.len();
+                let intent = IOIntent::TcpSendPacket(
+                    TcpMessage {
+                        content: packet,
+                        seq,
+                        ttl: handle.config.ttl,
+                        dest_addr: handle.peer_addr,
+                        src_addr: handle.local_addr,
+                        keepalive: None,
+                    },
+                    delay,
+                );
+                swap.push(intent);
+
+                delay += stagger(len);
+            }
+
+            // Signal the end of the stream once all data was send.
+            if handle.write_closed && !handle.fin_sent && handle.outgoing.is_empty() {
+                handle.fin_sent = true;
+                swap.push(IOIntent::TcpSendPacket(
+                    TcpMessage {
+                        content: Vec::new(),
+                        seq: handle.outgoing.next_seq(),
+                        ttl: handle.config.ttl,
+                        dest_addr: handle.peer_addr,
+                        src_addr: handle.local_addr,
+                        keepalive: None,
+                    },
+                    delay,
+                ));
+
+                delay += stagger(0);
+            }
+
+            // # Keepalive
+            let mut keepalive = None;
+            if handle.keepalive_ack {
+                handle.keepalive_ack = false;
+                keepalive = Some(TcpKeepalive::Ack);
+            }
+            if handle.is_keepalive() && handle.keepalive_at <= SimTime::now() {
+                if handle.keepalive_probes >= handle.config.keepalive_probes {
+                    dead.push(*key);
+                    continue;
+                }
+
+                handle.keepalive_probes += 1;
+                handle.delay_keepalive();
+                keepalive = Some(TcpKeepalive::Probe);
+            }
+            if keepalive.is_some() {
+                swap.push(IOIntent::TcpSendPacket(
+                    TcpMessage {
+                        content: Vec::new(),
+                        seq: handle.outgoing.next_seq(),
+                        ttl: handle.config.ttl,
+                        dest_addr: handle.peer_addr,
+                        src_addr: handle.local_addr,
+                        keepalive,
+                    },
+                    delay,
+                ));
+
+                delay += stagger(0);
+            }
+        }
+
+        // Streams whose peer did not answer the keepalive probes are reset.
+        for key in dead {
+            self.reset_tcp_stream(key);
+        }
+
+        // Dropped streams are removed once both ends were closed.
+        let closed = self
+            .tcp_streams
+            .iter()
+            .filter(|(_, handle)| handle.dropped && handle.fin_sent && handle.peer_closed)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in closed {
+            self.tcp_streams.remove(&key);
+        }
+
+        // # Check for IoTick
+        // Packets held back by a congestion window are send on the next tick.
+        let backlog = self
+            .tcp_streams
+            .iter()
+            .filter(|(key, _)| !self.links.contains_key(&key.0.ip()))
+            .any(|(_, handle)| !handle.outgoing.is_empty());
+
+        // Packets waiting for a busy link are send once it is idle again.
+        let link_idle = self
+            .links
+            .iter()
+            .filter(|(ip, _)| {
+                self.tcp_streams
+                    .iter()
+                    .any(|(key, handle)| key.0.ip() == **ip && !handle.outgoing.is_empty())
+            })
+            .map(|(_, link)| link.busy_until())
+            .min();
+
+        // Writers blocked by a busy link wait for it to become idle.
+        let waiting = link_idle.is_some() && swap.len() == produced;
+        let wake = (!self.tick_wakeups.is_empty() && !waiting) || backlog;
+        let tick_time = SimTime::now() + delay;
+        if wake && tick_time > self.next_io_tick {
+            swap.push(IOIntent::IoTick(tick_time));
+            self.next_io_tick = tick_time;
+        }
+        if let Some(idle) = link_idle {
+            if idle > self.next_io_tick {
+                swap.push(IOIntent::IoTick(idle));
+                self.next_io_tick = idle;
+            }
+        }
+
+        // Wake the context for the next keepalive probe.
+        let keepalive = self
+            .tcp_streams
+            .values()
+            .filter(|handle| handle.is_keepalive())
+            .map(|handle| handle.keepalive_at)
+            .min();
+        if let Some(keepalive) = keepalive {
+            if keepalive != self.next_keepalive_tick {
+                swap.push(IOIntent::IoTick(keepalive));
+                self.next_keepalive_tick = keepalive;
+            }
+        }
+
+        for intent in &swap[produced..] {
+            notify(&mut self.observer, &mut self.capture, intent);
+        }
+
+        swap
+    }
+
+    /// io_tick
+    pub fn io_tick(&mut self) {
+        self.tick_wakeups.drain(..).for_each(|w| w.wake());
+        self.next_io_tick = SimTime::MIN;
+    }
+
+    ///
+    /// Processes a UDP packet.
+    ///
+    /// Returns the message if no socket could receive it. The simulation
+    /// core may return such a message to the sending node using
+    /// [`process_udp_unreachable`](IOContext::process_udp_unreachable),
+    /// modelling an ICMP port unreachable response.
+    ///
+    pub fn process_udp(&mut self, msg: UdpMessage) -> std::result::Result<(), UdpMessage> {
+        self.capture_incoming(|time, dir| CaptureRecord::udp(time, dir, &msg));
+        let sock = msg.dest_addr;
+
+        match msg.dest_addr.ip() {
+            IpAddr::V4(ip) if ip.is_broadcast() || ip.is_loopback() => {
+                // all socket received, sharing a single copy of the content
+                let mut datagram: Option<UdpDatagram> = None;
+                for (_, handle) in self
+                    .udp_sockets
+                    .iter_mut()
+                    .filter(|(k, _)| k.port() == msg.dest_addr.port())
+                    .filter(|(_, handle)| ip.is_loopback() || handle.broadcast_recv)
+                {
+                    let datagram = datagram.get_or_insert_with(|| UdpDatagram {
+                        src_addr: msg.src_addr,
+                        content: Arc::from(&msg.content[..]),
+                    });
+                    handle.incoming.push_back(datagram.clone());
+                    handle.interests.drain(..).for_each(|w| w.waker.wake());
+                    if ip.is_loopback() {
+                        break;
+                    }
+                }
+                if datagram.is_some() {
+                    self.log_delivery(msg.src_addr, msg.dest_addr);
+                    Ok(())
+                } else {
+                    self.drop_udp_message(msg)
+                }
+            }
+            _ => {
+                if let Some(handle) = self.udp_sockets.get_mut(&sock) {
+                    let src = msg.src_addr;
+                    handle.incoming.push_back(UdpDatagram::from(msg));
+                    handle.interests.drain(..).for_each(|w| w.waker.wake());
+                    self.log_delivery(src, sock);
+                    Ok(())
+                } else {
+                    self.drop_udp_message(msg)
+                }
+            }
+        }
+    }
+
+    ///
+    /// Processes the notification that a UDP packet, send by a socket
+    /// of this context, could not be delivered.
+    ///
+    /// If the sending socket is connected to the unreachable address,
+    /// its next receive call fails with [`SimNetError::ConnectionRefused`].
+    /// Returns the message if the sending socket no longer exists.
+    ///
+    pub fn process_udp_unreachable(
+        &mut self,
+        msg: UdpMessage,
+    ) -> std::result::Result<(), UdpMessage> {
+        if let Some(handle) = self.udp_sockets.get_mut(&msg.src_addr) {
+            if handle.state.peer() == Some(msg.dest_addr) {
+                handle.error = Some(SimNetError::ConnectionRefused);
+                handle.interests.drain(..).for_each(|w| w.waker.wake());
+            }
+            Ok(())
+        } else {
+            Err(msg)
+        }
+    }
+
+    ///
+    /// Processes a TCP Connection Message.
+    ///
+    pub fn process_tcp_connect(
+        &mut self,
+        msg: TcpConnectMessage,
+    ) -> std::result::Result<(), TcpConnectMessage> {
+        self.capture_incoming(|time, dir| CaptureRecord::tcp_connect(time, dir, &msg));
+        match msg {
+            // Server side code
+            TcpConnectMessage::ClientInitiate { client, server } => {
+                // look for listener
+                if let Some(handle) = self.tcp_listeners.get_mut(&server) {
+                    handle.incoming.push_back(TcpListenerPendingConnection {
+                        local_addr: server,
+                        peer_addr: client,
+                    });
+
+                    // Wake up
+                    wake_interests(&mut handle.interests, |interest| {
+                        matches!(interest, IOInterest::TcpAccept(_))
+                    });
+
+                    // Ack to client
+                    self.push_intent(IOIntent::TcpConnect(TcpConnectMessage::ServerAcknowledge {
+                        client,
+                        server,
+                    }));
+                    Ok(())
+                } else {
+                    Err(msg)
+                }
+            }
+            // Client side code
+            TcpConnectMessage::ServerAcknowledge { client, server } => {
+                if let Some(handle) = self.tcp_streams.get_mut(&(client, server)) {
+                    handle.acked = true;
+                    handle.delay_keepalive();
+
+                    wake_interests(&mut handle.interests, |interest| {
+                        matches!(interest, IOInterest::TcpConnect(_))
+                    });
+                    Ok(())
+                } else {
+                    Err(msg)
+                }
+            }
+        }
+    }
+
+    ///
+    /// Processa a tcp packet
+    ///
+    /// Returns the message if no stream could receive it. The simulation
+    /// core may return such a message to the sending node using
+    /// [`synthesize_rst`](IOContext::synthesize_rst), modelling a TCP reset.
+    ///
+    pub fn process_tcp_packet(&mut self, msg: TcpMessage) -> std::result::Result<(), TcpMessage> {
+        self.capture_incoming(|time, dir| CaptureRecord::tcp(time, dir, &msg));
+        let (src_addr, dest_addr) = (msg.src_addr, msg.dest_addr);
+        let data = msg.keepalive.is_none() && !msg.content.is_empty();
+        if let Some(handle) = self.tcp_streams.get_mut(&(dest_addr, src_addr)) {
+            // Any packet proves that the peer is alive.
+            handle.keepalive_probes = 0;
+            handle.delay_keepalive();
+
+            match msg.keepalive {
+                Some(TcpKeepalive::Probe) => handle.keepalive_ack = true,
+                Some(TcpKeepalive::Ack) => {}
+                None if msg.content.is_empty() => handle.peer_fin = Some(msg.seq),
+                None if !handle.read_closed => handle.incoming.insert(msg.seq, msg.content),
+                None => {}
+            }
+
+            // The peer closed its end, once all data before its FIN arrived.
+            if let Some(fin) = handle.peer_fin {
+                handle.peer_closed = handle.read_closed || handle.incoming.next_seq() >= fin;
+                if handle.peer_closed {
+                    handle.incoming.seal();
+                }
+            }
+
+            wake_interests(&mut handle.interests, |interest| {
+                matches!(interest, IOInterest::TcpRead(_))
+            });
+            if data {
+                self.log_delivery(src_addr, dest_addr);
+            }
+            Ok(())
+        } else {
+            Err(msg)
+        }
+    }
+
+    ///
+    /// Processes the reset of a TCP stream, caused by a packet
+    /// send by this context that found no receiving stream.
+    ///
+    /// The sending stream is removed, so that all further operations on it
+    /// fail with [`SimNetError::ConnectionReset`]. Returns the message if
+    /// the sending stream no longer exists.
+    ///
+    pub fn synthesize_rst(&mut self, msg: TcpMessage) -> std::result::Result<(), TcpMessage> {
+        let key = (msg.src_addr, msg.dest_addr);
+        if self.tcp_streams.contains_key(&key) {
+            self.reset_tcp_stream(key);
+            Ok(())
+        } else {
+            Err(msg)
+        }
+    }
+
+    ///
+    /// Processes a timeout
+    ///
+    pub fn process_tcp_connect_timeout(
+        &mut self,
+        msg: TcpConnectMessage,
+    ) -> std::result::Result<(), TcpConnectMessage> {
+        match msg {
+            TcpConnectMessage::ClientInitiate { client, server } => {
+                if let Some(handle) = self.tcp_streams.get_mut(&(client, server)) {
+                    // If no connection was established
+                    if !handle.acked {
+                        handle.connection_failed = true;
+
+                        wake_interests(&mut handle.interests, |interest| {
+                            matches!(interest, IOInterest::TcpConnect(_))
+                        });
+                    }
+                    Ok(())
+                } else {
+                    Err(msg)
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    ///
+    /// Injects a raw inbound UDP packet, as if it arrived from the network.
+    ///
+    /// Injected packets bypass all latency and loss models of the simulation
+    /// core, and are delivered immediatly. Returns the message if no socket
+    /// accepted it.
+    ///
+    pub fn inject_udp(&mut self, msg: UdpMessage) -> std::result::Result<(), UdpMessage> {
+        self.process_udp(msg)
+    }
+
+    ///
+    /// Injects a raw inbound TCP packet, as if it arrived from the network.
+    ///
+    /// Injected packets bypass all latency and loss models of the simulation
+    /// core, and are delivered immediatly. Returns the message if no stream
+    /// accepted it.
+    ///
+    pub fn inject_tcp(&mut self, msg: TcpMessage) -> std::result::Result<(), TcpMessage> {
+        self.process_tcp_packet(msg)
+    }
+
+    ///
+    /// Injects a raw inbound TCP handshake message, as if it arrived from the network.
+    ///
+    /// Injected messages bypass all latency and loss models of the simulation
+    /// core, and are delivered immediatly. Returns the message if no listener
+    /// or stream accepted it.
+    ///
+    pub fn inject_tcp_connect(
+        &mut self,
+        msg: TcpConnectMessage,
+    ) -> std::result::Result<(), TcpConnectMessage> {
+        self.process_tcp_connect(msg)
+    }
+}
+
+/// A compact summary of the node state, intended for debugging.
+impl std::fmt::Display for IOContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[IOContext]")?;
+
+        writeln!(f, "[[interfaces]]")?;
+        for interface in &self.interfaces {
+            write!(f, "{} = {}", interface.name, interface.status)?;
+            for addr in &interface.addrs {
+                write!(f, ", {}", addr)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "[[udp]]")?;
+        writeln!(f, "sockets = {}", self.udp_sockets.len())?;
+        for (addr, handle) in &self.udp_sockets {
+            match handle.state {
+                UdpSocketState::Bound => writeln!(f, "{} = bound", addr)?,
+                UdpSocketState::Connected(peer) => writeln!(f, "{} = connected {}", addr, peer)?,
+            }
+        }
+
+        writeln!(f, "[[tcp]]")?;
+        writeln!(f, "listeners = {}", self.tcp_listeners.len())?;
+        for (addr, handle) in &self.tcp_listeners {
+            let pending = handle.incoming.len();
+            writeln!(f, "{} = listening ({} pending)", addr, pending)?;
+        }
+        writeln!(f, "streams = {}", self.tcp_streams.len())?;
+        for ((addr, peer), handle) in &self.tcp_streams {
+            let state = if handle.dropped {
+                "dropped"
+            } else if !handle.acked {
+                "connecting"
+            } else if handle.write_closed || handle.peer_closed {
+                "closing"
+            } else {
+                "established"
+            };
+            writeln!(f, "{} -> {} = {} (#{})", addr, peer, state, handle.id)?;
+        }
+
+        writeln!(f, "[[intents]]")?;
+        writeln!(f, "pending = {}", self.intents.len())
+    }
+}
+
+// === UDP ===
+
+impl IOContext {
+    /// Grants the information over the udp sockets, ordered by their local address.
+    pub fn udp_sockets(&self) -> Vec<UdpSocketInfo> {
+        self.udp_sockets.iter().map(|(_, v)| v.info()).collect()
     }
 
     pub(self) fn udp_bind(&mut self, addr: SocketAddr) -> Result<UdpSocket> {
-        let addr = self.bind_addr(addr)?;
+        let addr = self.bind_addr(addr, BindProtocol::Udp)?;
 
         let buf = UdpSocketHandle {
             local_addr: addr,
@@ -544,11 +1550,13 @@ impl IOContext {
 
             ttl: 64,
             broadcast: false,
+            broadcast_recv: true,
             multicast_loop_v4: false,
             multicast_loop_v6: false,
             multicast_ttl_v4: 64,
 
             interests: Vec::new(),
+            error: None,
         };
 
         self.udp_sockets.insert(addr, buf);
@@ -565,47 +1573,84 @@ impl IOContext {
         // (1.1) Check a socket exits
         let handle = match self.udp_sockets.get(&src_addr) {
             Some(v) => v,
-            None => {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped UdpSocket",
-                ))
-            }
+            None => return Err(SimNetError::SocketDropped.into()),
         };
 
         // (1.2) Check Broadcast
         if let IpAddr::V4(dest_addr) = dest_addr.ip() {
             if dest_addr.is_broadcast() && !handle.broadcast {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "Cannot send broadcast without broadcast flag enabled",
-                ));
+                return Err(SimNetError::BroadcastDisabled.into());
             }
         }
 
+        // (1.3) Check the interface is still up
+        if !self.is_link_up(src_addr.ip()) {
+            return Err(SimNetError::NetworkUnreachable.into());
+        }
+
+        // (1.4) Check the datagram size
+        if content.len() > self.max_datagram_size {
+            return Err(SimNetError::MessageSize.into());
+        }
+
+        // (1.5) Check the transmit queue
+        if self.tx_queue_full() {
+            return Err(Error::new(ErrorKind::WouldBlock, "transmit queue is full"));
+        }
+
         // (2) Build Message
+        let multicast = match dest_addr.ip() {
+            IpAddr::V4(ip) => ip.is_multicast(),
+            IpAddr::V6(_) => false,
+        };
+        let ttl = if multicast {
+            handle.multicast_ttl_v4
+        } else {
+            handle.ttl
+        };
+
+        // Multicast datagrams without any hops never leave the node.
+        if multicast && ttl == 0 {
+            return Ok(());
+        }
+
         let msg = UdpMessage {
             content,
             src_addr,
             dest_addr,
-            ttl: handle.ttl,
+            ttl,
         };
 
         // (3) Send
-        self.intents.push(IOIntent::UdpSendPacket(msg));
+        self.push_intent(IOIntent::UdpSendPacket(msg));
 
         Ok(())
     }
 
+    pub(self) fn poll_udp_send(
+        &mut self,
+        cx: &mut Context<'_>,
+        src_addr: SocketAddr,
+        dest_addr: SocketAddr,
+        content: &[u8],
+    ) -> Poll<Result<()>> {
+        // Oversized datagrams fail without waiting for the transmit queue.
+        if content.len() > self.max_datagram_size {
+            return Poll::Ready(Err(SimNetError::MessageSize.into()));
+        }
+
+        if self.tx_queue_full() {
+            self.tx_wakeups.push(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        Poll::Ready(self.udp_send(src_addr, dest_addr, Vec::from(content)))
+    }
+
     pub(self) fn udp_connect(&mut self, socket: SocketAddr, peer: SocketAddr) -> Result<()> {
         let handle = match self.udp_sockets.get_mut(&socket) {
             Some(v) => v,
-            None => {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped UdpSocket",
-                ))
-            }
+            None => return Err(SimNetError::SocketDropped.into()),
         };
 
         handle.state = UdpSocketState::Connected(peer);
@@ -621,25 +1666,52 @@ impl IOContext {
     }
 
     pub(self) fn udp_drop(&mut self, socket: SocketAddr) {
-        println!("Dropping socket");
+        if self.debug {
+            eprintln!("Dropping socket {}", socket);
+        }
         self.udp_sockets.remove(&socket);
     }
 }
 
+struct IntentObserver(Box<dyn FnMut(&IOIntent)>);
+
+// Reports a produced intent to the observer and the capture, once it was
+// added to the pending intents.
+fn notify(
+    observer: &mut Option<IntentObserver>,
+    capture: &mut Option<Vec<CaptureRecord>>,
+    intent: &IOIntent,
+) {
+    if let Some(IntentObserver(observer)) = observer.as_mut() {
+        observer(intent);
+    }
+    if let Some(capture) = capture.as_mut() {
+        capture.extend(CaptureRecord::outgoing(intent));
+    }
+}
+
+impl std::fmt::Debug for IntentObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IntentObserver").finish()
+    }
+}
+
 #[derive(Debug, Clone)]
 #[allow(unused)]
 pub(super) struct UdpSocketHandle {
     pub(super) local_addr: SocketAddr,
     pub(super) state: UdpSocketState,
-    pub(super) incoming: VecDeque<UdpMessage>,
+    pub(super) incoming: VecDeque<UdpDatagram>,
 
     pub(super) ttl: u32,
     pub(super) broadcast: bool,
+    pub(super) broadcast_recv: bool,
     pub(super) multicast_loop_v4: bool,
     pub(super) multicast_loop_v6: bool,
     pub(super) multicast_ttl_v4: u32,
 
     pub(super) interests: Vec<IOInterestGuard>,
+    pub(super) error: Option<SimNetError>,
 }
 
 impl UdpSocketHandle {
@@ -695,15 +1767,57 @@ pub struct UdpMessage {
     pub ttl: u32,
 }
 
+impl UdpMessage {
+    /// Creates a new message from `src_addr` to `dest_addr`, with a TTL of 64.
+    pub fn new(src_addr: SocketAddr, dest_addr: SocketAddr, content: Vec<u8>) -> UdpMessage {
+        UdpMessage {
+            content,
+            src_addr,
+            dest_addr,
+            ttl: 64,
+        }
+    }
+}
+
+/// A datagram buffered at a receiving socket.
+///
+/// The content is shared, so that a broadcast to multiple sockets
+/// does not copy the payload for each recipient.
+#[derive(Debug, Clone)]
+pub(super) struct UdpDatagram {
+    pub(super) src_addr: SocketAddr,
+    pub(super) content: Arc<[u8]>,
+}
+
+impl From<UdpMessage> for UdpDatagram {
+    fn from(msg: UdpMessage) -> Self {
+        UdpDatagram {
+            src_addr: msg.src_addr,
+            content: Arc::from(msg.content),
+        }
+    }
+}
+
 // == TCP ==
 
 impl IOContext {
+    /// Returns the peers of all connections that are queued at the listener
+    /// bound to `addr`, but were not yet accepted, in the order they arrived.
+    ///
+    /// Returns an empty list if no listener is bound to `addr`.
+    pub fn listener_pending(&self, addr: SocketAddr) -> Vec<SocketAddr> {
+        self.tcp_listeners
+            .get(&addr)
+            .map(|handle| handle.incoming.iter().map(|con| con.peer_addr).collect())
+            .unwrap_or_default()
+    }
+
     pub(self) fn tcp_bind_listener(
         &mut self,
         addr: SocketAddr,
         config: Option<TcpSocketConfig>,
     ) -> Result<TcpListener> {
-        let addr = self.bind_addr(addr)?;
+        let addr = self.bind_addr(addr, BindProtocol::TcpListener)?;
 
         let buf = TcpListenerHandle {
             local_addr: addr,
@@ -711,6 +1825,10 @@ impl IOContext {
             interests: Vec::new(),
 
             config: config.unwrap_or(TcpSocketConfig::listener(addr)),
+
+            accept_rate: None,
+            accept_window: SimTime::MIN,
+            accepted: 0,
         };
 
         self.tcp_listeners.insert(addr, buf);
@@ -722,43 +1840,272 @@ impl IOContext {
         self.tcp_listeners.remove(&addr);
     }
 
+    pub(self) fn tcp_drop_stream(&mut self, key: (SocketAddr, SocketAddr)) {
+        let handle = match self.tcp_streams.get_mut(&key) {
+            Some(handle) => handle,
+            None => {
+                // The reset is no longer observable without the stream.
+                self.tcp_reset.remove(&key);
+                return;
+            }
+        };
+
+        if self.debug {
+            eprintln!("Dropping TCP stream #{} {} -> {}", handle.id, key.0, key.1);
+        }
+
+        if handle.acked && !(handle.fin_sent && handle.peer_closed) {
+            // Close the stream gracefully, so that buffered data is still
+            // send, and packets of the peer are absorbed until it closes too.
+            handle.dropped = true;
+            handle.write_closed = true;
+            handle.read_closed = true;
+            handle.incoming.clear();
+            handle.incoming.seal();
+            self.wake_intent_waiters();
+        } else {
+            self.tcp_streams.remove(&key);
+        }
+    }
+
     pub(self) fn tcp_accept(&mut self, addr: SocketAddr) -> Result<TcpStream> {
         if let Some(handle) = self.tcp_listeners.get_mut(&addr) {
+            // Pending connections are deferred once the accept rate
+            // was exceeded within the current window.
+            if handle.accept_rate.is_some() {
+                if handle.accept_throttled().is_some() {
+                    return Err(Error::new(ErrorKind::WouldBlock, "WouldBlock"));
+                }
+                if SimTime::now() >= handle.accept_window + Duration::from_secs(1) {
+                    handle.accept_window = SimTime::now();
+                    handle.accepted = 0;
+                }
+            }
+
             let con = match handle.incoming.pop_front() {
                 Some(con) => con,
                 None => return Err(Error::new(ErrorKind::WouldBlock, "WouldBlock")),
             };
+            handle.accepted = handle.accepted.saturating_add(1);
+
+            assert_eq!(con.local_addr, addr);
+
+            let config = handle.config.accept(con);
+
+            let mut buf = TcpStreamHandle {
+                id: SimContext::next_id(),
+                local_addr: con.local_addr,
+                peer_addr: con.peer_addr,
+
+                acked: true,
+                initiated: true,
+                connection_failed: false,
+
+                write_closed: false,
+                fin_sent: false,
+                peer_closed: false,
+                peer_fin: None,
+                read_closed: false,
+                dropped: false,
+
+                keepalive_at: SimTime::MAX,
+                keepalive_probes: 0,
+                keepalive_ack: false,
+                write_stalled: None,
+
+                cwnd: config.initial_cwnd.map(|cwnd| cwnd as usize),
+                incoming: SocketIncomingBuffer::new(config.recv_buffer_size),
+                interests: Vec::new(),
+                outgoing: SocketOutgoingBuffer::new(config.send_buffer_size, config.mss),
+
+                config,
+            };
+            buf.delay_keepalive();
+
+            self.tcp_reset.remove(&(con.local_addr, con.peer_addr));
+            self.tcp_streams
+                .insert((con.local_addr, con.peer_addr), buf);
+            Ok(TcpStream::from_inner(Arc::new(TcpStreamInner {
+                local_addr: con.local_addr,
+                peer_addr: con.peer_addr,
+            })))
+        } else {
+            Err(SimNetError::ListenerDropped.into())
+        }
+    }
+
+    pub(self) fn tcp_shutdown_write(&mut self, key: (SocketAddr, SocketAddr)) -> Result<()> {
+        self.tcp_shutdown(key, Shutdown::Write)
+    }
+
+    /// Shuts down the writing half of a stream, as done by `poll_shutdown`.
+    ///
+    /// Streams with a nonzero linger duration complete the shutdown only once
+    /// all buffered data and the FIN were emitted. While pending, the linger
+    /// duration is returned, bounding the wait.
+    pub(self) fn poll_tcp_shutdown_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        key: (SocketAddr, SocketAddr),
+    ) -> (Poll<Result<()>>, Option<Duration>) {
+        if let Err(e) = self.tcp_shutdown_write(key) {
+            return (Poll::Ready(Err(e)), None);
+        }
+
+        let handle = &self.tcp_streams[&key];
+        match handle.config.linger {
+            Some(linger) if linger > Duration::from_secs(0) && !handle.fin_sent => {
+                // The FIN is emitted by `yield_intents`, followed by an IoTick.
+                self.tick_wakeups.push(cx.waker().clone());
+                (Poll::Pending, Some(linger))
+            }
+            _ => (Poll::Ready(Ok(())), None),
+        }
+    }
+
+    fn tcp_shutdown(&mut self, key: (SocketAddr, SocketAddr), how: Shutdown) -> Result<()> {
+        if let Some(handle) = self.tcp_streams.get_mut(&key) {
+            if matches!(how, Shutdown::Write | Shutdown::Both) {
+                handle.write_closed = true;
+            }
+            if matches!(how, Shutdown::Read | Shutdown::Both) {
+                // Discard all unread data, so that reads return EOF.
+                handle.read_closed = true;
+                handle.incoming.clear();
+                handle.incoming.seal();
+                wake_interests(&mut handle.interests, |interest| {
+                    matches!(interest, IOInterest::TcpRead(_))
+                });
+            }
+
+            // Tasks awaiting intents create the FIN.
+            self.wake_intent_waiters();
+            Ok(())
+        } else {
+            Err(self.tcp_stream_error(key))
+        }
+    }
+
+    /// Removes a stream, so that all further operations on it
+    /// fail with [`SimNetError::ConnectionReset`].
+    pub(self) fn reset_tcp_stream(&mut self, key: (SocketAddr, SocketAddr)) {
+        if let Some(mut handle) = self.tcp_streams.remove(&key) {
+            if self.debug {
+                eprintln!("Resetting TCP stream #{} {} -> {}", handle.id, key.0, key.1);
+            }
+            handle.interests.drain(..).for_each(|guard| guard.waker.wake());
+            if !handle.dropped {
+                self.tcp_reset.insert(key);
+            }
+        }
+    }
+
+    /// Writes `bufs` to the send buffer of a stream, as done by `try_write`.
+    ///
+    /// Fails with [`ErrorKind::WouldBlock`] if neither the transmit queue
+    /// nor the send buffer has room for any data.
+    pub(self) fn tcp_write(
+        &mut self,
+        key: (SocketAddr, SocketAddr),
+        bufs: &[IoSlice<'_>],
+    ) -> Result<usize> {
+        let tx_queue_full = self.tx_queue_full();
+        let handle = match self.tcp_streams.get_mut(&key) {
+            Some(handle) => handle,
+            None => return Err(self.tcp_stream_error(key)),
+        };
+
+        if handle.write_closed {
+            return Err(SimNetError::WriteClosed.into());
+        }
+        if tx_queue_full {
+            return Err(Error::new(ErrorKind::WouldBlock, "transmit queue is full"));
+        }
+
+        let total: usize = bufs.iter().map(|buf| buf.len()).sum();
+        let mut written = 0;
+        for buf in bufs {
+            if let Err(rem) = handle.outgoing.write(buf) {
+                written += buf.len() - rem.len();
+                break;
+            }
+            written += buf.len();
+        }
+
+        if written == 0 && total != 0 {
+            return Err(Error::new(ErrorKind::WouldBlock, "send buffer is full"));
+        }
 
-            assert_eq!(con.local_addr, addr);
+        handle.write_stalled = None;
+        self.wake_intent_waiters();
+        Ok(written)
+    }
 
-            let config = handle.config.accept(con);
+    /// Writes `bufs` to the send buffer of a stream, as done by `poll_write`.
+    ///
+    /// While pending, the write timeout and the time at which a stalled
+    /// write resets the stream are returned, see `tcp_write_stalled`.
+    pub(self) fn poll_tcp_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        key: (SocketAddr, SocketAddr),
+        bufs: &[IoSlice<'_>],
+    ) -> (Poll<Result<usize>>, Option<Duration>, Option<SimTime>) {
+        let tx_queue_full = self.tx_queue_full();
+        match self.tcp_write(key, bufs) {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            r => return (Poll::Ready(r), None, None),
+        }
 
-            let buf = TcpStreamHandle {
-                local_addr: con.local_addr,
-                peer_addr: con.peer_addr,
+        if tx_queue_full {
+            self.tx_wakeups.push(cx.waker().clone());
+            return (Poll::Pending, None, None);
+        }
 
-                acked: true,
-                connection_failed: false,
+        // The send buffer is full until the next IO tick drains it.
+        let timeout = self.tcp_streams[&key].config.write_timeout;
+        match self.tcp_write_stalled(key) {
+            Ok(stall) => {
+                self.tick_wakeups.push(cx.waker().clone());
+                (Poll::Pending, timeout, stall)
+            }
+            Err(e) => (Poll::Ready(Err(e)), None, None),
+        }
+    }
 
-                incoming: SocketIncomingBuffer::new(config.recv_buffer_size),
-                interests: Vec::new(),
-                outgoing: SocketOutgoingBuffer::new(config.send_buffer_size),
+    /// Marks a write on the stream as blocked by a full send buffer.
+    ///
+    /// Returns the time at which the stream is reset, if the send buffer
+    /// stays full. Once that time passed, the stream is reset instead.
+    pub(self) fn tcp_write_stalled(
+        &mut self,
+        key: (SocketAddr, SocketAddr),
+    ) -> Result<Option<SimTime>> {
+        let handle = match self.tcp_streams.get_mut(&key) {
+            Some(handle) => handle,
+            None => return Err(self.tcp_stream_error(key)),
+        };
 
-                config,
-            };
-            self.tcp_streams
-                .insert((con.local_addr, con.peer_addr), buf);
-            Ok(TcpStream {
-                inner: Arc::new(TcpStreamInner {
-                    local_addr: con.local_addr,
-                    peer_addr: con.peer_addr,
-                }),
-            })
+        let since = *handle.write_stalled.get_or_insert_with(SimTime::now);
+        let deadline = match handle.config.write_stall_timeout {
+            Some(timeout) => since.checked_add(timeout).unwrap_or(SimTime::MAX),
+            None => return Ok(None),
+        };
+
+        if deadline <= SimTime::now() {
+            self.reset_tcp_stream(key);
+            return Err(SimNetError::ConnectionReset.into());
+        }
+        Ok(Some(deadline))
+    }
+
+    // Distinguishes streams that were reset with the context, or
+    // by missing keepalive probes or stalled writes, from streams that were dropped.
+    fn tcp_stream_error(&self, key: (SocketAddr, SocketAddr)) -> Error {
+        if self.tcp_reset.contains(&key) {
+            SimNetError::ConnectionReset.into()
         } else {
-            Err(Error::new(
-                ErrorKind::Other,
-                "Simulation context has dropped TcpListener",
-            ))
+            SimNetError::StreamDropped.into()
         }
     }
 
@@ -773,97 +2120,110 @@ impl IOContext {
                 .as_ref()
                 .map(|c| c.addr)
                 .unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)),
+            BindProtocol::TcpStream,
         )?;
 
         let config = config.unwrap_or(TcpSocketConfig::stream(addr));
 
         let buf = TcpStreamHandle {
+            id: SimContext::next_id(),
             local_addr: addr,
             peer_addr: peer,
 
             acked: false,
+            initiated: false,
             connection_failed: false,
 
+            write_closed: false,
+            fin_sent: false,
+            peer_closed: false,
+            peer_fin: None,
+            read_closed: false,
+            dropped: false,
+
+            keepalive_at: SimTime::MAX,
+            keepalive_probes: 0,
+            keepalive_ack: false,
+            write_stalled: None,
+
+            cwnd: config.initial_cwnd.map(|cwnd| cwnd as usize),
             incoming: SocketIncomingBuffer::new(config.recv_buffer_size),
             interests: Vec::new(),
-            outgoing: SocketOutgoingBuffer::new(config.send_buffer_size),
+            outgoing: SocketOutgoingBuffer::new(config.send_buffer_size, config.mss),
 
             config,
         };
 
+        self.tcp_reset.remove(&(addr, peer));
         self.tcp_streams.insert((addr, peer), buf);
 
-        return Ok(TcpStream {
-            inner: Arc::new(TcpStreamInner {
-                local_addr: addr,
-                peer_addr: peer,
-            }),
-        });
+        return Ok(TcpStream::from_inner(Arc::new(TcpStreamInner {
+            local_addr: addr,
+            peer_addr: peer,
+        })));
     }
 }
 
 impl IOContext {
-    // Finds and confirms an address.
-    fn bind_addr(&mut self, mut addr: SocketAddr) -> Result<SocketAddr> {
-        if addr.ip().is_unspecified() {
-            // # Case 1: Unspecified address.
-
-            // go for default interface;
-            let mut intf = self
-                .interfaces
-                .iter()
-                .map(|i| (i, i.prio))
-                .collect::<Vec<_>>();
-
-            intf.sort_by(|(_, lhs), (_, rhs)| lhs.cmp(rhs));
-
-            // Iterate through
-            for (interface, _) in intf {
-                // Skip inactive ones
-                if interface.status == InterfaceStatus::Inactive {
-                    continue;
-                }
+    // Finds the address used for binding to an unspecified address.
+    fn default_ip(&self) -> Option<IpAddr> {
+        let mut intf = self
+            .interfaces
+            .iter()
+            .map(|i| (i, i.prio))
+            .collect::<Vec<_>>();
+
+        intf.sort_by(|(_, lhs), (_, rhs)| lhs.cmp(rhs));
+
+        // Iterate through
+        for (interface, _) in intf {
+            // Skip inactive ones
+            if interface.status == InterfaceStatus::Inactive {
+                continue;
+            }
 
-                if !interface.flags.up {
-                    continue;
-                }
+            if !interface.flags.up {
+                continue;
+            }
 
-                // get a good addr
-                for iaddr in &interface.addrs {
-                    if let Some(next) = iaddr.next_ip() {
-                        let mut addr = SocketAddr::new(next, addr.port());
+            // get a good addr
+            if let Some(next) = interface.addrs.iter().find_map(InterfaceAddr::next_ip) {
+                return Some(next);
+            }
+            // WELP next interface
+        }
 
-                        if addr.port() == 0 {
-                            // Grab the next one
-                            loop {
-                                addr.set_port(self.tcp_next_port);
-                                self.tcp_next_port += 1;
+        None
+    }
 
-                                if self.tcp_listeners.get(&addr).is_none() {
-                                    break;
-                                }
-                            }
-                        } else {
-                            // Check for collision
-                            // TODO
-                        }
+    // Finds and confirms an address.
+    //
+    // Addresses are in use per protocol and per ip, so the same port may be
+    // bound on different interface addresses, or by UDP and TCP at once.
+    fn bind_addr(&mut self, mut addr: SocketAddr, protocol: BindProtocol) -> Result<SocketAddr> {
+        if addr.ip().is_unspecified() {
+            // # Case 1: Unspecified address.
 
-                        return Ok(addr);
-                    }
-                }
-                // WELP next interface
+            // go for default interface;
+            let next = match self.default_ip() {
+                Some(next) => next,
+                None => return Err(SimNetError::AddrNotAvailable.into()),
+            };
+            let addr = SocketAddr::new(next, addr.port());
+
+            if addr.port() == 0 {
+                self.ephemeral_addr(addr.ip(), protocol)
+            } else if self.addr_in_use(addr, protocol) {
+                Err(SimNetError::AddrInUse.into())
+            } else {
+                Ok(addr)
             }
-
-            Err(Error::new(
-                ErrorKind::AddrNotAvailable,
-                "Address not available",
-            ))
         } else {
             // # Case 2: Direct reference to a given interface
 
             // Check for sockets that allready have this key
-            if self.tcp_listeners.get(&addr).is_some() {
-                return Err(Error::new(ErrorKind::AddrInUse, "Address allready in use"));
+            if addr.port() != 0 && self.addr_in_use(addr, protocol) {
+                return Err(SimNetError::AddrInUse.into());
             }
 
             // Find right interface
@@ -875,38 +2235,73 @@ impl IOContext {
                 {
                     // Found the right interface
                     if interface.status == InterfaceStatus::Inactive {
-                        return Err(Error::new(ErrorKind::NotFound, "Interface inactive"));
+                        return Err(SimNetError::InterfaceInactive.into());
                     }
 
                     if !interface.flags.up {
-                        return Err(Error::new(ErrorKind::NotFound, "Interface down"));
+                        return Err(SimNetError::InterfaceDown.into());
                     }
 
                     // Ip Check now check for port number
                     if addr.port() == 0 {
-                        // Grab the next one
-                        loop {
-                            addr.set_port(self.tcp_next_port);
-                            self.tcp_next_port += 1;
-
-                            if self.tcp_listeners.get(&addr).is_none() {
-                                break;
-                            }
-                        }
+                        addr = self.ephemeral_addr(addr.ip(), protocol)?;
                     }
                     // Else no collision possible
                     return Ok(addr);
                 }
             }
 
-            Err(Error::new(
-                ErrorKind::AddrNotAvailable,
-                "Address not available",
-            ))
+            Err(SimNetError::AddrNotAvailable.into())
+        }
+    }
+
+    // Grabs the next port that is not in use on `ip`, wrapping around
+    // within the ephemeral range until every port was tried once.
+    fn ephemeral_addr(&mut self, ip: IpAddr, protocol: BindProtocol) -> Result<SocketAddr> {
+        let mut addr = SocketAddr::new(ip, 0);
+        for _ in EPHEMERAL_PORT_MIN..=u16::MAX {
+            addr.set_port(self.next_ephemeral_port);
+            self.next_ephemeral_port = match self.next_ephemeral_port {
+                u16::MAX => EPHEMERAL_PORT_MIN,
+                port => port + 1,
+            };
+
+            // Ephemeral stream ports additionally avoid the ports
+            // of listeners and other streams.
+            let taken = self.addr_in_use(addr, protocol)
+                || (protocol == BindProtocol::TcpStream
+                    && (self.tcp_listeners.contains_key(&addr)
+                        || self.tcp_streams.keys().any(|(local, _)| *local == addr)));
+            if !taken {
+                return Ok(addr);
+            }
+        }
+
+        Err(SimNetError::AddrInUse.into())
+    }
+
+    fn addr_in_use(&self, addr: SocketAddr, protocol: BindProtocol) -> bool {
+        match protocol {
+            BindProtocol::Udp => self.udp_sockets.contains_key(&addr),
+            BindProtocol::TcpListener => self.tcp_listeners.contains_key(&addr),
+            // Streams may share their local address with a listener,
+            // or with other streams to different peers.
+            BindProtocol::TcpStream => false,
         }
     }
 }
 
+/// The first port handed out for binds to port 0.
+const EPHEMERAL_PORT_MIN: u16 = 1024;
+
+/// The kind of socket an address is bound for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BindProtocol {
+    Udp,
+    TcpListener,
+    TcpStream,
+}
+
 #[derive(Debug, Clone)]
 #[allow(unused)]
 pub(self) struct TcpListenerHandle {
@@ -915,6 +2310,26 @@ pub(self) struct TcpListenerHandle {
     pub(super) incoming: VecDeque<TcpListenerPendingConnection>,
     pub(self) config: TcpSocketConfig,
     pub(super) interests: Vec<IOInterestGuard>,
+
+    /// The maximum number of accepts per second, together with the
+    /// start of the current window and the accepts within it.
+    pub(super) accept_rate: Option<u32>,
+    pub(super) accept_window: SimTime,
+    pub(super) accepted: u32,
+}
+
+impl TcpListenerHandle {
+    /// Returns the end of the current accept window, if the accept
+    /// rate was exceeded within it.
+    pub(super) fn accept_throttled(&self) -> Option<SimTime> {
+        let rate = self.accept_rate?;
+        let end = self.accept_window + Duration::from_secs(1);
+        if self.accepted >= rate && SimTime::now() < end {
+            Some(end)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -968,12 +2383,34 @@ impl TcpConnectMessage {
 #[derive(Debug, Clone)]
 #[allow(unused)]
 pub(self) struct TcpStreamHandle {
+    /// The id of the connection, unique within the simulation.
+    pub(super) id: usize,
     pub(super) local_addr: SocketAddr,
     pub(super) peer_addr: SocketAddr,
 
     pub(super) acked: bool,
+    pub(super) initiated: bool,
     pub(super) connection_failed: bool,
 
+    pub(super) write_closed: bool,
+    pub(super) fin_sent: bool,
+    pub(super) peer_closed: bool,
+    pub(super) peer_fin: Option<u64>,
+    pub(super) read_closed: bool,
+    pub(super) dropped: bool,
+
+    /// The time of the next keepalive probe, the number of
+    /// unanswered probes and whether a probe must be answered.
+    pub(super) keepalive_at: SimTime,
+    pub(super) keepalive_probes: u32,
+    pub(super) keepalive_ack: bool,
+
+    /// The time since which writes are blocked by a full send buffer.
+    pub(super) write_stalled: Option<SimTime>,
+
+    /// The number of packets that may be send per round,
+    /// or `None` if the stream is not limited.
+    pub(super) cwnd: Option<usize>,
     pub(super) incoming: SocketIncomingBuffer,
     pub(super) interests: Vec<IOInterestGuard>,
     pub(super) outgoing: SocketOutgoingBuffer,
@@ -981,15 +2418,765 @@ pub(self) struct TcpStreamHandle {
     pub(self) config: TcpSocketConfig,
 }
 
+impl TcpStreamHandle {
+    /// Returns whether reads on the stream signal EOF, once all buffered
+    /// data was consumed.
+    pub(super) fn is_read_closed(&self) -> bool {
+        self.incoming.is_sealed()
+    }
+
+    /// Returns whether the stream sends keepalive probes.
+    pub(super) fn is_keepalive(&self) -> bool {
+        self.config.keepalive.is_some() && self.acked && !self.peer_closed && !self.dropped
+    }
+
+    /// Delays the next keepalive probe by the keepalive interval.
+    pub(super) fn delay_keepalive(&mut self) {
+        self.keepalive_at = match self.config.keepalive {
+            Some(interval) => SimTime::now()
+                .checked_add(interval)
+                .unwrap_or(SimTime::MAX),
+            None => SimTime::MAX,
+        };
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// A UDP Message in the network.
 pub struct TcpMessage {
     /// The content byte-encoded.
     pub content: Vec<u8>,
+    /// The stream offset of the first content byte.
+    ///
+    /// Packets without content signal the end of the stream
+    /// after all data up to this offset.
+    pub seq: u64,
     /// The senders bound address.
     pub src_addr: SocketAddr,
     /// The receivers address.
     pub dest_addr: SocketAddr,
     /// Time-To-Live
     pub ttl: u32,
+    /// Marks a packet without content as a keepalive probe or its answer,
+    /// instead of the end of the stream.
+    pub keepalive: Option<TcpKeepalive>,
+}
+
+impl TcpMessage {
+    /// Creates a new packet from `src_addr` to `dest_addr`, with a TTL of 64.
+    ///
+    /// The content starts at stream offset zero. Use [`seq`](TcpMessage::seq)
+    /// to place it elsewhere in the stream.
+    pub fn new(src_addr: SocketAddr, dest_addr: SocketAddr, content: Vec<u8>) -> TcpMessage {
+        TcpMessage {
+            content,
+            seq: 0,
+            src_addr,
+            dest_addr,
+            ttl: 64,
+            keepalive: None,
+        }
+    }
+}
+
+/// The type of a keepalive packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TcpKeepalive {
+    /// A probe, checking whether the peer is still alive.
+    Probe,
+    /// The answer to a probe.
+    Ack,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::runtime::Builder;
+    use crate::sim::SimContext;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn observe_udp_intents() {
+        let (rt, ctx) = testutil::node("observer");
+        let guard = rt.enter_context(ctx);
+
+        let observed = Rc::new(Cell::new(0));
+        let counter = observed.clone();
+        IOContext::with_current(|ctx| {
+            ctx.on_intent(Box::new(move |intent| {
+                if let IOIntent::UdpSendPacket(_) = intent {
+                    counter.set(counter.get() + 1);
+                }
+            }))
+        });
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            for i in 0..10u8 {
+                socket.send_to(&[i], "192.168.2.2:100").await.unwrap();
+            }
+        });
+        rt.block_or_idle_on(handle).unwrap().unwrap();
+
+        assert_eq!(observed.get(), 10);
+        assert_eq!(rt.yield_intents().len(), 10);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn router_task_awaits_intents() {
+        let (rt, ctx) = testutil::node("next_intent");
+        let guard = rt.enter_context(ctx);
+
+        // The router delivers all datagrams, without being driven by the test.
+        let router = rt.spawn(async {
+            let mut delivered = 0;
+            while delivered < 2 {
+                if let IOIntent::UdpSendPacket(msg) = IOContext::next_intent().await {
+                    IOContext::with_current(|ctx| ctx.process_udp(msg)).unwrap();
+                    delivered += 1;
+                }
+            }
+            delivered
+        });
+
+        let server = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:7").await?;
+            let mut buf = [0u8; 16];
+            let (n, from) = socket.recv_from(&mut buf).await?;
+            assert_eq!(&buf[..n], b"ping");
+            socket.send_to(b"pong", from).await?;
+            Result::Ok(())
+        });
+        let client = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:1000").await?;
+            socket.send_to(b"ping", "192.168.2.1:7").await?;
+            let mut buf = [0u8; 16];
+            let (n, _) = socket.recv_from(&mut buf).await?;
+            Result::Ok(buf[..n].to_vec())
+        });
+
+        let pong = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(pong, b"pong");
+        rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(rt.block_or_idle_on(router).unwrap().unwrap(), 2);
+        assert!(rt.yield_intents().is_empty());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn router_task_awaits_tcp_packets() {
+        let (rt, ctx) = testutil::node("next_intent_tcp");
+        let guard = rt.enter_context(ctx);
+
+        let router = rt.spawn(async {
+            loop {
+                let intent = IOContext::next_intent().await;
+                IOContext::with_current(|ctx| match intent {
+                    IOIntent::TcpConnect(msg) => ctx.process_tcp_connect(msg).unwrap(),
+                    IOIntent::TcpSendPacket(msg, _) => ctx.process_tcp_packet(msg).unwrap(),
+                    IOIntent::IoTick(_) => ctx.io_tick(),
+                    _ => {}
+                });
+            }
+        });
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        let payload = (0..8192).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let data = payload.clone();
+        let client = rt.spawn(async move {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await?;
+            stream.write_all(&data).await?;
+            AsyncWriteExt::shutdown(&mut stream).await?;
+            Result::Ok(stream)
+        });
+
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(buf, payload);
+
+        router.abort();
+        rt.poll_until_idle();
+        guard.leave();
+    }
+
+    #[test]
+    fn primary_ip_selects_unspecified_bind() {
+        let (rt, ctx) = testutil::node("primary");
+        let guard = rt.enter_context(ctx);
+
+        let en0 = IpAddr::V4(Ipv4Addr::new(192, 168, 2, 1));
+        let lo0 = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let bind = || {
+            rt.block_on(async {
+                let socket = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+                socket.local_addr().unwrap().ip()
+            })
+        };
+
+        assert_eq!(IOContext::with_current(|ctx| ctx.primary_ip()), Some(en0));
+        assert_eq!(bind(), en0);
+
+        IOContext::with_current(|ctx| ctx.set_primary_ip(lo0)).unwrap();
+        assert_eq!(IOContext::with_current(|ctx| ctx.primary_ip()), Some(lo0));
+        assert_eq!(get_ip(), Some(lo0));
+        assert_eq!(bind(), lo0);
+
+        let unknown = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        assert!(IOContext::with_current(|ctx| ctx.set_primary_ip(unknown)).is_err());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn message_constructors_round_trip() {
+        let (rt, ctx) = testutil::node("constructors");
+        let guard = rt.enter_context(ctx);
+
+        let local: SocketAddr = "192.168.2.1:1000".parse().unwrap();
+        let peer: SocketAddr = "10.0.0.1:2000".parse().unwrap();
+
+        let msg = UdpMessage::new(peer, local, b"ping".to_vec());
+        assert_eq!(msg.ttl, 64);
+
+        let handle = rt.spawn(async move {
+            let socket = UdpSocket::bind(local).await.unwrap();
+            let mut buf = [0u8; 8];
+            let (n, from) = socket.recv_from(&mut buf).await.unwrap();
+            (buf[..n].to_vec(), from)
+        });
+        rt.poll_until_idle();
+        rt.process_udp(msg).unwrap();
+
+        let (content, from) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(content, b"ping");
+        assert_eq!(from, peer);
+
+        let msg = TcpMessage::new(peer, local, b"ping".to_vec());
+        assert_eq!((msg.ttl, msg.seq, msg.keepalive), (64, 0, None));
+        assert_eq!(msg.content, b"ping");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn tcp_connect_emits_intents_once() {
+        let (rt, ctx) = testutil::node("connect");
+        let guard = rt.enter_context(ctx);
+
+        let peer: SocketAddr = "192.168.2.2:80".parse().unwrap();
+        let stream = IOContext::with_current(|ctx| ctx.tcp_bind_stream(peer, None)).unwrap();
+
+        let mut interest = IOInterest::TcpConnect((stream.inner.local_addr, peer));
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        for _ in 0..5 {
+            assert!(Pin::new(&mut interest).poll(&mut cx).is_pending());
+        }
+
+        let intents = rt.yield_intents();
+        let connects = intents
+            .iter()
+            .filter(|i| matches!(i, IOIntent::TcpConnect(_)))
+            .count();
+        let timeouts = intents
+            .iter()
+            .filter(|i| matches!(i, IOIntent::TcpConnectTimeout(..)))
+            .count();
+        assert_eq!((connects, timeouts), (1, 1));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn distinct_interests_register_separately() {
+        let (rt, ctx) = testutil::node("interests");
+        let guard = rt.enter_context(ctx);
+
+        let peer: SocketAddr = "192.168.2.2:80".parse().unwrap();
+        let stream = IOContext::with_current(|ctx| ctx.tcp_bind_stream(peer, None)).unwrap();
+        let key = (stream.inner.local_addr, peer);
+
+        let mut read = IOInterest::TcpRead(key);
+        let mut connect = IOInterest::TcpConnect(key);
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        for _ in 0..3 {
+            assert!(Pin::new(&mut read).poll(&mut cx).is_pending());
+            assert!(Pin::new(&mut connect).poll(&mut cx).is_pending());
+        }
+
+        let interests = IOContext::with_current(|ctx| ctx.tcp_streams[&key].interests.len());
+        assert_eq!(interests, 2);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn count_dropped_udp() {
+        let (rt, ctx) = testutil::node("dropped_udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.send_to(b"lost", "192.168.2.1:101").await.unwrap();
+            socket.send_to(b"self", "192.168.2.1:100").await.unwrap();
+            socket
+        });
+        let _socket = rt.block_or_idle_on(handle).unwrap().unwrap();
+
+        let results = rt
+            .yield_intents()
+            .into_iter()
+            .filter_map(|intent| match intent {
+                IOIntent::UdpSendPacket(msg) => Some(rt.process_udp(msg).is_ok()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(results, vec![false, true]);
+        assert_eq!(IOContext::with_current(|ctx| ctx.dropped_udp_count()), 1);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn udp_sockets_in_stable_order() {
+        let bound = |name: &str| {
+            let (rt, ctx) = testutil::node(name);
+            let guard = rt.enter_context(ctx);
+
+            let handle = rt.spawn(async {
+                let mut sockets = Vec::new();
+                for port in &[300, 100, 400, 200] {
+                    sockets.push(UdpSocket::bind(("192.168.2.1", *port)).await.unwrap());
+                }
+                sockets
+            });
+            let _sockets = rt.block_or_idle_on(handle).unwrap().unwrap();
+
+            let infos = IOContext::with_current(|ctx| ctx.udp_sockets());
+            guard.leave();
+            infos
+                .into_iter()
+                .map(|info| info.addr.port())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(bound("first"), vec![100, 200, 300, 400]);
+        assert_eq!(bound("second"), vec![100, 200, 300, 400]);
+    }
+
+    #[test]
+    fn accept_waiters_wake_in_order() {
+        let (rt, ctx) = testutil::node("accept_order");
+        let guard = rt.enter_context(ctx);
+
+        let listener = rt
+            .block_on(TcpListener::bind("127.0.0.1:80"))
+            .map(std::sync::Arc::new)
+            .unwrap();
+
+        let mut waiters = Vec::new();
+        for _ in 0..3 {
+            let listener = listener.clone();
+            waiters.push(rt.spawn(async move { listener.accept().await.unwrap().1 }));
+            rt.poll_until_idle();
+        }
+
+        let clients = (0..3)
+            .map(|_| rt.spawn(TcpStream::connect("127.0.0.1:80")))
+            .collect::<Vec<_>>();
+        rt.poll_until_idle();
+
+        // Deliver all handshakes before any waiter is polled again.
+        let mut initiated = Vec::new();
+        for intent in rt.yield_intents() {
+            if let IOIntent::TcpConnect(msg) = intent {
+                if let TcpConnectMessage::ClientInitiate { client, .. } = msg {
+                    initiated.push(client);
+                }
+                rt.process_tcp_connect(msg).unwrap();
+            }
+        }
+
+        let accepted = waiters
+            .into_iter()
+            .map(|waiter| rt.block_or_idle_on(waiter).unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(accepted, initiated);
+
+        drop(clients);
+        guard.leave();
+    }
+
+    #[test]
+    fn accept_rate_spreads_connections() {
+        let (rt, ctx) = testutil::node("accept_rate");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await.unwrap();
+            listener.set_accept_rate(Some(2)).unwrap();
+
+            let mut accepted = Vec::new();
+            for _ in 0..6 {
+                let (stream, _) = listener.accept().await.unwrap();
+                accepted.push((stream, SimTime::now()));
+            }
+
+            // Clearing the limit admits the remaining connections at once.
+            listener.set_accept_rate(None).unwrap();
+            assert_eq!(listener.accept_rate().unwrap(), None);
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                accepted.push((stream, SimTime::now()));
+            }
+            accepted
+        });
+        rt.poll_until_idle();
+
+        let clients = (0..8)
+            .map(|_| rt.spawn(TcpStream::connect("127.0.0.1:80")))
+            .collect::<Vec<_>>();
+        for _ in 0..4 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let IOIntent::TcpConnect(msg) = intent {
+                    rt.process_tcp_connect(msg).unwrap();
+                }
+            }
+        }
+        for _ in 0..3 {
+            rt.advance(Duration::from_secs(1));
+            rt.poll_until_idle();
+        }
+
+        let times = rt
+            .block_or_idle_on(server)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|(_, time)| time.as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(times, vec![0, 0, 1, 1, 2, 2, 2, 2]);
+
+        drop(clients);
+        guard.leave();
+    }
+
+    #[test]
+    fn listener_pending_before_accept() {
+        let (rt, ctx) = testutil::node("listener_pending");
+        let guard = rt.enter_context(ctx);
+
+        let addr: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        let listener = rt
+            .block_or_idle_on(rt.spawn(TcpListener::bind(addr)))
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        let clients = (0..3)
+            .map(|_| rt.spawn(TcpStream::connect(addr)))
+            .collect::<Vec<_>>();
+        for _ in 0..2 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let IOIntent::TcpConnect(msg) = intent {
+                    rt.process_tcp_connect(msg).unwrap();
+                }
+            }
+        }
+
+        // Three clients are queued, but none was accepted yet.
+        let clients = clients
+            .into_iter()
+            .map(|client| rt.block_or_idle_on(client).unwrap().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        let peers = clients
+            .iter()
+            .map(|client| client.local_addr().unwrap())
+            .collect::<Vec<_>>();
+        let pending = IOContext::with_current(|ctx| ctx.listener_pending(addr));
+        assert_eq!(pending, peers);
+
+        let accept = rt.spawn(async move {
+            let (_, peer) = listener.accept().await.unwrap();
+            (listener, peer)
+        });
+        let (_listener, accepted) = rt.block_or_idle_on(accept).unwrap().unwrap();
+        assert_eq!(accepted, peers[0]);
+
+        let pending = IOContext::with_current(|ctx| ctx.listener_pending(addr));
+        assert_eq!(pending, peers[1..]);
+        let unbound = "127.0.0.1:81".parse().unwrap();
+        assert!(IOContext::with_current(|ctx| ctx.listener_pending(unbound)).is_empty());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn accepted_streams_inherit_listener_config() {
+        let (rt, ctx) = testutil::node("accept_config");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let socket = TcpSocket::new_v4().unwrap();
+            socket.bind("127.0.0.1:80".parse().unwrap()).unwrap();
+            socket.set_recv_buffer_size(64 * 1024).unwrap();
+            socket.set_send_buffer_size(8 * 1024).unwrap();
+            socket.set_linger(Some(Duration::from_secs(1))).unwrap();
+
+            let listener = socket.listen(32).unwrap();
+            listener.set_ttl(32).unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            stream
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(TcpStream::connect("127.0.0.1:80"));
+        for _ in 0..2 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let IOIntent::TcpConnect(msg) = intent {
+                    rt.process_tcp_connect(msg).unwrap();
+                }
+            }
+        }
+
+        let stream = rt.block_or_idle_on(server).unwrap().unwrap();
+        let key = (stream.local_addr().unwrap(), stream.peer_addr().unwrap());
+        let config = IOContext::with_current(|ctx| ctx.tcp_streams[&key].config.clone());
+        assert_eq!(config.recv_buffer_size, 64 * 1024);
+        assert_eq!(config.send_buffer_size, 8 * 1024);
+        assert_eq!(config.linger, Some(Duration::from_secs(1)));
+        assert_eq!(config.ttl, 32);
+        assert_eq!(stream.ttl().unwrap(), 32);
+
+        drop(client);
+        guard.leave();
+    }
+
+    #[test]
+    fn resource_counts_return_to_zero() {
+        let (rt, ctx) = testutil::node("resources");
+        let guard = rt.enter_context(ctx);
+        let counts = || IOContext::with_current(|ctx| ctx.open_resource_count());
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await.unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            (listener, stream)
+        });
+        rt.poll_until_idle();
+        let client = rt.spawn(async {
+            let socket = UdpSocket::bind("127.0.0.1:100").await.unwrap();
+            let stream = TcpStream::connect("127.0.0.1:80").await.unwrap();
+            (socket, stream)
+        });
+        for _ in 0..4 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let IOIntent::TcpConnect(msg) = intent {
+                    rt.process_tcp_connect(msg).unwrap();
+                }
+            }
+        }
+
+        let (listener, server) = rt.block_or_idle_on(server).unwrap().unwrap();
+        let (socket, client) = rt.block_or_idle_on(client).unwrap().unwrap();
+        assert_eq!(
+            counts(),
+            ResourceCounts {
+                udp: 1,
+                tcp_listeners: 1,
+                tcp_streams: 2,
+            }
+        );
+
+        // A split stream is deregistered once both halves were dropped.
+        let (read, write) = client.into_split();
+        drop(read);
+        assert_eq!(counts().tcp_streams, 2);
+        drop(write);
+        assert_eq!(counts().tcp_streams, 1);
+
+        drop((listener, server, socket));
+        assert_eq!(counts(), ResourceCounts::default());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn late_segment_resets_sender() {
+        let (rt_a, mut ctx_a) = testutil::node_at(Ipv4Addr::new(192, 168, 2, 1));
+        let (rt_b, mut ctx_b) = testutil::node_at(Ipv4Addr::new(192, 168, 2, 2));
+
+        let guard = rt_b.enter_context(ctx_b);
+        rt_b.spawn(async {
+            let listener = TcpListener::bind("0.0.0.0:80").await.unwrap();
+            listener.accept().await.unwrap();
+        });
+        rt_b.poll_until_idle();
+        ctx_b = guard.leave();
+
+        let guard = rt_a.enter_context(ctx_a);
+        let client = rt_a.spawn(TcpStream::connect("192.168.2.2:80"));
+        ctx_a = guard.leave();
+
+        for _ in 0..2 {
+            let (ctx, intents) = testutil::poll(&rt_a, ctx_a);
+            ctx_a = ctx;
+            ctx_b = testutil::deliver(&rt_b, ctx_b, intents).0;
+            let (ctx, intents) = testutil::poll(&rt_b, ctx_b);
+            ctx_b = ctx;
+            ctx_a = testutil::deliver(&rt_a, ctx_a, intents).0;
+        }
+
+        // The server node restarts, losing the state of all streams.
+        drop(ctx_b);
+        let ctx_b = SimContext::new([1, 2, 3, 4, 5, 2], Ipv4Addr::new(192, 168, 2, 2))
+            .with_time(String::from("restarted"));
+
+        let guard = rt_a.enter_context(ctx_a);
+        let mut client = rt_a.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        rt_a.block_on(client.write_all(b"late")).unwrap();
+        ctx_a = guard.leave();
+
+        let (ctx, intents) = testutil::poll(&rt_a, ctx_a);
+        let (_, lost) = testutil::deliver(&rt_b, ctx_b, intents);
+        assert_eq!(lost.len(), 1);
+
+        let guard = rt_a.enter_context(ctx);
+        for intent in lost {
+            if let IOIntent::TcpSendPacket(msg, _) = intent {
+                rt_a.synthesize_rst(msg).unwrap();
+            }
+        }
+        let mut buf = [0u8; 8];
+        let err = rt_a.block_on(client.read(&mut buf)).unwrap_err();
+        assert_eq!(
+            SimNetError::from_io(&err),
+            Some(SimNetError::ConnectionReset)
+        );
+
+        drop(client);
+        guard.leave();
+    }
+
+    #[test]
+    fn same_port_on_multiple_interfaces() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let en0 = Interface::en0([1, 2, 3, 4, 5, 6], Ipv4Addr::new(10, 0, 0, 1));
+        let en1 = Interface::en0([1, 2, 3, 4, 5, 7], Ipv4Addr::new(10, 0, 1, 1));
+        let mut ctx = SimContext::empty().with_time(String::from("multihomed"));
+        let io = IOContext::builder().add_interface(en0).add_interface(en1);
+        ctx.io = Some(io.build());
+        let guard = rt.enter_context(ctx);
+
+        let a: SocketAddr = "10.0.0.1:8080".parse().unwrap();
+        let b: SocketAddr = "10.0.1.1:8080".parse().unwrap();
+        let handle = rt.spawn(async move {
+            let sock_a = UdpSocket::bind(a).await.unwrap();
+            let sock_b = UdpSocket::bind(b).await.unwrap();
+            let listener_a = TcpListener::bind(a).await.unwrap();
+            let listener_b = TcpListener::bind(b).await.unwrap();
+
+            // Collisions are per protocol and ip.
+            let err = UdpSocket::bind(a).await.unwrap_err();
+            assert_eq!(SimNetError::from_io(&err), Some(SimNetError::AddrInUse));
+            let err = TcpListener::bind(b).await.unwrap_err();
+            assert_eq!(SimNetError::from_io(&err), Some(SimNetError::AddrInUse));
+
+            // Ephemeral ports skip the ports in use on their ip.
+            let ephemeral = IOContext::with_current(|ctx| {
+                ctx.next_ephemeral_port = 8080;
+                ctx.bind_addr("10.0.1.1:0".parse().unwrap(), BindProtocol::Udp)
+            });
+            assert_eq!(ephemeral.unwrap(), "10.0.1.1:8081".parse().unwrap());
+
+            let mut buf = [0u8; 8];
+            let (n, _) = sock_a.recv_from(&mut buf).await.unwrap();
+            let first = buf[..n].to_vec();
+            let (n, _) = sock_b.recv_from(&mut buf).await.unwrap();
+            let second = buf[..n].to_vec();
+            drop((listener_a, listener_b));
+            (first, second)
+        });
+        rt.poll_until_idle();
+
+        let from: SocketAddr = "10.0.0.2:100".parse().unwrap();
+        rt.process_udp(UdpMessage::new(from, b, b"to b".to_vec()))
+            .unwrap();
+        rt.process_udp(UdpMessage::new(from, a, b"to a".to_vec()))
+            .unwrap();
+
+        let (first, second) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(first, b"to a");
+        assert_eq!(second, b"to b");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn ephemeral_ports_wrap_and_exhaust() {
+        let (rt, ctx) = testutil::node("ephemeral");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            IOContext::with_current(|ctx| ctx.next_ephemeral_port = u16::MAX);
+            let last = UdpSocket::bind("192.168.2.1:0").await.unwrap();
+            let first = UdpSocket::bind("192.168.2.1:0").await.unwrap();
+            assert_eq!(last.local_addr().unwrap().port(), u16::MAX);
+            assert_eq!(first.local_addr().unwrap().port(), EPHEMERAL_PORT_MIN);
+
+            let mut sockets = vec![last, first];
+            loop {
+                match UdpSocket::bind("192.168.2.1:0").await {
+                    Ok(socket) => sockets.push(socket),
+                    Err(e) => {
+                        assert_eq!(SimNetError::from_io(&e), Some(SimNetError::AddrInUse));
+                        break sockets.len();
+                    }
+                }
+            }
+        });
+
+        let bound = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(bound, usize::from(u16::MAX - EPHEMERAL_PORT_MIN) + 1);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn display_summarizes_node() {
+        let (rt, ctx) = testutil::node("display");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.send_to(b"ping", "192.168.2.2:100").await.unwrap();
+            let listener = TcpListener::bind("127.0.0.1:80").await.unwrap();
+            (socket, listener)
+        });
+        let resources = rt.block_or_idle_on(handle).unwrap().unwrap();
+
+        let summary = IOContext::with_current(|ctx| ctx.to_string());
+        assert!(summary.contains("en0 = active"));
+        assert!(summary.contains("inet 192.168.2.1"));
+        assert!(summary.contains("192.168.2.1:100 = bound"));
+        assert!(summary.contains("127.0.0.1:80 = listening (0 pending)"));
+        assert!(summary.contains("pending = 1"));
+
+        drop(resources);
+        guard.leave();
+    }
 }
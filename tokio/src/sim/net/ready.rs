@@ -0,0 +1,193 @@
+//! Waiting on the readiness of multiple sockets.
+
+use super::{IOInterest, TcpListener, TcpStream, UdpSocket};
+use std::future::Future;
+use std::io::Result;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// A socket that can be awaited by [`ready_any`].
+///
+/// This trait is sealed and implemented for [`UdpSocket`], [`TcpListener`]
+/// and [`TcpStream`].
+pub trait Readable: sealed::ReadablePriv + Sync {}
+
+impl Readable for UdpSocket {}
+
+impl sealed::ReadablePriv for UdpSocket {
+    fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut IOInterest::UdpRead(self.addr)).poll(cx)
+    }
+
+    fn cancel_read_ready(&self, waker: &Waker) {
+        IOInterest::UdpRead(self.addr).cancel(waker);
+    }
+}
+
+impl Readable for TcpListener {}
+
+impl sealed::ReadablePriv for TcpListener {
+    fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut IOInterest::TcpAccept(self.addr)).poll(cx)
+    }
+
+    fn cancel_read_ready(&self, waker: &Waker) {
+        IOInterest::TcpAccept(self.addr).cancel(waker);
+    }
+}
+
+impl Readable for TcpStream {}
+
+impl sealed::ReadablePriv for TcpStream {
+    fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut IOInterest::TcpRead(self.inner.key())).poll(cx)
+    }
+
+    fn cancel_read_ready(&self, waker: &Waker) {
+        IOInterest::TcpRead(self.inner.key()).cancel(waker);
+    }
+}
+
+/// Waits until any of the given sockets becomes readable, returning its index.
+///
+/// In contrast to a [`select!`](crate::select) over multiple `readable` calls,
+/// each socket holds at most one interest of the waiting task. Once the future
+/// completes or is dropped, all remaining interests are removed.
+///
+/// A socket counts as readable if data can be received, a connection can be
+/// accepted, or the next operation will return an error. If `sockets` is
+/// empty, the future never completes.
+///
+/// ```no_run
+/// use tokio::net::{ready_any, UdpSocket};
+///
+/// # async fn dox() -> std::io::Result<()> {
+/// let a = UdpSocket::bind("0.0.0.0:1000").await?;
+/// let b = UdpSocket::bind("0.0.0.0:2000").await?;
+///
+/// match ready_any(&[&a, &b]).await {
+///     0 => { /* a is readable */ }
+///     _ => { /* b is readable */ }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn ready_any<'a>(sockets: &'a [&'a dyn Readable]) -> impl Future<Output = usize> + 'a {
+    ReadyAny {
+        sockets,
+        waker: None,
+    }
+}
+
+struct ReadyAny<'a> {
+    sockets: &'a [&'a dyn Readable],
+    waker: Option<Waker>,
+}
+
+impl ReadyAny<'_> {
+    fn cancel(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            for socket in self.sockets {
+                socket.cancel_read_ready(&waker);
+            }
+        }
+    }
+}
+
+impl Future for ReadyAny<'_> {
+    type Output = usize;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+        // A new waker replaces the registrations of the previous one.
+        if let Some(waker) = &self.waker {
+            if !waker.will_wake(cx.waker()) {
+                self.cancel();
+            }
+        }
+
+        let ready = self
+            .sockets
+            .iter()
+            .position(|socket| socket.poll_read_ready(cx).is_ready());
+        self.waker = Some(cx.waker().clone());
+
+        match ready {
+            Some(idx) => {
+                self.cancel();
+                Poll::Ready(idx)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for ReadyAny<'_> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+pub(crate) mod sealed {
+    //! The contents of this trait are intended to remain private and __not__
+    //! part of the `Readable` public API.
+
+    use std::io::Result;
+    use std::task::{Context, Poll, Waker};
+
+    #[doc(hidden)]
+    pub trait ReadablePriv {
+        fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<Result<()>>;
+
+        fn cancel_read_ready(&self, waker: &Waker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::net::{testutil, IOContext, IOIntent};
+
+    #[test]
+    fn second_socket_ready_first() {
+        let (rt, ctx) = testutil::node("second_socket_ready_first");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let a = UdpSocket::bind("192.168.2.1:1000").await.unwrap();
+            let b = UdpSocket::bind("192.168.2.1:2000").await.unwrap();
+            let idx = ready_any(&[&a, &b]).await;
+            let remaining = IOContext::with_current(|ctx| ctx.udp_sockets[&a.addr].interests.len());
+            assert_eq!(remaining, 0);
+
+            let mut buf = [0u8; 8];
+            let (n, _) = b.try_recv_from(&mut buf).unwrap();
+            (idx, buf[..n].to_vec())
+        });
+        rt.poll_until_idle();
+
+        // Each socket holds a single interest while waiting.
+        for addr in &["192.168.2.1:1000", "192.168.2.1:2000"] {
+            let interests = IOContext::with_current(|ctx| {
+                ctx.udp_sockets[&addr.parse().unwrap()].interests.len()
+            });
+            assert_eq!(interests, 1);
+        }
+
+        rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.send_to(b"ping", "192.168.2.1:2000").await.unwrap();
+        });
+        rt.poll_until_idle();
+        for intent in rt.yield_intents() {
+            if let IOIntent::UdpSendPacket(msg) = intent {
+                rt.process_udp(msg).unwrap();
+            }
+        }
+
+        let (idx, content) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(idx, 1);
+        assert_eq!(content, b"ping");
+
+        guard.leave();
+    }
+}
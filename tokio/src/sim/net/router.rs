@@ -0,0 +1,647 @@
+//! Fault injection between simulated nodes.
+
+use super::{IOIntent, TcpConnectMessage};
+use crate::util::FastRand;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// A filter for intents that are exchanged between simulated nodes.
+///
+/// The simulation core forwards all [`IOIntent`]s through the router
+/// before delivering them to the receiving node. Intents whose source and
+/// destination are separated by a partition are dropped, all other intents
+/// are passed through. Each routed UDP datagram consumes one hop of its
+/// time-to-live, datagrams without any hops left are dropped.
+///
+/// Optionally, a fraction of the packets can be duplicated, using
+/// [`route_all`](Router::route_all) to deliver all copies.
+///
+/// UDP datagrams can be reordered within a bounded window per path, see
+/// [`set_udp_reorder`](Router::set_udp_reorder).
+///
+/// Links can be assigned a latency for each direction. The router does not
+/// delay intents itself, but [`route_delayed`](Router::route_delayed) returns
+/// the latency the simulation core should wait before delivering an intent.
+///
+/// ```no_run
+/// use tokio::net::Router;
+/// # use std::net::IpAddr;
+///
+/// # fn dox(rt: &tokio::runtime::Runtime, node_a: IpAddr, node_b: IpAddr) {
+/// let mut router = Router::new();
+/// router.partition(&[node_a], &[node_b]);
+///
+/// for intent in rt.yield_intents() {
+///     if let Some(intent) = router.route(intent) {
+///         // deliver to the receiving node
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+    partitions: Vec<(Vec<IpAddr>, Vec<IpAddr>)>,
+    dropped: usize,
+
+    duplication: Option<(f64, FastRand)>,
+    duplicated: usize,
+
+    latencies: HashMap<(IpAddr, IpAddr), Duration>,
+
+    reorder: Option<(usize, FastRand)>,
+    held: BTreeMap<(IpAddr, IpAddr), HeldWindow>,
+}
+
+/// The UDP datagrams of a path that are held back for reordering.
+#[derive(Debug, Clone, Default)]
+struct HeldWindow {
+    intents: Vec<IOIntent>,
+    /// The number of calls to `route_all` without a newer datagram.
+    idle: usize,
+}
+
+impl Router {
+    /// Creates a new router without any partitions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cuts all traffic between the addresses of `group_a` and `group_b`.
+    ///
+    /// Traffic within either group remains unaffected. Multiple partitions
+    /// may be active at the same time.
+    pub fn partition(&mut self, group_a: &[IpAddr], group_b: &[IpAddr]) {
+        self.partitions.push((Vec::from(group_a), Vec::from(group_b)));
+    }
+
+    /// Removes all partitions.
+    pub fn heal(&mut self) {
+        self.partitions.clear();
+    }
+
+    /// Returns whether traffic from `src` can reach `dest`.
+    pub fn is_reachable(&self, src: IpAddr, dest: IpAddr) -> bool {
+        !self.partitions.iter().any(|(a, b)| {
+            (a.contains(&src) && b.contains(&dest)) || (b.contains(&src) && a.contains(&dest))
+        })
+    }
+
+    /// Returns the number of intents dropped by a partition or an exhausted time-to-live.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Duplicates the given fraction of all UDP and TCP packets.
+    ///
+    /// Duplicates are drawn from a generator initialized with `seed`,
+    /// so the same packets are duplicated across runs. A fraction of
+    /// zero disables duplication.
+    pub fn duplicate(&mut self, fraction: f64, seed: u64) {
+        self.duplication = if fraction > 0.0 {
+            Some((fraction, FastRand::new(seed)))
+        } else {
+            None
+        };
+    }
+
+    /// Returns the number of packets that were duplicated.
+    pub fn duplicated(&self) -> usize {
+        self.duplicated
+    }
+
+    /// Reorders UDP datagrams within windows of `depth` datagrams.
+    ///
+    /// [`route_all`](Router::route_all) holds back the datagrams of each path,
+    /// until `depth` datagrams were collected. The window is then released in
+    /// an order drawn from a generator initialized with `seed`, so the same
+    /// datagrams are reordered across runs. A datagram is never reordered
+    /// beyond its window. A depth of zero or one disables reordering.
+    ///
+    /// An incomplete window is released as is, once `depth` further calls
+    /// to `route_all` brought no newer datagram on its path. All remaining
+    /// windows can be released with [`release_held`](Router::release_held).
+    ///
+    /// Datagrams held back under the previous configuration are released
+    /// and returned, so disabling reordering flushes all windows.
+    #[must_use = "datagrams held back for reordering are returned and must be delivered"]
+    pub fn set_udp_reorder(&mut self, depth: usize, seed: u64) -> Vec<IOIntent> {
+        let released = self.release_held();
+        self.reorder = if depth > 1 {
+            Some((depth, FastRand::new(seed)))
+        } else {
+            None
+        };
+        released
+    }
+
+    /// Releases all UDP datagrams held back for reordering.
+    ///
+    /// The datagrams of each path are shuffled, like a complete window.
+    pub fn release_held(&mut self) -> Vec<IOIntent> {
+        let held = std::mem::take(&mut self.held);
+        let mut released = Vec::new();
+        for (_, mut window) in held {
+            if let Some((_, rng)) = &self.reorder {
+                shuffle(&mut window.intents, rng);
+            }
+            released.extend(window.intents);
+        }
+        released
+    }
+
+    /// Sets the latency of the link between `a` and `b`.
+    ///
+    /// Traffic from `a` to `b` is delayed by `to_peer`, while traffic from
+    /// `b` to `a` is delayed by `from_peer`. Links without a configured
+    /// latency deliver immediately.
+    pub fn set_latency(&mut self, a: IpAddr, b: IpAddr, to_peer: Duration, from_peer: Duration) {
+        self.latencies.insert((a, b), to_peer);
+        self.latencies.insert((b, a), from_peer);
+    }
+
+    /// Returns the latency of traffic from `src` to `dest`.
+    pub fn latency(&self, src: IpAddr, dest: IpAddr) -> Duration {
+        self.latencies
+            .get(&(src, dest))
+            .copied()
+            .unwrap_or(Duration::from_secs(0))
+    }
+
+    /// Routes an intent, returning `None` if it was dropped.
+    ///
+    /// Intents that are not exchanged between nodes, like timeouts or
+    /// io ticks, are always passed through. UDP datagrams with a time-to-live
+    /// of zero are dropped, all others are forwarded with a decremented one.
+    pub fn route(&mut self, mut intent: IOIntent) -> Option<IOIntent> {
+        if let IOIntent::UdpSendPacket(ref mut msg) = intent {
+            if msg.ttl == 0 {
+                self.dropped += 1;
+                return None;
+            }
+            msg.ttl -= 1;
+        }
+
+        let (src, dest) = match endpoints(&intent) {
+            Some(endpoints) => endpoints,
+            None => return Some(intent),
+        };
+
+        if self.is_reachable(src.ip(), dest.ip()) {
+            Some(intent)
+        } else {
+            self.dropped += 1;
+            None
+        }
+    }
+
+    /// Routes an intent, returning it together with the latency of its link.
+    ///
+    /// Intents that are not exchanged between nodes have no latency.
+    pub fn route_delayed(&mut self, intent: IOIntent) -> Option<(IOIntent, Duration)> {
+        let intent = self.route(intent)?;
+        let latency = match endpoints(&intent) {
+            Some((src, dest)) => self.latency(src.ip(), dest.ip()),
+            None => Duration::from_secs(0),
+        };
+        Some((intent, latency))
+    }
+
+    /// Routes an intent, returning all copies that should be delivered.
+    ///
+    /// In contrast to [`route`](Router::route), packets may be duplicated.
+    /// Duplicated TCP segments are discarded by the receiving stream,
+    /// while duplicated UDP datagrams are delivered twice. If reordering
+    /// is enabled, UDP datagrams may be held back and released later.
+    pub fn route_all(&mut self, intent: IOIntent) -> Vec<IOIntent> {
+        let intents = self.route_copies(intent);
+        if self.reorder.is_none() {
+            return intents;
+        }
+
+        let mut released = intents
+            .into_iter()
+            .flat_map(|intent| self.reorder_udp(intent))
+            .collect::<Vec<_>>();
+        released.extend(self.release_idle());
+        released
+    }
+
+    /// Routes an intent, duplicating it if configured.
+    fn route_copies(&mut self, intent: IOIntent) -> Vec<IOIntent> {
+        let intent = match self.route(intent) {
+            Some(intent) => intent,
+            None => return Vec::new(),
+        };
+
+        let copy = match &intent {
+            IOIntent::UdpSendPacket(msg) => IOIntent::UdpSendPacket(msg.clone()),
+            IOIntent::TcpSendPacket(msg, delay) => IOIntent::TcpSendPacket(msg.clone(), *delay),
+            _ => return vec![intent],
+        };
+
+        let duplicate = match &self.duplication {
+            Some((fraction, rng)) => {
+                let sample = f64::from(rng.fastrand_n(u32::MAX)) / f64::from(u32::MAX);
+                sample < *fraction
+            }
+            None => false,
+        };
+
+        if duplicate {
+            self.duplicated += 1;
+            vec![intent, copy]
+        } else {
+            vec![intent]
+        }
+    }
+
+    /// Holds back a UDP datagram, returning its window once complete.
+    fn reorder_udp(&mut self, intent: IOIntent) -> Vec<IOIntent> {
+        let (depth, rng) = match &self.reorder {
+            Some((depth, rng)) => (*depth, rng),
+            None => return vec![intent],
+        };
+        let path = match &intent {
+            IOIntent::UdpSendPacket(msg) => (msg.src_addr.ip(), msg.dest_addr.ip()),
+            _ => return vec![intent],
+        };
+
+        let window = self.held.entry(path).or_default();
+        window.intents.push(intent);
+        window.idle = 0;
+        if window.intents.len() < depth {
+            return Vec::new();
+        }
+
+        let mut window = self.held.remove(&path).unwrap_or_default();
+        shuffle(&mut window.intents, rng);
+        window.intents
+    }
+
+    /// Releases all incomplete windows that received no newer datagram
+    /// during the last `depth` calls to `route_all`.
+    fn release_idle(&mut self) -> Vec<IOIntent> {
+        let (depth, rng) = match &self.reorder {
+            Some((depth, rng)) => (*depth, rng),
+            None => return Vec::new(),
+        };
+
+        let mut idle = Vec::new();
+        for (path, window) in self.held.iter_mut() {
+            window.idle += 1;
+            if window.idle > depth {
+                idle.push(*path);
+            }
+        }
+
+        let mut released = Vec::new();
+        for path in idle {
+            if let Some(mut window) = self.held.remove(&path) {
+                shuffle(&mut window.intents, rng);
+                released.extend(window.intents);
+            }
+        }
+        released
+    }
+}
+
+/// Shuffles `intents` in place, using the Fisher-Yates algorithm.
+fn shuffle(intents: &mut [IOIntent], rng: &FastRand) {
+    for i in (1..intents.len()).rev() {
+        let j = rng.fastrand_n(i as u32 + 1) as usize;
+        intents.swap(i, j);
+    }
+}
+
+/// Returns the source and destination of intents exchanged between nodes.
+fn endpoints(intent: &IOIntent) -> Option<(SocketAddr, SocketAddr)> {
+    match intent {
+        IOIntent::UdpSendPacket(msg) => Some((msg.src_addr, msg.dest_addr)),
+        IOIntent::TcpSendPacket(msg, _) => Some((msg.src_addr, msg.dest_addr)),
+        IOIntent::TcpConnect(TcpConnectMessage::ClientInitiate { client, server }) => {
+            Some((*client, *server))
+        }
+        IOIntent::TcpConnect(TcpConnectMessage::ServerAcknowledge { client, server }) => {
+            Some((*server, *client))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::sim::net::{testutil, TcpListener, TcpStream, UdpMessage, UdpSocket};
+    use crate::time::SimTime;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn partition_and_heal() {
+        let a = Ipv4Addr::new(192, 168, 2, 1);
+        let b = Ipv4Addr::new(192, 168, 2, 2);
+        let (rt_a, ctx_a) = testutil::node_at(a);
+        let (rt_b, ctx_b) = testutil::node_at(b);
+
+        let mut router = Router::new();
+        router.partition(&[a.into()], &[b.into()]);
+
+        let server: SocketAddr = (b, 7).into();
+        let mut recv = rt_b.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.2:7").await.unwrap();
+            let mut buf = [0u8; 8];
+            let (n, from) = socket.recv_from(&mut buf).await.unwrap();
+            (buf[..n].to_vec(), from)
+        });
+        let (mut ctx_b, _) = testutil::poll(&rt_b, ctx_b);
+
+        let send = move || async move {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.send_to(b"ping", server).await.unwrap();
+        };
+
+        // Partitioned
+        rt_a.spawn(send());
+        let (ctx_a, intents) = testutil::poll(&rt_a, ctx_a);
+        let intents = intents
+            .into_iter()
+            .filter_map(|i| router.route(i))
+            .collect();
+        ctx_b = testutil::deliver(&rt_b, ctx_b, intents).0;
+        assert_eq!(router.dropped(), 1);
+
+        let guard = rt_b.enter_context(ctx_b);
+        assert!(rt_b.block_or_idle_on(&mut recv).is_err());
+        ctx_b = guard.leave();
+
+        // Healed
+        router.heal();
+        rt_a.spawn(send());
+        let (_, intents) = testutil::poll(&rt_a, ctx_a);
+        let intents = intents
+            .into_iter()
+            .filter_map(|i| router.route(i))
+            .collect();
+        ctx_b = testutil::deliver(&rt_b, ctx_b, intents).0;
+        assert_eq!(router.dropped(), 1);
+
+        let guard = rt_b.enter_context(ctx_b);
+        let (content, from) = rt_b.block_or_idle_on(recv).unwrap().unwrap();
+        assert_eq!(content, b"ping");
+        assert_eq!(from, SocketAddr::from((a, 100)));
+        guard.leave();
+    }
+
+    #[test]
+    fn duplicate_udp_datagrams() {
+        let (rt, ctx) = testutil::node_at(Ipv4Addr::new(192, 168, 2, 1));
+        let guard = rt.enter_context(ctx);
+        let mut router = Router::new();
+        router.duplicate(1.0, 42);
+
+        let recv = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:7").await.unwrap();
+            let mut buf = [0u8; 8];
+            let mut received = Vec::new();
+            for _ in 0..2 {
+                let (n, _) = socket.recv_from(&mut buf).await.unwrap();
+                received.push(buf[..n].to_vec());
+            }
+            received
+        });
+        rt.poll_until_idle();
+
+        rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.send_to(b"ping", "192.168.2.1:7").await.unwrap();
+        });
+        rt.poll_until_idle();
+        for intent in rt.yield_intents() {
+            for intent in router.route_all(intent) {
+                if let IOIntent::UdpSendPacket(msg) = intent {
+                    rt.process_udp(msg).unwrap();
+                }
+            }
+        }
+
+        let received = rt.block_or_idle_on(recv).unwrap().unwrap();
+        assert_eq!(received, vec![b"ping".to_vec(), b"ping".to_vec()]);
+        assert_eq!(router.duplicated(), 1);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn duplicate_tcp_segments() {
+        let (rt, ctx) = testutil::node_at(Ipv4Addr::new(192, 168, 2, 1));
+        let guard = rt.enter_context(ctx);
+        let mut router = Router::new();
+        router.duplicate(1.0, 42);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await.unwrap();
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await.unwrap();
+            buf
+        });
+        rt.poll_until_idle();
+
+        rt.spawn(async {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+        });
+        for _ in 0..4 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                for intent in router.route_all(intent) {
+                    match intent {
+                        IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                        IOIntent::TcpSendPacket(msg, _) => rt.process_tcp_packet(msg).unwrap(),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        assert_eq!(rt.block_or_idle_on(server).unwrap().unwrap(), b"hello");
+        assert!(router.duplicated() >= 2);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn multicast_ttl_limits_hops() {
+        let (rt, ctx) = testutil::node_at(Ipv4Addr::new(192, 168, 2, 1));
+        let guard = rt.enter_context(ctx);
+        let mut router = Router::new();
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            assert_eq!(socket.multicast_ttl_v4().unwrap(), 64);
+
+            socket.set_multicast_ttl_v4(0).unwrap();
+            socket.send_to(b"node", "224.0.0.1:7").await.unwrap();
+            socket.set_multicast_ttl_v4(1).unwrap();
+            socket.send_to(b"link", "224.0.0.1:7").await.unwrap();
+            socket.send_to(b"unicast", "192.168.2.2:7").await.unwrap();
+        });
+        rt.block_or_idle_on(handle).unwrap().unwrap();
+
+        let sent = rt
+            .yield_intents()
+            .into_iter()
+            .filter_map(|intent| match intent {
+                IOIntent::UdpSendPacket(msg) => Some(msg),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sent.iter().map(|msg| msg.ttl).collect::<Vec<_>>(),
+            vec![1, 64]
+        );
+
+        // The first router consumes the only hop, the second drops the datagram.
+        let mut next_router = Router::new();
+        let routed = sent
+            .into_iter()
+            .filter_map(|msg| router.route(IOIntent::UdpSendPacket(msg)))
+            .filter_map(|intent| next_router.route(intent))
+            .collect::<Vec<_>>();
+        assert_eq!(routed.len(), 1);
+        assert_eq!((router.dropped(), next_router.dropped()), (0, 1));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn asymmetric_latency() {
+        let (rt, ctx) = testutil::node_at(Ipv4Addr::new(192, 168, 2, 1));
+        let guard = rt.enter_context(ctx);
+        let client = Ipv4Addr::new(192, 168, 2, 1);
+        let server = Ipv4Addr::LOCALHOST;
+
+        let mut router = Router::new();
+        router.set_latency(
+            client.into(),
+            server.into(),
+            Duration::from_millis(30),
+            Duration::from_millis(10),
+        );
+        assert_eq!(
+            router.latency(server.into(), client.into()),
+            Duration::from_millis(10)
+        );
+
+        let received = rt.spawn(async {
+            let socket = UdpSocket::bind("127.0.0.1:7").await.unwrap();
+            let mut buf = [0u8; 8];
+            let (n, from) = socket.recv_from(&mut buf).await.unwrap();
+            socket.send_to(&buf[..n], from).await.unwrap();
+            SimTime::now()
+        });
+        let exchange = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            let sent = SimTime::now();
+            socket.send_to(b"ping", "127.0.0.1:7").await.unwrap();
+            let mut buf = [0u8; 8];
+            socket.recv_from(&mut buf).await.unwrap();
+            (sent, SimTime::now())
+        });
+
+        // Delivers each packet once its latency elapsed.
+        for _ in 0..2 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let Some((intent, latency)) = router.route_delayed(intent) {
+                    if let IOIntent::UdpSendPacket(msg) = intent {
+                        rt.advance(latency);
+                        rt.process_udp(msg).unwrap();
+                    }
+                }
+            }
+        }
+
+        let (sent, replied) = rt.block_or_idle_on(exchange).unwrap().unwrap();
+        let received = rt.block_or_idle_on(received).unwrap().unwrap();
+        assert_eq!(received - sent, Duration::from_millis(30));
+        assert_eq!(replied - received, Duration::from_millis(10));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn udp_reorder_within_depth() {
+        let src: SocketAddr = "192.168.2.1:100".parse().unwrap();
+        let dest: SocketAddr = "192.168.2.2:7".parse().unwrap();
+        let mut router = Router::new();
+        assert!(router.set_udp_reorder(4, 42).is_empty());
+
+        let mut released = Vec::new();
+        for i in 0..10u8 {
+            let msg = UdpMessage::new(src, dest, vec![i]);
+            released.extend(router.route_all(IOIntent::UdpSendPacket(msg)));
+            // Datagrams are only released in complete windows.
+            assert_eq!(released.len(), usize::from(i + 1) / 4 * 4);
+        }
+        released.extend(router.release_held());
+
+        let order = released
+            .into_iter()
+            .map(|intent| match intent {
+                IOIntent::UdpSendPacket(msg) => msg.content[0],
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_ne!(order, (0..10).collect::<Vec<_>>());
+
+        // Each datagram stays within its window.
+        for (pos, seq) in order.iter().enumerate() {
+            assert_eq!(pos / 4, usize::from(*seq) / 4);
+        }
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn udp_reorder_releases_idle_window() {
+        let src: SocketAddr = "192.168.2.1:100".parse().unwrap();
+        let dest: SocketAddr = "192.168.2.2:7".parse().unwrap();
+        let others: [SocketAddr; 2] = [
+            "192.168.2.3:7".parse().unwrap(),
+            "192.168.2.4:7".parse().unwrap(),
+        ];
+        let mut router = Router::new();
+        assert!(router.set_udp_reorder(4, 42).is_empty());
+
+        // An incomplete window is held while its path is busy ...
+        for i in 0..2u8 {
+            let msg = UdpMessage::new(src, dest, vec![i]);
+            assert!(router.route_all(IOIntent::UdpSendPacket(msg)).is_empty());
+        }
+
+        // ... and released once `depth` calls brought no newer datagram.
+        let mut released = Vec::new();
+        for i in 0..4u8 {
+            assert!(released.is_empty());
+            let msg = UdpMessage::new(src, others[usize::from(i) % 2], vec![i]);
+            released = router.route_all(IOIntent::UdpSendPacket(msg));
+        }
+        let mut released = released
+            .into_iter()
+            .map(|intent| match intent {
+                IOIntent::UdpSendPacket(msg) => (msg.dest_addr, msg.content[0]),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        released.sort_unstable();
+        assert_eq!(released, [(dest, 0), (dest, 1)]);
+
+        // Disabling reordering flushes the remaining window.
+        assert_eq!(router.set_udp_reorder(0, 0).len(), 4);
+        let msg = UdpMessage::new(src, dest, vec![0]);
+        assert_eq!(router.route_all(IOIntent::UdpSendPacket(msg)).len(), 1);
+    }
+}
@@ -1,5 +1,6 @@
-use super::super::{addr::*, Result, IOContext, IOInterest};
+use super::super::{addr::*, Result, IOContext, IOInterest, SimNetError};
 use super::stream::TcpStream;
+use crate::time::sleep_until;
 use std::net::SocketAddr;
 use std::io::{Error, ErrorKind};
 use std::task::*;
@@ -39,7 +40,7 @@ impl TcpListener {
         let addrs = to_socket_addrs(addr).await?;
 
         // Get the current context
-        IOContext::with_current(|ctx| {
+        IOContext::with_current_or_err(|ctx| {
             let mut last_err = None;
 
             for addr in addrs {
@@ -59,11 +60,22 @@ impl TcpListener {
     /// 
     /// This function will yield once a new TCP connection is established. 
     /// When established, the corresponding `TcpStream` and the remote peer’s address will be returned
+    /// 
+    /// If an accept rate was set, see [set_accept_rate](TcpListener::set_accept_rate),
+    /// this function waits for the next window once the rate was exceeded.
     pub async fn accept(&self) -> Result<(TcpStream, SocketAddr)> {
         loop {
             let interest = IOInterest::TcpAccept(self.addr);
             interest.await?;
 
+            let throttled = IOContext::with_current(|ctx| {
+                ctx.tcp_listeners.get(&self.addr).and_then(|handle| handle.accept_throttled())
+            });
+            if let Some(end) = throttled {
+                sleep_until(end).await;
+                continue
+            }
+
             let con = IOContext::with_current(|ctx| {
                 ctx.tcp_accept(self.addr)
             });
@@ -122,7 +134,7 @@ impl TcpListener {
             if let Some(handle) = ctx.tcp_listeners.get(&self.addr) {
                 Ok(handle.config.ttl)
             } else {
-                Err(Error::new(ErrorKind::Other, "Lost Tcp"))
+                Err(SimNetError::ListenerDropped.into())
             }
         })
     }
@@ -136,7 +148,40 @@ impl TcpListener {
                 handle.config.ttl = ttl;
                 Ok(())
             } else {
-                Err(Error::new(ErrorKind::Other, "Lost Tcp"))
+                Err(SimNetError::ListenerDropped.into())
+            }
+        })
+    }
+
+    /// Limits the number of connections accepted per second of simulation time.
+    /// 
+    /// Once `per_sec` connections were accepted within the current window, further
+    /// connections remain pending until the window ends. This models an overloaded
+    /// accept loop. `None` removes the limit.
+    pub fn set_accept_rate(&self, per_sec: Option<u32>) -> Result<()> {
+        if per_sec == Some(0) {
+            return Err(Error::new(ErrorKind::InvalidInput, "accept rate must be non-zero"));
+        }
+
+        IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_listeners.get_mut(&self.addr) {
+                handle.accept_rate = per_sec;
+                Ok(())
+            } else {
+                Err(SimNetError::ListenerDropped.into())
+            }
+        })
+    }
+
+    /// Returns the number of connections accepted per second, if limited.
+    /// 
+    /// For more information about this option, see [set_accept_rate](TcpListener::set_accept_rate).
+    pub fn accept_rate(&self) -> Result<Option<u32>> {
+        IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_listeners.get(&self.addr) {
+                Ok(handle.accept_rate)
+            } else {
+                Err(SimNetError::ListenerDropped.into())
             }
         })
     }
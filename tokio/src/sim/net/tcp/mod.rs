@@ -1,3 +1,4 @@
+use super::IOContext;
 use std::net::SocketAddr;
 use std::time::Duration;
 
@@ -13,6 +14,20 @@ pub(crate) struct TcpStreamInner {
     pub(crate) peer_addr: SocketAddr,
 }
 
+impl TcpStreamInner {
+    pub(crate) fn key(&self) -> (SocketAddr, SocketAddr) {
+        (self.local_addr, self.peer_addr)
+    }
+}
+
+// The inner state is shared by the halves of a split stream,
+// so the stream is deregistered once all halves were dropped.
+impl Drop for TcpStreamInner {
+    fn drop(&mut self) {
+        IOContext::try_with_current(|ctx| ctx.tcp_drop_stream(self.key()));
+    }
+}
+
 #[derive(Debug, Clone)]
 #[allow(unused)]
 pub(super) struct TcpSocketConfig {
@@ -22,10 +37,17 @@ pub(super) struct TcpSocketConfig {
     pub(super) listen_backlog: u32,
     pub(super) recv_buffer_size: u32,
     pub(super) send_buffer_size: u32,
+    pub(super) mss: u32,
+    pub(super) initial_cwnd: Option<u32>,
+    pub(super) keepalive: Option<Duration>,
+    pub(super) keepalive_probes: u32,
     pub(super) reuseaddr: bool,
     pub(super) reuseport: bool,
 
     pub(super) connect_timeout: Duration,
+    pub(super) read_timeout: Option<Duration>,
+    pub(super) write_timeout: Option<Duration>,
+    pub(super) write_stall_timeout: Option<Duration>,
     pub(super) nodelay: bool,
 
     pub(super) ttl: u32,
@@ -40,10 +62,17 @@ impl TcpSocketConfig {
             listen_backlog: 32,
             recv_buffer_size: 2048,
             send_buffer_size: 2048,
+            mss: 1024,
+            initial_cwnd: None,
+            keepalive: None,
+            keepalive_probes: 3,
             reuseaddr: true,
             reuseport: true,
 
             connect_timeout: Duration::from_secs(2),
+            read_timeout: None,
+            write_timeout: None,
+            write_stall_timeout: None,
             nodelay: true,
 
             ttl: 64,
@@ -58,10 +87,17 @@ impl TcpSocketConfig {
             listen_backlog: 32,
             recv_buffer_size: 2048,
             send_buffer_size: 2048,
+            mss: 1024,
+            initial_cwnd: None,
+            keepalive: None,
+            keepalive_probes: 3,
             reuseaddr: true,
             reuseport: true,
 
             connect_timeout: Duration::from_secs(2),
+            read_timeout: None,
+            write_timeout: None,
+            write_stall_timeout: None,
             nodelay: true,
 
             ttl: 64,
@@ -76,10 +112,17 @@ impl TcpSocketConfig {
             listen_backlog: 1,
             recv_buffer_size: 2048,
             send_buffer_size: 2048,
+            mss: 1024,
+            initial_cwnd: None,
+            keepalive: None,
+            keepalive_probes: 3,
             reuseaddr: false,
             reuseport: false,
 
             connect_timeout: Duration::from_secs(2),
+            read_timeout: None,
+            write_timeout: None,
+            write_stall_timeout: None,
             nodelay: true,
 
             ttl: 64,
@@ -89,18 +132,25 @@ impl TcpSocketConfig {
     pub(super) fn accept(&self, con: super::TcpListenerPendingConnection) -> TcpSocketConfig {
         TcpSocketConfig {
             addr: con.local_addr,
-            linger: None,
+            linger: self.linger,
 
             listen_backlog: 0,
-            recv_buffer_size: 2048,
-            send_buffer_size: 2048,
+            recv_buffer_size: self.recv_buffer_size,
+            send_buffer_size: self.send_buffer_size,
+            mss: self.mss,
+            initial_cwnd: self.initial_cwnd,
+            keepalive: self.keepalive,
+            keepalive_probes: self.keepalive_probes,
             reuseaddr: false,
             reuseport: false,
 
             connect_timeout: Duration::from_secs(2),
-            nodelay: true,
+            read_timeout: None,
+            write_timeout: None,
+            write_stall_timeout: self.write_stall_timeout,
+            nodelay: self.nodelay,
 
-            ttl: 64,
+            ttl: self.ttl,
         }
     }
 }
@@ -1,4 +1,4 @@
-use super::super::{IOContext, IOInterest, Result, TcpStream, TcpListener};
+use super::super::{IOContext, IOInterest, Result, SimNetError, TcpStream, TcpListener};
 use super::TcpSocketConfig;
 
 use std::cell::RefCell;
@@ -101,6 +101,99 @@ impl TcpSocket {
         Ok(self.config.borrow().recv_buffer_size)
     }
 
+    /// Sets the maximum segment size of streams created from this socket.
+    ///
+    /// Data written to a stream is split into packets of at most `mss` bytes.
+    /// On most operating systems, this sets the TCP_MAXSEG socket option.
+    pub fn set_mss(&self, mss: u32) -> Result<()> {
+        if mss == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "mss must be non-zero"));
+        }
+
+        self.config.borrow_mut().mss = mss;
+        Ok(())
+    }
+
+    /// Returns the maximum segment size of streams created from this socket.
+    ///
+    /// For more information about this option, see [set_mss](TcpSocket::set_mss).
+    pub fn mss(&self) -> Result<u32> {
+        Ok(self.config.borrow().mss)
+    }
+
+    /// Sets the initial congestion window of streams created from this socket.
+    ///
+    /// A stream with a congestion window sends at most `cwnd` packets per
+    /// round of [`yield_intents`](crate::sim::net::IOContext::yield_intents),
+    /// doubling the window after each round that used it fully. If `None`
+    /// is given, streams send all buffered data at once.
+    pub fn set_initial_cwnd(&self, cwnd: Option<u32>) -> Result<()> {
+        if cwnd == Some(0) {
+            return Err(Error::new(ErrorKind::InvalidInput, "cwnd must be non-zero"));
+        }
+
+        self.config.borrow_mut().initial_cwnd = cwnd;
+        Ok(())
+    }
+
+    /// Returns the initial congestion window of streams created from this socket.
+    ///
+    /// For more information about this option, see [set_initial_cwnd](TcpSocket::set_initial_cwnd).
+    pub fn initial_cwnd(&self) -> Result<Option<u32>> {
+        Ok(self.config.borrow().initial_cwnd)
+    }
+
+    /// Sets the keepalive interval of streams created from this socket.
+    ///
+    /// A stream that received nothing from its peer for the given interval
+    /// sends a keepalive probe. If the peer does not answer the configured
+    /// number of probes, see [set_keepalive_probes](TcpSocket::set_keepalive_probes),
+    /// operations on the stream fail with [`ConnectionReset`](ErrorKind::ConnectionReset).
+    ///
+    /// On most operating systems, this sets the SO_KEEPALIVE and TCP_KEEPIDLE socket options.
+    pub fn set_keepalive(&self, interval: Option<Duration>) -> Result<()> {
+        self.config.borrow_mut().keepalive = interval;
+        Ok(())
+    }
+
+    /// Returns the keepalive interval of streams created from this socket.
+    ///
+    /// For more information about this option, see [set_keepalive](TcpSocket::set_keepalive).
+    pub fn keepalive(&self) -> Result<Option<Duration>> {
+        Ok(self.config.borrow().keepalive)
+    }
+
+    /// Sets the number of unanswered keepalive probes, after which a stream
+    /// is considered dead.
+    ///
+    /// On most operating systems, this sets the TCP_KEEPCNT socket option.
+    pub fn set_keepalive_probes(&self, probes: u32) -> Result<()> {
+        self.config.borrow_mut().keepalive_probes = probes;
+        Ok(())
+    }
+
+    /// Returns the number of unanswered keepalive probes, after which a stream
+    /// is considered dead.
+    ///
+    /// For more information about this option, see [set_keepalive_probes](TcpSocket::set_keepalive_probes).
+    pub fn keepalive_probes(&self) -> Result<u32> {
+        Ok(self.config.borrow().keepalive_probes)
+    }
+
+    /// Sets the write stall timeout of streams created from this socket.
+    ///
+    /// For more information about this option, see
+    /// [TcpStream::set_write_stall_timeout](crate::net::TcpStream::set_write_stall_timeout).
+    pub fn set_write_stall_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.config.borrow_mut().write_stall_timeout = dur;
+        Ok(())
+    }
+
+    /// Returns the write stall timeout of streams created from this socket.
+    pub fn write_stall_timeout(&self) -> Result<Option<Duration>> {
+        Ok(self.config.borrow().write_stall_timeout)
+    }
+
     /// Sets the linger duration of this socket by setting the SO_LINGER option.
     ///
     /// This option controls the action taken when a stream has unsent messages
@@ -139,7 +232,7 @@ impl TcpSocket {
     /// Behavior is platform specific. Refer to the target platform’s documentation for more details.
     pub fn bind(&self, addr: SocketAddr) -> Result<()> {
         if self.expect_v4 != addr.ip().is_ipv4() {
-            return Err(Error::new(ErrorKind::Other, "Expected other ip typ"));
+            return Err(SimNetError::IpVersionMismatch.into());
         }
 
         self.config.borrow_mut().addr = addr;
@@ -156,7 +249,7 @@ impl TcpSocket {
     /// Behavior is platform specific. 
     /// Refer to the target platform’s documentation for more details.
     pub async fn connect(self, peer: SocketAddr) -> Result<TcpStream> {
-        let this = IOContext::with_current(|ctx| {
+        let this = IOContext::with_current_or_err(|ctx| {
             ctx.tcp_bind_stream(peer, Some(self.config.into_inner()))
         })?;
 
@@ -174,10 +267,7 @@ impl TcpSocket {
                 if let Some(handle) = ctx.tcp_streams.get(&(this.inner.local_addr, this.inner.peer_addr)) {
                     Ok(handle.acked)
                 } else {
-                    Err(Error::new(
-                        ErrorKind::Other,
-                        "Simulation context has dropped TcpStream",
-                    ))
+                    Err(SimNetError::StreamDropped)
                 }
             })?;
 
@@ -199,7 +289,7 @@ impl TcpSocket {
     /// Refer to the target platform’s documentation for more details.
     pub fn listen(self, backlog: u32) -> Result<TcpListener> {
         self.config.borrow_mut().listen_backlog = backlog;
-        IOContext::with_current(|ctx| {
+        IOContext::with_current_or_err(|ctx| {
             ctx.tcp_bind_listener(self.local_addr()?, Some(self.config.into_inner()))
         })
     }
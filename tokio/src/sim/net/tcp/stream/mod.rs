@@ -1,8 +1,11 @@
-use super::super::{addr::*, Result, IOContext, IOInterest, IOInterestGuard};
+use super::super::{addr::*, Result, IOContext, IOInterest, register_interest, SimNetError};
 use super::TcpStreamInner;
+use crate::future::poll_fn;
 use crate::io::{Error, ErrorKind, Ready, ReadBuf, AsyncRead, AsyncWrite, Interest};
+use crate::time::{sleep, sleep_until, SimTime, Sleep};
 
-use std::net::SocketAddr;
+use std::future::Future;
+use std::net::{Shutdown, SocketAddr};
 use std::task::*;
 use std::time::Duration;
 use std::io::{IoSlice, IoSliceMut};
@@ -16,12 +19,30 @@ mod ref_half;
 // pub use ref_half::*;
 
 /// A TCP Stream.
+///
+/// Dropping the stream, or both halves of a split stream, closes the connection.
+/// Buffered data is still send to the peer, followed by the end of the stream.
 #[derive(Debug)]
 pub struct TcpStream {
     pub(crate) inner: Arc<TcpStreamInner>,
+
+    read_timer: Option<Pin<Box<Sleep>>>,
+    write_timer: Option<Pin<Box<Sleep>>>,
+    stall_timer: Option<Pin<Box<Sleep>>>,
+    linger_timer: Option<Pin<Box<Sleep>>>,
 }
 
 impl TcpStream {
+    pub(crate) fn from_inner(inner: Arc<TcpStreamInner>) -> TcpStream {
+        TcpStream {
+            inner,
+            read_timer: None,
+            write_timer: None,
+            stall_timer: None,
+            linger_timer: None,
+        }
+    }
+
     /// Opens a TCP connection to a remote host.
     /// 
     /// addr is an address of the remote host. 
@@ -30,12 +51,32 @@ impl TcpStream {
     /// until a connection is successful. If none of the addresses result in a successful connection, 
     /// the error returned from the last connection attempt (the last address) is returned.
     pub async fn connect<A: ToSocketAddrs>(addr: A) -> Result<TcpStream> {
+        Self::connect_with(addr, None).await
+    }
+
+    /// Opens a TCP connection to a remote host, failing if a connection attempt
+    /// was not acknowledged within `timeout`.
+    /// 
+    /// This behaves like [connect](TcpStream::connect), but overrides the default
+    /// connect timeout of 2s. The timeout applies to each of the addresses
+    /// individually.
+    pub async fn connect_timeout<A: ToSocketAddrs>(addr: A, timeout: Duration) -> Result<TcpStream> {
+        Self::connect_with(addr, Some(timeout)).await
+    }
+
+    async fn connect_with<A: ToSocketAddrs>(addr: A, timeout: Option<Duration>) -> Result<TcpStream> {
         let addrs = to_socket_addrs(addr).await?;
         let mut last_err = None;
 
         for peer in addrs {
-            let this = IOContext::with_current(|ctx| {
-                ctx.tcp_bind_stream(peer, None)
+            let this = IOContext::with_current_or_err(|ctx| {
+                let this = ctx.tcp_bind_stream(peer, None)?;
+                if let Some(timeout) = timeout {
+                    if let Some(handle) = ctx.tcp_streams.get_mut(&(this.inner.local_addr, this.inner.peer_addr)) {
+                        handle.config.connect_timeout = timeout;
+                    }
+                }
+                Ok(this)
             })?;
     
             loop {
@@ -53,10 +94,7 @@ impl TcpStream {
                     if let Some(handle) = ctx.tcp_streams.get(&(this.inner.local_addr, this.inner.peer_addr)) {
                         Ok(handle.acked)
                     } else {
-                        Err(Error::new(
-                            ErrorKind::Other,
-                            "Simulation context has dropped TcpStream",
-                        ))
+                        Err(ctx.tcp_stream_error(this.inner.key()))
                     }
                 })?;
     
@@ -143,17 +181,12 @@ impl TcpStream {
     pub fn try_read(&self, buf: &mut [u8]) -> Result<usize> {
         IOContext::with_current(|ctx| {
             if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                let n = handle.incoming.read(buf);
-                if n > 0 {
-                    Ok(n)
-                } else {
-                    Err(Error::new(ErrorKind::WouldBlock, "No message could be received non-blocking"))
+                match handle.incoming.read(buf) {
+                    Some(n) => Ok(n),
+                    None => Err(Error::new(ErrorKind::WouldBlock, "No message could be received non-blocking")),
                 }
             } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                ))
+                Err(ctx.tcp_stream_error(self.inner.key()))
             }
         })
     }
@@ -168,7 +201,8 @@ impl TcpStream {
     /// Waits for the socket to become writable.
     /// 
     /// This function is equivalent to `ready(Interest::WRITABLE)` and 
-    /// is usually paired with `try_write()`.
+    /// is usually paired with `try_write()`. The stream is writable
+    /// while its send buffer has room for more data.
     pub async fn writable(&self) -> Result<()> {
         self.ready(Interest::WRITABLE).await?;
         Ok(())
@@ -186,17 +220,7 @@ impl TcpStream {
     /// The function will attempt to write the entire contents of `buf`, 
     /// but only part of the buffer may be written.
     pub fn try_write(&self, buf: &[u8]) -> Result<usize> {
-        IOContext::with_current(|ctx| {
-            if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                if let Err(rem) = handle.outgoing.write(buf) {
-                    Ok(buf.len() - rem.len())
-                } else {
-                    Ok(buf.len())
-                }
-            } else {
-                Err(Error::new(ErrorKind::Other, "Simulation context has lost TcpStream"))
-            }
-        })
+        IOContext::with_current(|ctx| ctx.tcp_write(self.inner.key(), &[IoSlice::new(buf)]))
     }
 
     /// DEPRECATED
@@ -228,21 +252,38 @@ impl TcpStream {
             let interest = IOInterest::TcpRead((self.inner.local_addr, self.inner.peer_addr));
             interest.await?;
 
-            let n = IOContext::with_current(|ctx| {
+            let (n, closed) = IOContext::with_current(|ctx| {
                 if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                    Ok(handle.incoming.peek(buf))
+                    Ok((handle.incoming.peek(buf), handle.is_read_closed()))
                 } else {
-                    Err(Error::new(
-                        ErrorKind::Other,
-                        "Simulation context has dropped TcpStream",
-                    ))
+                    Err(ctx.tcp_stream_error(self.inner.key()))
                 }
             })?;
 
-            if n != 0 { return Ok(n) }
+            if n != 0 || closed { return Ok(n) }
         }
     }
 
+    /// Waits until the peer closed its end of the connection,
+    /// or the connection was reset.
+    ///
+    /// In contrast to a pending read, this does not consume any data,
+    /// so a server can react to a disconnect while otherwise idle.
+    /// Data sent before the peer closed the connection remains readable.
+    pub async fn closed(&self) {
+        let key = self.inner.key();
+        poll_fn(|cx| IOContext::with_current(|ctx| {
+            match ctx.tcp_streams.get_mut(&key) {
+                Some(handle) if !handle.peer_closed => {
+                    // Any packet or reset wakes read interests.
+                    register_interest(&mut handle.interests, IOInterest::TcpRead(key), cx.waker());
+                    Poll::Pending
+                },
+                _ => Poll::Ready(()),
+            }
+        })).await
+    }
+
     /// Gets the value of the TCP_NODELAY option on this socket.
     /// 
     /// For more information about this option, see [set_nodelay](TcpStream::set_nodelay).
@@ -251,10 +292,7 @@ impl TcpStream {
             if let Some(handle) = ctx.tcp_streams.get(&(self.inner.local_addr, self.inner.peer_addr)) {
                 Ok(handle.config.nodelay)
             } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                ))
+                Err(ctx.tcp_stream_error(self.inner.key()))
             }
         })
     }
@@ -272,10 +310,7 @@ impl TcpStream {
                 handle.config.nodelay = nodelay;
                 Ok(())
             } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                ))
+                Err(ctx.tcp_stream_error(self.inner.key()))
             }
         })
     }
@@ -288,10 +323,7 @@ impl TcpStream {
             if let Some(handle) = ctx.tcp_streams.get(&(self.inner.local_addr, self.inner.peer_addr)) {
                 Ok(handle.config.linger)
             } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                ))
+                Err(ctx.tcp_stream_error(self.inner.key()))
             }
         })
     }
@@ -301,16 +333,107 @@ impl TcpStream {
     /// This option controls the action taken when a stream has unsent messages and 
     /// the stream is closed. If SO_LINGER is set, the system shall block the process 
     /// until it can transmit the data or until the time expires.
+    ///
+    /// In the simulation, this applies to [shutdown](crate::io::AsyncWriteExt::shutdown),
+    /// which completes once all buffered data and the end of the stream were send,
+    /// or fails with [TimedOut](ErrorKind::TimedOut) once the linger duration elapsed.
     pub fn set_linger(&self, dur: Option<Duration>) -> Result<()> {
         IOContext::with_current(|ctx| {
             if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
                 handle.config.linger = dur;
                 Ok(())
             } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                ))
+                Err(ctx.tcp_stream_error(self.inner.key()))
+            }
+        })
+    }
+
+    /// Gets the value of the SO_RCVTIMEO option on this socket.
+    /// 
+    /// For more information about this option, see [set_read_timeout](TcpStream::set_read_timeout).
+    pub fn read_timeout(&self) -> Result<Option<Duration>> {
+        IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_streams.get(&(self.inner.local_addr, self.inner.peer_addr)) {
+                Ok(handle.config.read_timeout)
+            } else {
+                Err(ctx.tcp_stream_error(self.inner.key()))
+            }
+        })
+    }
+
+    /// Sets the value of the SO_RCVTIMEO option on this socket.
+    /// 
+    /// If set, a read that makes no progress within the timeout fails with
+    /// [`ErrorKind::TimedOut`]. The timeout is measured in simulation time, starting
+    /// with the first poll that could not read any data. Only reads on the `TcpStream`
+    /// itself are affected, not reads on its split halves.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
+                handle.config.read_timeout = dur;
+                Ok(())
+            } else {
+                Err(ctx.tcp_stream_error(self.inner.key()))
+            }
+        })
+    }
+
+    /// Gets the value of the SO_SNDTIMEO option on this socket.
+    /// 
+    /// For more information about this option, see [set_write_timeout](TcpStream::set_write_timeout).
+    pub fn write_timeout(&self) -> Result<Option<Duration>> {
+        IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_streams.get(&(self.inner.local_addr, self.inner.peer_addr)) {
+                Ok(handle.config.write_timeout)
+            } else {
+                Err(ctx.tcp_stream_error(self.inner.key()))
+            }
+        })
+    }
+
+    /// Sets the value of the SO_SNDTIMEO option on this socket.
+    /// 
+    /// If set, a write that makes no progress within the timeout, since the send
+    /// buffer is full, fails with [`ErrorKind::TimedOut`]. Like the read timeout,
+    /// this only affects writes on the `TcpStream` itself.
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
+                handle.config.write_timeout = dur;
+                Ok(())
+            } else {
+                Err(ctx.tcp_stream_error(self.inner.key()))
+            }
+        })
+    }
+
+    /// Gets the write stall timeout of this socket.
+    /// 
+    /// For more information about this option, see [set_write_stall_timeout](TcpStream::set_write_stall_timeout).
+    pub fn write_stall_timeout(&self) -> Result<Option<Duration>> {
+        IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_streams.get(&(self.inner.local_addr, self.inner.peer_addr)) {
+                Ok(handle.config.write_stall_timeout)
+            } else {
+                Err(ctx.tcp_stream_error(self.inner.key()))
+            }
+        })
+    }
+
+    /// Sets the write stall timeout of this socket.
+    /// 
+    /// If set, a stream whose send buffer stays full for the timeout is reset,
+    /// since the peer is assumed to be dead. The blocked write, and all further
+    /// operations on the stream, fail with [`ErrorKind::ConnectionReset`].
+    /// In contrast to the write timeout, the stall is measured across writes,
+    /// until any write makes progress.
+    pub fn set_write_stall_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
+                handle.config.write_stall_timeout = dur;
+                Ok(())
+            } else {
+                Err(ctx.tcp_stream_error(self.inner.key()))
             }
         })
     }
@@ -323,10 +446,7 @@ impl TcpStream {
             if let Some(handle) = ctx.tcp_streams.get(&(self.inner.local_addr, self.inner.peer_addr)) {
                 Ok(handle.config.ttl)
             } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                ))
+                Err(ctx.tcp_stream_error(self.inner.key()))
             }
         })
     }
@@ -340,17 +460,30 @@ impl TcpStream {
                 handle.config.ttl = ttl;
                 Ok(())
             } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                ))
+                Err(ctx.tcp_stream_error(self.inner.key()))
             }
         })
     }
 
+    /// Shuts down the read, write, or both halves of this connection.
+    /// 
+    /// Shutting down the write half signals EOF to the peer once all buffered
+    /// data was send, like [poll_shutdown](AsyncWrite::poll_shutdown). Subsequent writes
+    /// fail with [`SimNetError::WriteClosed`].
+    /// Shutting down the read half discards all unread data, so that subsequent
+    /// reads return EOF. Data arriving afterwards is dropped.
+    /// 
+    /// Note that this method shadows [`AsyncWriteExt::shutdown`](crate::io::AsyncWriteExt::shutdown),
+    /// which must be called explicitly to shut down the write half asynchronously.
+    pub fn shutdown(&self, how: Shutdown) -> Result<()> {
+        IOContext::with_current(|ctx| {
+            ctx.tcp_shutdown((self.inner.local_addr, self.inner.peer_addr), how)
+        })
+    }
+
     /// Splits a `TcpStream` into a read half and a write half, which can be used to read and write the stream concurrently.
     pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
-        (OwnedReadHalf { inner: self.inner.clone() }, OwnedWriteHalf { inner: self.inner })
+        (OwnedReadHalf::new(self.inner.clone()), OwnedWriteHalf::new(self.inner))
     }
 }
 
@@ -360,28 +493,25 @@ impl AsyncRead for TcpStream {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>
     ) -> Poll<Result<()>> {
+        let this = self.get_mut();
+
         // Whenever polled -- try to fill the buffer first
-        IOContext::with_current(|ctx| {
-            if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                let old = buf.remaining();
-                let remaining = handle.incoming.read_buf(buf);
-                if old != remaining {
-                    Poll::Ready(Ok(()))
+        let (poll, timeout) = IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_streams.get_mut(&(this.inner.local_addr, this.inner.peer_addr)) {
+                if handle.incoming.read_buf(buf).is_some() {
+                    // Reading nothing after the peer closed the stream signals EOF.
+                    (Poll::Ready(Ok(())), None)
                 } else {
-                    let interest = IOInterest::TcpRead((self.inner.local_addr, self.inner.peer_addr));
-                    handle.interests.push(IOInterestGuard {
-                        interest: interest.clone(),
-                        waker: cx.waker().clone(),
-                    });
-                    Poll::Pending
+                    let interest = IOInterest::TcpRead((this.inner.local_addr, this.inner.peer_addr));
+                    register_interest(&mut handle.interests, interest, cx.waker());
+                    (Poll::Pending, handle.config.read_timeout)
                 }
             } else {
-                Poll::Ready(Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                )))
+                (Poll::Ready(Err(ctx.tcp_stream_error(this.inner.key()))), None)
             }
-        })
+        });
+
+        poll_with_timeout(&mut this.read_timer, poll, timeout, cx)
     }
 }
 
@@ -391,23 +521,29 @@ impl AsyncWrite for TcpStream {
         cx: &mut Context<'_>,
         buf: &[u8]
     ) -> Poll<Result<usize>> {
-        IOContext::with_current(|ctx| {
-            if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                if let Err(rem) = handle.outgoing.write(buf) {
-                    if buf.len() == rem.len() {
-                        // must be exceeded buffer size
-                        ctx.tick_wakeups.push(cx.waker().clone());
-                        Poll::Pending
-                    } else {
-                        Poll::Ready(Ok(buf.len() - rem.len()))
-                    }
-                } else {
-                    Poll::Ready(Ok(buf.len()))
-                }
-            } else {
-                Poll::Ready(Err(Error::new(ErrorKind::Other, "Simulation context has lost TcpStream"))) 
-            }
-        })
+        let this = self.get_mut();
+        let (poll, timeout, stall) = IOContext::with_current(|ctx| {
+            ctx.poll_tcp_write(cx, this.inner.key(), &[IoSlice::new(buf)])
+        });
+
+        poll_stall_timer(&mut this.stall_timer, stall, cx);
+        poll_with_timeout(&mut this.write_timer, poll, timeout, cx)
+    }
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        let (poll, timeout, stall) = IOContext::with_current(|ctx| {
+            ctx.poll_tcp_write(cx, this.inner.key(), bufs)
+        });
+
+        poll_stall_timer(&mut this.stall_timer, stall, cx);
+        poll_with_timeout(&mut this.write_timer, poll, timeout, cx)
+    }
+    fn is_write_vectored(&self) -> bool {
+        true
     }
     fn poll_flush(
         self: Pin<&mut Self>,
@@ -418,8 +554,1448 @@ impl AsyncWrite for TcpStream {
     }
     fn poll_shutdown(
         self: Pin<&mut Self>,
-        _: &mut Context<'_>
+        cx: &mut Context<'_>
     ) -> Poll<Result<()>> {
-        Poll::Ready(Ok(()))
+        // With linger set, wait until all buffered data was sent.
+        let this = self.get_mut();
+        let (poll, linger) = IOContext::with_current(|ctx| {
+            ctx.poll_tcp_shutdown_write(cx, this.inner.key())
+        });
+        poll_with_timeout(&mut this.linger_timer, poll, linger, cx)
     }
-}
\ No newline at end of file
+}
+
+/// Arms the socket timeout while an operation makes no progress,
+/// failing the operation once the timeout elapsed.
+fn poll_with_timeout<T>(
+    timer: &mut Option<Pin<Box<Sleep>>>,
+    poll: Poll<Result<T>>,
+    timeout: Option<Duration>,
+    cx: &mut Context<'_>
+) -> Poll<Result<T>> {
+    let timeout = match (&poll, timeout) {
+        (Poll::Pending, Some(timeout)) => timeout,
+        _ => {
+            *timer = None;
+            return poll
+        }
+    };
+
+    let timer_ref = timer.get_or_insert_with(|| Box::pin(sleep(timeout)));
+    if timer_ref.as_mut().poll(cx).is_ready() {
+        *timer = None;
+        return Poll::Ready(Err(SimNetError::TimedOut.into()))
+    }
+
+    Poll::Pending
+}
+
+/// Arms a timer to wake the task once a stalled write should reset the stream.
+fn poll_stall_timer(
+    timer: &mut Option<Pin<Box<Sleep>>>,
+    deadline: Option<SimTime>,
+    cx: &mut Context<'_>
+) {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => {
+            *timer = None;
+            return
+        }
+    };
+
+    let timer_ref = timer.get_or_insert_with(|| Box::pin(sleep_until(deadline)));
+    if timer_ref.deadline() != deadline {
+        timer_ref.as_mut().reset(deadline);
+    }
+    let _ = timer_ref.as_mut().poll(cx);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::net::{TcpListener, TcpSocket};
+    use crate::runtime::Runtime;
+    use crate::sim::net::{testutil, IOIntent, TcpKeepalive};
+    use crate::task::JoinHandle;
+    use crate::time::SimTime;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::Mutex;
+
+    #[test]
+    fn connect_timeout_to_dead_port() {
+        let (rt, ctx) = testutil::node("connect_timeout");
+        let guard = rt.enter_context(ctx);
+
+        let client = rt.spawn(async {
+            let result =
+                TcpStream::connect_timeout("127.0.0.1:81", Duration::from_millis(100)).await;
+            (result.map(|_| ()), SimTime::now())
+        });
+
+        let mut timeouts = Vec::new();
+        for _ in 0..20 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                match intent {
+                    IOIntent::TcpConnect(msg) => assert!(rt.process_tcp_connect(msg).is_err()),
+                    IOIntent::TcpConnectTimeout(msg, dur) => {
+                        timeouts.push((SimTime::now() + dur, msg))
+                    }
+                    _ => {}
+                }
+            }
+
+            SimTime::set_now(SimTime::now() + Duration::from_millis(10));
+            for (_, msg) in timeouts.iter().filter(|(at, _)| *at <= SimTime::now()) {
+                rt.process_tcp_connect_timeout(*msg).unwrap();
+            }
+            timeouts.retain(|(at, _)| *at > SimTime::now());
+        }
+
+        let (result, failed_at) = rt.block_or_idle_on(client).unwrap().unwrap();
+        let err = result.unwrap_err();
+        assert_eq!(
+            SimNetError::from_io(&err),
+            Some(SimNetError::ConnectionTimedOut)
+        );
+        assert_eq!(
+            failed_at,
+            SimTime::from_duration(Duration::from_millis(100))
+        );
+
+        guard.leave();
+    }
+
+    #[test]
+    fn write_vectored_reaches_peer_in_order() {
+        let (rt, ctx) = testutil::node("write_vectored");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            let mut buf = vec![0u8; 1500];
+            stream.read_exact(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await?;
+            let (a, b, c) = (vec![1u8; 400], vec![2u8; 500], vec![3u8; 600]);
+            let bufs = [IoSlice::new(&a), IoSlice::new(&b), IoSlice::new(&c)];
+            let n = stream.write_vectored(&bufs).await?;
+            Result::Ok((n, stream))
+        });
+
+        for _ in 0..8 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                match intent {
+                    IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                    IOIntent::TcpSendPacket(msg, _) => rt.process_tcp_packet(msg).unwrap(),
+                    IOIntent::IoTick(_) => rt.io_tick(),
+                    _ => {}
+                }
+            }
+        }
+
+        let (n, _stream) = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(n, 1500);
+
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert!(buf[..400].iter().all(|&b| b == 1));
+        assert!(buf[400..900].iter().all(|&b| b == 2));
+        assert!(buf[900..].iter().all(|&b| b == 3));
+
+        guard.leave();
+    }
+
+    fn send_and_shutdown(rt: &Runtime, payload: &'static [u8]) -> JoinHandle<Result<TcpStream>> {
+        let client = rt.spawn(async move {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await?;
+            stream.write_all(payload).await?;
+            AsyncWriteExt::shutdown(&mut stream).await?;
+            Result::Ok(stream)
+        });
+        testutil::route(rt);
+        client
+    }
+
+    #[test]
+    fn read_to_end_after_peer_shutdown() {
+        let (rt, ctx) = testutil::node("read_to_end");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        rt.poll_until_idle();
+
+        let client = send_and_shutdown(&rt, b"hello world");
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(buf, b"hello world");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn write_stall_resets_stream() {
+        let (rt, ctx) = testutil::node("write_stall");
+        let guard = rt.enter_context(ctx);
+
+        let _server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (stream, _) = listener.accept().await?;
+            crate::time::sleep(Duration::from_secs(60)).await;
+            Result::Ok(stream)
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async {
+            let socket = TcpSocket::new_v4()?;
+            socket.set_send_buffer_size(1024)?;
+            socket.set_write_stall_timeout(Some(Duration::from_secs(5)))?;
+            let mut stream = socket.connect("127.0.0.1:80".parse().unwrap()).await?;
+
+            let result = stream.write_all(&[0u8; 4096]).await;
+            let read = stream.read(&mut [0u8; 8]).await;
+            let (write, read) = (result.map_err(|e| e.kind()), read.map_err(|e| e.kind()));
+            Result::Ok((write, read, SimTime::now()))
+        });
+        for _ in 0..2 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let IOIntent::TcpConnect(msg) = intent {
+                    rt.process_tcp_connect(msg).unwrap();
+                }
+            }
+        }
+
+        // The peer never reads, and no packets leave the node,
+        // so the send buffer stays full.
+        rt.advance(Duration::from_secs(10));
+
+        let (write, read, time) = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(write, Err(ErrorKind::ConnectionReset));
+        assert_eq!(read, Err(ErrorKind::ConnectionReset));
+        assert_eq!(time, SimTime::from_secs(5));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn write_stall_restarts_after_progress() {
+        let (rt, ctx) = testutil::node("write_stall_split");
+        let guard = rt.enter_context(ctx);
+
+        let _server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (stream, _) = listener.accept().await?;
+            crate::time::sleep(Duration::from_secs(60)).await;
+            Result::Ok(stream)
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async {
+            let socket = TcpSocket::new_v4()?;
+            socket.set_send_buffer_size(1024)?;
+            socket.set_write_stall_timeout(Some(Duration::from_secs(5)))?;
+            let stream = socket.connect("127.0.0.1:80".parse().unwrap()).await?;
+            let (_read, mut write) = stream.into_split();
+
+            // The first stall is cancelled, before the buffer drains.
+            let cancelled =
+                crate::time::timeout(Duration::from_secs(1), write.write_all(&[0u8; 4096])).await;
+            assert!(cancelled.is_err());
+            crate::time::sleep(Duration::from_secs(5)).await;
+
+            assert_eq!(write.try_write(&[0u8; 1024])?, 1024);
+            let result = write.write_all(&[0u8; 4096]).await;
+            Result::Ok((result.map_err(|e| e.kind()), SimTime::now()))
+        });
+        for _ in 0..2 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let IOIntent::TcpConnect(msg) = intent {
+                    rt.process_tcp_connect(msg).unwrap();
+                }
+            }
+        }
+
+        // The buffer drains at 2s, so the second stall starts at 6s.
+        rt.advance(Duration::from_secs(2));
+        assert!(!rt.yield_intents().is_empty());
+        rt.advance(Duration::from_secs(20));
+
+        let (write, time) = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(write, Err(ErrorKind::ConnectionReset));
+        assert_eq!(time, SimTime::from_secs(11));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn closed_resolves_after_peer_drop() {
+        let (rt, ctx) = testutil::node("closed");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            stream.closed().await;
+
+            // The data sent before the close was not consumed.
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        rt.poll_until_idle();
+
+        let (tx, rx) = crate::sync::oneshot::channel::<()>();
+        rt.spawn(async move {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await?;
+            stream.write_all(b"bye").await?;
+            let _ = rx.await;
+            Result::Ok(())
+        });
+        testutil::route(&rt);
+        assert!(!server.is_finished());
+
+        tx.send(()).unwrap();
+        testutil::route(&rt);
+
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(buf, b"bye");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn take_after_peer_shutdown() {
+        let (rt, ctx) = testutil::node("take");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (stream, _) = listener.accept().await?;
+
+            let mut prefix = Vec::new();
+            let mut stream = stream.take(5);
+            stream.read_to_end(&mut prefix).await?;
+
+            let mut rest = Vec::new();
+            let mut stream = stream.into_inner().take(100);
+            stream.read_to_end(&mut rest).await?;
+            Result::Ok((prefix, rest))
+        });
+        rt.poll_until_idle();
+
+        let client = send_and_shutdown(&rt, b"hello world");
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        let (prefix, rest) = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(prefix, b"hello");
+        assert_eq!(rest, b" world");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn read_exact_past_peer_shutdown() {
+        let (rt, ctx) = testutil::node("read_exact");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await?;
+            assert_eq!(&buf, b"hello");
+
+            let mut buf = [0u8; 16];
+            Result::Ok(stream.read_exact(&mut buf).await.map(|_| ()))
+        });
+        rt.poll_until_idle();
+
+        let client = send_and_shutdown(&rt, b"hello world");
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        let result = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn read_timeout_fires_without_data() {
+        let (rt, ctx) = testutil::node("read_timeout");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+            let mut buf = [0u8; 5];
+            let result = stream.read(&mut buf).await;
+            Result::Ok((result.map_err(|e| e.kind()), SimTime::now()))
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async { TcpStream::connect("127.0.0.1:80").await });
+        testutil::route(&rt);
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        rt.advance(Duration::from_millis(200));
+        let (result, at) = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(result, Err(ErrorKind::TimedOut));
+        assert_eq!(at, SimTime::from_duration(Duration::from_millis(100)));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn read_timeout_not_fired_when_data_arrives() {
+        let (rt, ctx) = testutil::node("read_timeout");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await?;
+            crate::time::sleep(Duration::from_millis(50)).await;
+            stream.write_all(b"hello").await?;
+            Result::Ok(stream)
+        });
+        testutil::route(&rt);
+
+        rt.advance(Duration::from_millis(50));
+        testutil::route(&rt);
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        rt.advance(Duration::from_millis(200));
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(&buf, b"hello");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn split_read_timeout_fires_without_data() {
+        let (rt, ctx) = testutil::node("read_timeout");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (stream, _) = listener.accept().await?;
+            stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+            let (mut rx, _tx) = stream.into_split();
+
+            let mut buf = [0u8; 5];
+            let result = rx.read(&mut buf).await;
+            Result::Ok((result.map_err(|e| e.kind()), SimTime::now()))
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async { TcpStream::connect("127.0.0.1:80").await });
+        testutil::route(&rt);
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        rt.advance(Duration::from_millis(200));
+        let (result, at) = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(result, Err(ErrorKind::TimedOut));
+        assert_eq!(at, SimTime::from_duration(Duration::from_millis(100)));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn repeated_read_polls_register_once() {
+        let (rt, ctx) = testutil::node("read_timeout");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+            let key = stream.inner.key();
+
+            poll_fn(|cx| {
+                let mut storage = [0u8; 8];
+                let mut read = ReadBuf::new(&mut storage);
+                for _ in 0..3 {
+                    assert!(Pin::new(&mut stream).poll_read(cx, &mut read).is_pending());
+                }
+                Poll::Ready(())
+            })
+            .await;
+            let stream_interests =
+                IOContext::with_current(|ctx| ctx.tcp_streams[&key].interests.len());
+
+            let (mut read_half, _write_half) = stream.into_split();
+            poll_fn(|cx| {
+                let mut storage = [0u8; 8];
+                let mut read = ReadBuf::new(&mut storage);
+                for _ in 0..3 {
+                    assert!(Pin::new(&mut read_half).poll_read(cx, &mut read).is_pending());
+                }
+                Poll::Ready(())
+            })
+            .await;
+            let half_interests =
+                IOContext::with_current(|ctx| ctx.tcp_streams[&key].interests.len());
+
+            Result::Ok((stream_interests, half_interests))
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async { TcpStream::connect("127.0.0.1:80").await });
+        testutil::route(&rt);
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        let interests = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(interests, (1, 1));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn write_timeout_fires_on_full_buffer() {
+        let (rt, ctx) = testutil::node("write_timeout");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (stream, _) = listener.accept().await?;
+            Result::Ok(stream)
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await?;
+            stream.set_write_timeout(Some(Duration::from_millis(100)))?;
+            crate::time::sleep(Duration::from_millis(10)).await;
+
+            let result = stream.write_all(&[0u8; 4096]).await;
+            Result::Ok((result.map_err(|e| e.kind()), SimTime::now()))
+        });
+        testutil::route(&rt);
+        let _server = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+
+        // No intents are yielded, so the send buffer is never drained.
+        rt.advance(Duration::from_millis(200));
+        let (result, at) = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(result, Err(ErrorKind::TimedOut));
+        assert_eq!(at, SimTime::from_duration(Duration::from_millis(110)));
+
+        guard.leave();
+    }
+
+    fn accept_and_connect(rt: &Runtime) -> (TcpStream, TcpStream) {
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (stream, _) = listener.accept().await?;
+            Result::Ok(stream)
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(TcpStream::connect("127.0.0.1:80"));
+        testutil::route(rt);
+
+        let server = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        let client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn shutdown_write() {
+        let (rt, ctx) = testutil::node("shutdown_write");
+        let guard = rt.enter_context(ctx);
+        let (mut server, mut client) = accept_and_connect(&rt);
+
+        let client = rt.spawn(async move {
+            client.write_all(b"hello").await?;
+            client.shutdown(Shutdown::Write)?;
+            let result = client.write_all(b"world").await;
+            Result::Ok(result.map_err(|e| e.kind()))
+        });
+        testutil::route(&rt);
+        let result = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(result, Err(ErrorKind::BrokenPipe));
+
+        let server = rt.spawn(async move {
+            let mut buf = Vec::new();
+            server.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(buf, b"hello");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn shutdown_read() {
+        let (rt, ctx) = testutil::node("shutdown_read");
+        let guard = rt.enter_context(ctx);
+        let (mut server, mut client) = accept_and_connect(&rt);
+
+        let client = rt.spawn(async move {
+            client.write_all(b"hello").await?;
+            Result::Ok(client)
+        });
+        testutil::route(&rt);
+        let mut client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        // Unread and later data is discarded, writing remains possible.
+        server.shutdown(Shutdown::Read).unwrap();
+        let client = rt.spawn(async move {
+            client.write_all(b"world").await?;
+            let mut buf = [0u8; 3];
+            client.read_exact(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        let server = rt.spawn(async move {
+            let mut buf = Vec::new();
+            server.read_to_end(&mut buf).await?;
+            server.write_all(b"bye").await?;
+            Result::Ok(buf)
+        });
+        testutil::route(&rt);
+
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert!(buf.is_empty());
+        let buf = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(&buf, b"bye");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn shutdown_both() {
+        let (rt, ctx) = testutil::node("shutdown_both");
+        let guard = rt.enter_context(ctx);
+        let (mut server, mut client) = accept_and_connect(&rt);
+
+        server.shutdown(Shutdown::Both).unwrap();
+        let server = rt.spawn(async move {
+            let mut buf = [0u8; 8];
+            let n = server.read(&mut buf).await?;
+            let result = server.write(b"hello").await;
+            Result::Ok((n, result.map_err(|e| e.kind())))
+        });
+        let client = rt.spawn(async move {
+            let mut buf = Vec::new();
+            client.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        testutil::route(&rt);
+
+        let (n, result) = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(n, 0);
+        assert_eq!(result, Err(ErrorKind::BrokenPipe));
+
+        let buf = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert!(buf.is_empty());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn shutdown_with_linger_flushes() {
+        let (rt, ctx) = testutil::node("shutdown_with_linger");
+        let guard = rt.enter_context(ctx);
+        let (mut server, client) = accept_and_connect(&rt);
+        client.set_linger(Some(Duration::from_secs(1))).unwrap();
+
+        let payload = (0..8192).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let data = payload.clone();
+        let mut client = rt.spawn(async move {
+            let (read, mut write) = client.into_split();
+            write.write_all(&data).await?;
+            AsyncWriteExt::shutdown(&mut write).await?;
+            Result::Ok((read, write))
+        });
+        let server = rt.spawn(async move {
+            let mut buf = Vec::new();
+            server.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+
+        // The shutdown waits until the buffered data was send.
+        rt.poll_until_idle();
+        assert!(rt.block_or_idle_on(&mut client).is_err());
+
+        testutil::route(&rt);
+        let _halves = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(buf, payload);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn linger_ignores_cancelled_write_timeout() {
+        let (rt, ctx) = testutil::node("linger_ignores_cancelled_write_timeout");
+        let guard = rt.enter_context(ctx);
+        let (mut server, mut client) = accept_and_connect(&rt);
+        client
+            .set_write_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        client.set_linger(Some(Duration::from_secs(5))).unwrap();
+
+        let payload = (0..1 << 20).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let data = payload.clone();
+        let mut client = rt.spawn(async move {
+            // The write blocks on the full send buffer and is cancelled,
+            // before its timeout elapsed.
+            let write = crate::time::timeout(Duration::from_millis(500), client.write_all(&data));
+            assert!(write.await.is_err());
+            AsyncWriteExt::shutdown(&mut client).await?;
+            Result::Ok(client)
+        });
+        let server = rt.spawn(async move {
+            let mut buf = Vec::new();
+            server.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+
+        // The shutdown is bound by the linger duration only.
+        rt.poll_until_idle();
+        rt.advance(Duration::from_secs(2));
+        assert!(rt.block_or_idle_on(&mut client).is_err());
+
+        testutil::route(&rt);
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert!(!buf.is_empty() && payload.starts_with(&buf));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn tx_queue_limit_blocks_writes() {
+        let (rt, ctx) = testutil::node("tx_queue_limit_blocks_writes");
+        let guard = rt.enter_context(ctx);
+        let (mut server, mut client) = accept_and_connect(&rt);
+        IOContext::with_current(|ctx| ctx.set_tx_queue_limit(Some(0)));
+
+        let err = client.try_write(b"hello").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+
+        let mut client = rt.spawn(async move {
+            client.write_all(b"hello").await?;
+            Result::Ok(client)
+        });
+        assert!(rt.block_or_idle_on(&mut client).is_err());
+
+        // Yielding the intents lets the write continue.
+        IOContext::with_current(|ctx| ctx.set_tx_queue_limit(None));
+        testutil::route(&rt);
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        let server = rt.spawn(async move {
+            let mut buf = [0; 5];
+            server.read_exact(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        testutil::route(&rt);
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(&buf, b"hello");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn segment_by_mss() {
+        let (rt, ctx) = testutil::node("segment_by_mss");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            let mut buf = vec![0u8; 4096];
+            stream.read_exact(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        rt.poll_until_idle();
+
+        let payload = (0..4096).map(|i| i as u8).collect::<Vec<_>>();
+        let data = payload.clone();
+        let client = rt.spawn(async move {
+            let socket = TcpSocket::new_v4()?;
+            socket.set_mss(512)?;
+            socket.set_send_buffer_size(4096)?;
+            let mut stream = socket.connect("127.0.0.1:80".parse().unwrap()).await?;
+            stream.write_all(&data).await?;
+            Result::Ok(stream)
+        });
+
+        let mut packets = 0;
+        for _ in 0..8 {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                match intent {
+                    IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                    IOIntent::TcpSendPacket(msg, _) => {
+                        // The end of the stream is signaled by an empty packet.
+                        if !msg.content.is_empty() {
+                            assert!(msg.content.len() <= 512);
+                            packets += 1;
+                        }
+                        rt.process_tcp_packet(msg).unwrap()
+                    }
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(packets, 8);
+
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(buf, payload);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn serialization_delay_between_packets() {
+        let (rt, ctx) = testutil::node("serialization_delay");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        rt.poll_until_idle();
+
+        let (tx, rx) = crate::sync::oneshot::channel::<()>();
+        let client = rt.spawn(async move {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await?;
+            stream.write_all(&[1; 2048]).await?;
+            rx.await.unwrap();
+            stream.write_all(&[2; 2048]).await?;
+            Result::Ok(())
+        });
+
+        let delays = |rt: &Runtime| {
+            let mut delays = Vec::new();
+            for _ in 0..4 {
+                rt.poll_until_idle();
+                for intent in rt.yield_intents() {
+                    match intent {
+                        IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                        IOIntent::TcpSendPacket(msg, delay) => {
+                            if !msg.content.is_empty() {
+                                delays.push(delay);
+                            }
+                            rt.process_tcp_packet(msg).unwrap()
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            delays
+        };
+
+        let ms = Duration::from_millis;
+        IOContext::with_current(|ctx| ctx.set_serialization_delay(ms(2)));
+        let fixed = delays(&rt);
+        assert_eq!(fixed, vec![ms(0), ms(2)]);
+
+        // 1024 byte packets at 1MB/s take 1ms to transmit.
+        IOContext::with_current(|ctx| ctx.set_bandwidth(Some(1_024_000)));
+        tx.send(()).unwrap();
+        let derived = delays(&rt);
+        assert_eq!(derived, vec![ms(0), ms(1)]);
+
+        rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        drop(server);
+        guard.leave();
+    }
+
+    #[test]
+    fn link_shared_fairly() {
+        let (rt, ctx) = testutil::node("link_shared_fairly");
+        let guard = rt.enter_context(ctx);
+
+        // 1024 byte packets at 1MB/s take 1ms to transmit.
+        let en0 = IpAddr::V4(Ipv4Addr::new(192, 168, 2, 1));
+        IOContext::with_current(|ctx| ctx.set_link(en0, Some(1_024_000)));
+
+        let received = Arc::new(Mutex::new(BTreeMap::<SocketAddr, usize>::new()));
+        let counters = received.clone();
+        rt.spawn(async move {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            for _ in 0..2 {
+                let (mut stream, peer) = listener.accept().await?;
+                let counters = counters.clone();
+                crate::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    loop {
+                        let n = stream.read(&mut buf).await?;
+                        *counters.lock().unwrap().entry(peer).or_default() += n;
+                        if n == 0 {
+                            return Result::Ok(());
+                        }
+                    }
+                });
+            }
+            Result::Ok(())
+        });
+        rt.poll_until_idle();
+
+        // Both streams saturate the link for the first 100ms.
+        for _ in 0..2 {
+            rt.spawn(async {
+                let mut stream = TcpStream::connect("127.0.0.1:80").await?;
+                stream.write_all(&[0u8; 64 * 1024]).await?;
+                Result::Ok(stream)
+            });
+        }
+
+        let end = SimTime::now() + Duration::from_millis(100);
+        let mut ticks = BTreeSet::new();
+        while SimTime::now() < end {
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                match intent {
+                    IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                    IOIntent::TcpSendPacket(msg, _) => rt.process_tcp_packet(msg).unwrap(),
+                    IOIntent::IoTick(at) => {
+                        ticks.insert(at);
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(&at) = ticks.iter().next() {
+                ticks.remove(&at);
+                rt.advance_to(at.min(end));
+                rt.io_tick();
+            }
+        }
+        rt.poll_until_idle();
+
+        // Each stream received half of the packets send within 100ms.
+        let link = IOContext::with_current(|ctx| ctx.link(en0).unwrap().clone());
+        assert_eq!(link.total_bytes_sent(), 100 * 1024);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let server = "127.0.0.1:80".parse().unwrap();
+        for (peer, n) in received.iter() {
+            assert_eq!(link.bytes_sent(*peer, server), *n as u64);
+            assert_eq!(*n, 50 * 1024);
+        }
+
+        drop(received);
+        guard.leave();
+    }
+
+    #[test]
+    fn congestion_window_slow_start() {
+        let (rt, ctx) = testutil::node("congestion_window_slow_start");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        rt.poll_until_idle();
+
+        let payload = (0..15 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        let data = payload.clone();
+        let client = rt.spawn(async move {
+            let socket = TcpSocket::new_v4()?;
+            socket.set_initial_cwnd(Some(1))?;
+            socket.set_send_buffer_size(16 * 1024)?;
+            let mut stream = socket.connect("127.0.0.1:80".parse().unwrap()).await?;
+            stream.write_all(&data).await?;
+            Result::Ok(())
+        });
+
+        let mut rounds = Vec::new();
+        for _ in 0..8 {
+            rt.poll_until_idle();
+            let mut packets = 0;
+            for intent in rt.yield_intents() {
+                match intent {
+                    IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                    IOIntent::TcpSendPacket(msg, _) => {
+                        if !msg.content.is_empty() {
+                            packets += 1;
+                        }
+                        rt.process_tcp_packet(msg).unwrap()
+                    }
+                    _ => {}
+                }
+            }
+            if packets > 0 {
+                rounds.push(packets);
+            }
+        }
+        assert_eq!(rounds, vec![1, 2, 4, 8]);
+
+        rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(buf, payload);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn reassemble_reordered_segments() {
+        let (rt, ctx) = testutil::node("reassemble_reordered_segments");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async {
+            let socket = TcpSocket::new_v4()?;
+            socket.set_mss(5)?;
+            let mut stream = socket.connect("127.0.0.1:80".parse().unwrap()).await?;
+            stream.write_all(b"helloworld").await?;
+            AsyncWriteExt::shutdown(&mut stream).await?;
+            Result::Ok(stream)
+        });
+
+        for _ in 0..4 {
+            rt.poll_until_idle();
+            let mut packets = Vec::new();
+            for intent in rt.yield_intents() {
+                match intent {
+                    IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                    IOIntent::TcpSendPacket(msg, _) => packets.push(msg),
+                    _ => {}
+                }
+            }
+
+            // Deliver all segments, including the end of the stream, reversed.
+            for msg in packets.into_iter().rev() {
+                rt.process_tcp_packet(msg).unwrap();
+            }
+        }
+
+        let _client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(buf, b"helloworld");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn write_all_with_backpressure() {
+        const LEN: usize = 1 << 20;
+        const SEND_BUFFER: usize = 4096;
+
+        let (rt, ctx) = testutil::node("write_all_with_backpressure");
+        let guard = rt.enter_context(ctx);
+
+        let mut server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            let mut received = Vec::with_capacity(LEN);
+            let mut buf = [0u8; 1024];
+            while received.len() < LEN {
+                let n = stream.read(&mut buf).await?;
+                received.extend_from_slice(&buf[..n]);
+                sleep(Duration::from_millis(1)).await;
+            }
+            Result::Ok(received)
+        });
+        rt.poll_until_idle();
+
+        let payload = (0..LEN).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let data = payload.clone();
+        let mut client = rt.spawn(async move {
+            let socket = TcpSocket::new_v4()?;
+            socket.set_send_buffer_size(SEND_BUFFER as u32)?;
+            let mut stream = socket.connect("127.0.0.1:80".parse().unwrap()).await?;
+            stream.write_all(&data).await?;
+            Result::Ok(stream)
+        });
+
+        // Routes the intents of one round, returning the number of bytes send.
+        let round = || {
+            let mut sent = 0;
+            for intent in rt.yield_intents() {
+                match intent {
+                    IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                    IOIntent::TcpSendPacket(msg, _) => {
+                        sent += msg.content.len();
+                        rt.process_tcp_packet(msg).unwrap()
+                    }
+                    IOIntent::IoTick(_) => rt.io_tick(),
+                    _ => {}
+                }
+            }
+            rt.advance(Duration::from_millis(1));
+            sent
+        };
+
+        let mut rounds = 0;
+        let polls = rt.sim_metrics().task_polls;
+        while rt.block_or_idle_on(&mut client).is_err() {
+            rounds += 1;
+            assert!(round() <= SEND_BUFFER);
+        }
+
+        // The writer parked on a full send buffer, instead of spinning.
+        assert!(rounds >= LEN / SEND_BUFFER);
+        assert!(rt.sim_metrics().task_polls - polls < 4 * rounds);
+
+        let buf = loop {
+            if let Ok(buf) = rt.block_or_idle_on(&mut server) {
+                break buf.unwrap().unwrap();
+            }
+            round();
+        };
+        assert_eq!(buf, payload);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn readiness_driven_proxy() {
+        const LEN: usize = 64 * 1024;
+        const SEND_BUFFER: u32 = 2048;
+
+        // Moves bytes from `src` to `dst`, returning `false` once `src` was closed.
+        fn transfer(
+            src: &TcpStream,
+            dst: &TcpStream,
+            pending: &mut Vec<u8>,
+            buf: &mut [u8],
+        ) -> Result<bool> {
+            let result = if pending.is_empty() {
+                src.try_read(buf).map(|n| {
+                    pending.extend_from_slice(&buf[..n]);
+                    n > 0
+                })
+            } else {
+                dst.try_write(pending).map(|n| {
+                    pending.drain(..n);
+                    true
+                })
+            };
+            match result {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(true),
+                result => result,
+            }
+        }
+
+        // Forwards bytes in both directions, as a `select!` loop over
+        // `readable` and `writable` would. The macro cannot be used within
+        // the crate, so the loop is expanded by hand.
+        async fn proxy(a: TcpStream, b: TcpStream) -> Result<()> {
+            let mut a_to_b = Vec::new();
+            let mut b_to_a = Vec::new();
+            let mut buf = [0u8; 1024];
+            loop {
+                let (forward, backward) = {
+                    let forward = async {
+                        if a_to_b.is_empty() {
+                            a.readable().await
+                        } else {
+                            b.writable().await
+                        }
+                    };
+                    let backward = async {
+                        if b_to_a.is_empty() {
+                            b.readable().await
+                        } else {
+                            a.writable().await
+                        }
+                    };
+                    crate::pin!(forward, backward);
+
+                    poll_fn(|cx| {
+                        let forward = forward.as_mut().poll(cx).is_ready();
+                        let backward = backward.as_mut().poll(cx).is_ready();
+                        if forward || backward {
+                            Poll::Ready((forward, backward))
+                        } else {
+                            Poll::Pending
+                        }
+                    })
+                    .await
+                };
+
+                if forward && !transfer(&a, &b, &mut a_to_b, &mut buf)? {
+                    return Ok(());
+                }
+                if backward && !transfer(&b, &a, &mut b_to_a, &mut buf)? {
+                    return Ok(());
+                }
+            }
+        }
+
+        let (rt, ctx) = testutil::node("readiness_driven_proxy");
+        let guard = rt.enter_context(ctx);
+
+        rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:81").await?;
+            let (stream, _) = listener.accept().await?;
+            let (mut read, mut write) = stream.into_split();
+            crate::io::copy(&mut read, &mut write).await
+        });
+        rt.spawn(async {
+            let socket = TcpSocket::new_v4()?;
+            socket.set_send_buffer_size(SEND_BUFFER)?;
+            socket.bind("127.0.0.1:80".parse().unwrap())?;
+            let listener = socket.listen(1024)?;
+            let (a, _) = listener.accept().await?;
+
+            let socket = TcpSocket::new_v4()?;
+            socket.set_send_buffer_size(SEND_BUFFER)?;
+            let b = socket.connect("127.0.0.1:81".parse().unwrap()).await?;
+            proxy(a, b).await
+        });
+        rt.poll_until_idle();
+
+        let payload = (0..LEN).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let data = payload.clone();
+        let mut client = rt.spawn(async move {
+            let stream = TcpStream::connect("127.0.0.1:80").await?;
+            let (mut read, mut write) = stream.into_split();
+            let writer = crate::spawn(async move { write.write_all(&data).await });
+
+            let mut echoed = vec![0u8; LEN];
+            read.read_exact(&mut echoed).await?;
+            writer.await??;
+            Result::Ok(echoed)
+        });
+
+        let mut rounds = 0;
+        let polls = rt.sim_metrics().task_polls;
+        let echoed = loop {
+            if let Ok(echoed) = rt.block_or_idle_on(&mut client) {
+                break echoed.unwrap().unwrap();
+            }
+
+            rounds += 1;
+            assert!(rounds < 10_000);
+            for intent in rt.yield_intents() {
+                match intent {
+                    IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                    IOIntent::TcpSendPacket(msg, _) => rt.process_tcp_packet(msg).unwrap(),
+                    IOIntent::IoTick(_) => rt.io_tick(),
+                    _ => {}
+                }
+            }
+            rt.advance(Duration::from_millis(1));
+        };
+        assert_eq!(echoed, payload);
+
+        // The proxy parked while waiting for readiness, instead of spinning.
+        let polls = rt.sim_metrics().task_polls - polls;
+        assert!(polls < 6 * rounds);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn drop_closes_connection() {
+        let (rt, ctx) = testutil::node("drop_closes_connection");
+        let guard = rt.enter_context(ctx);
+        let (mut server, mut client) = accept_and_connect(&rt);
+
+        let client = rt.spawn(async move { client.write_all(b"hello").await });
+        rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        let server = rt.spawn(async move {
+            let mut buf = Vec::new();
+            server.read_to_end(&mut buf).await?;
+            Result::Ok(buf)
+        });
+        testutil::route(&rt);
+        let buf = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(buf, b"hello");
+
+        testutil::route(&rt);
+        let counts = IOContext::with_current(|ctx| ctx.open_resource_count());
+        assert_eq!(counts.tcp_streams, 0);
+        assert!(IOContext::with_current(|ctx| ctx.tcp_streams.is_empty()));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn split_halves_share_teardown() {
+        let (rt, ctx) = testutil::node("split_halves_share_teardown");
+        let guard = rt.enter_context(ctx);
+        let (mut server, client) = accept_and_connect(&rt);
+        let key = (client.local_addr().unwrap(), client.peer_addr().unwrap());
+        let registered = move || IOContext::with_current(|ctx| ctx.tcp_streams.contains_key(&key));
+
+        let (read, mut write) = client.into_split();
+        drop(read);
+        assert!(registered());
+
+        let client = rt.spawn(async move {
+            write.write_all(b"hello").await?;
+            Result::Ok(write)
+        });
+        let write = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+
+        let server_read = rt.spawn(async move {
+            let mut buf = [0u8; 5];
+            server.read_exact(&mut buf).await?;
+            Result::Ok((server, buf))
+        });
+        testutil::route(&rt);
+        let (server, buf) = rt.block_or_idle_on(server_read).unwrap().unwrap().unwrap();
+        assert_eq!(&buf, b"hello");
+        assert!(registered());
+
+        drop(write);
+        drop(server);
+        testutil::route(&rt);
+        assert!(!registered());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn reunite_after_reset() {
+        let (rt, ctx) = testutil::node("reunite_after_reset");
+        let guard = rt.enter_context(ctx);
+        let (server, _client) = accept_and_connect(&rt);
+
+        let (read, write) = server.into_split();
+        IOContext::with_current(|ctx| ctx.reset());
+
+        let mut stream = read.reunite(write).unwrap();
+        let result = rt.block_or_idle_on(rt.spawn(async move {
+            let mut buf = [0u8; 8];
+            stream.read(&mut buf).await.map_err(|e| e.kind())
+        }));
+        assert_eq!(result.unwrap().unwrap(), Err(ErrorKind::ConnectionReset));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn reset_wakes_pending_read() {
+        let (rt, ctx) = testutil::node("reset_wakes_pending_read");
+        let guard = rt.enter_context(ctx);
+        let (mut server, _client) = accept_and_connect(&rt);
+
+        let read = rt.spawn(async move {
+            let mut buf = [0u8; 8];
+            let result = server.read(&mut buf).await.map_err(|e| e.kind());
+            (result, server)
+        });
+        rt.poll_until_idle();
+        assert!(!read.is_finished());
+
+        IOContext::with_current(|ctx| ctx.reset());
+        let (result, server) = rt.block_or_idle_on(read).unwrap().unwrap();
+        assert_eq!(result, Err(ErrorKind::ConnectionReset));
+
+        // The reset is forgotten once the stream was dropped.
+        let key = server.inner.key();
+        drop(server);
+        assert!(!IOContext::with_current(|ctx| ctx.tcp_reset.contains(&key)));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn keepalive_detects_dead_peer() {
+        let (rt, ctx) = testutil::node("keepalive_detects_dead_peer");
+        let guard = rt.enter_context(ctx);
+
+        let _server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (mut stream, _) = listener.accept().await?;
+            let mut buf = [0u8; 5];
+            stream.read(&mut buf).await?;
+            Result::Ok(())
+        });
+        rt.poll_until_idle();
+
+        let mut client = rt.spawn(async {
+            let socket = TcpSocket::new_v4()?;
+            socket.set_keepalive(Some(Duration::from_secs(1)))?;
+            socket.set_keepalive_probes(3)?;
+            let mut stream = socket.connect("127.0.0.1:80".parse().unwrap()).await?;
+
+            let mut buf = [0u8; 5];
+            let result = stream.read(&mut buf).await;
+            Result::Ok((result.map_err(|e| e.kind()), SimTime::now()))
+        });
+        testutil::route(&rt);
+
+        // Answered probes keep the connection alive.
+        let mut probes = 0;
+        for _ in 0..3 {
+            rt.advance(Duration::from_secs(1));
+            for _ in 0..2 {
+                rt.poll_until_idle();
+                for intent in rt.yield_intents() {
+                    if let IOIntent::TcpSendPacket(msg, _) = intent {
+                        if msg.keepalive == Some(TcpKeepalive::Probe) {
+                            probes += 1;
+                        }
+                        rt.process_tcp_packet(msg).unwrap();
+                    }
+                }
+            }
+        }
+        assert_eq!(probes, 3);
+        assert!(rt.block_or_idle_on(&mut client).is_err());
+
+        // Once the peer is gone, the stream is reset after the configured probes.
+        let mut probes = 0;
+        for _ in 0..4 {
+            rt.advance(Duration::from_secs(1));
+            rt.poll_until_idle();
+            for intent in rt.yield_intents() {
+                if let IOIntent::TcpSendPacket(msg, _) = intent {
+                    assert_eq!(msg.keepalive, Some(TcpKeepalive::Probe));
+                    probes += 1;
+                }
+            }
+        }
+        assert_eq!(probes, 3);
+
+        let (result, at) = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(result, Err(ErrorKind::ConnectionReset));
+        assert_eq!(at, SimTime::from_duration(Duration::from_secs(7)));
+
+        guard.leave();
+    }
+}
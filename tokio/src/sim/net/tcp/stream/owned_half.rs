@@ -1,8 +1,9 @@
 use super::super::TcpStreamInner;
-use super::TcpStream;
+use super::{poll_stall_timer, poll_with_timeout, TcpStream};
 
-use crate::sim::net::{IOContext, IOInterest, IOInterestGuard, Result};
+use crate::sim::net::{register_interest, IOContext, IOInterest, Result};
 use crate::io::{Ready, ReadBuf, AsyncRead, AsyncWrite, Interest};
+use crate::time::Sleep;
 
 use std::io::{Error, ErrorKind, IoSliceMut, IoSlice};
 use std::sync::Arc;
@@ -17,6 +18,7 @@ use std::net::SocketAddr;
 #[derive(Debug)]
 pub struct OwnedReadHalf {
     pub(super) inner: Arc<TcpStreamInner>,
+    read_timer: Option<Pin<Box<Sleep>>>,
 }
 
 /// Owned read half of a [TcpStream], created by [into_split](super::TcpStream::into_split).
@@ -26,6 +28,9 @@ pub struct OwnedReadHalf {
 #[derive(Debug)]
 pub struct OwnedWriteHalf {
     pub(super) inner: Arc<TcpStreamInner>,
+    write_timer: Option<Pin<Box<Sleep>>>,
+    stall_timer: Option<Pin<Box<Sleep>>>,
+    linger_timer: Option<Pin<Box<Sleep>>>,
 }
 
 /// Error indicating that two halves were
@@ -34,12 +39,16 @@ pub struct OwnedWriteHalf {
 pub struct ReuniteError(pub OwnedReadHalf, pub OwnedWriteHalf);
 
 impl OwnedReadHalf {
+    pub(super) fn new(inner: Arc<TcpStreamInner>) -> OwnedReadHalf {
+        OwnedReadHalf { inner, read_timer: None }
+    }
+
     /// Attempts to put the two halves of a [TcpStream] back together
     /// and recover the original socket.
     /// Succeeds only if the two halves originated from the same call to [into_split](TcpStream::into_split).
     pub fn reunite(self, other: OwnedWriteHalf) -> std::result::Result<TcpStream, ReuniteError> {
         if Arc::ptr_eq(&self.inner, &other.inner) {
-            Ok(TcpStream { inner: self.inner })
+            Ok(TcpStream::from_inner(self.inner))
         } else {
             Err(ReuniteError(self, other))
         }
@@ -56,18 +65,15 @@ impl OwnedReadHalf {
             let interest = IOInterest::TcpRead((self.inner.local_addr, self.inner.peer_addr));
             interest.await?;
 
-            let n = IOContext::with_current(|ctx| {
+            let (n, closed) = IOContext::with_current(|ctx| {
                 if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                    Ok(handle.incoming.peek(buf))
+                    Ok((handle.incoming.peek(buf), handle.is_read_closed()))
                 } else {
-                    Err(Error::new(
-                        ErrorKind::Other,
-                        "Simulation context has dropped TcpStream",
-                    ))
+                    Err(ctx.tcp_stream_error(self.inner.key()))
                 }
             })?;
 
-            if n != 0 { return Ok(n) }
+            if n != 0 || closed { return Ok(n) }
         }
     }
 
@@ -122,17 +128,12 @@ impl OwnedReadHalf {
     pub fn try_read(&self, buf: &mut [u8]) -> Result<usize> {
         IOContext::with_current(|ctx| {
             if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                let n = handle.incoming.read(buf);
-                if n > 0 {
-                    Ok(n)
-                } else {
-                    Err(Error::new(ErrorKind::WouldBlock, "No message could be received non-blocking"))
+                match handle.incoming.read(buf) {
+                    Some(n) => Ok(n),
+                    None => Err(Error::new(ErrorKind::WouldBlock, "No message could be received non-blocking")),
                 }
             } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                ))
+                Err(ctx.tcp_stream_error(self.inner.key()))
             }
         })
     }
@@ -153,13 +154,16 @@ impl OwnedReadHalf {
 }
 
 impl OwnedWriteHalf {
+    pub(super) fn new(inner: Arc<TcpStreamInner>) -> OwnedWriteHalf {
+        OwnedWriteHalf { inner, write_timer: None, stall_timer: None, linger_timer: None }
+    }
 
     /// Attempts to put the two halves of a [TcpStream] back together
     /// and recover the original socket.
     /// Succeeds only if the two halves originated from the same call to [into_split](TcpStream::into_split).
     pub fn reunite(self, other: OwnedReadHalf) -> std::result::Result<TcpStream, ReuniteError> {
         if Arc::ptr_eq(&self.inner, &other.inner) {
-            Ok(TcpStream { inner: self.inner })
+            Ok(TcpStream::from_inner(self.inner))
         } else {
             Err(ReuniteError(other, self))
         }
@@ -208,17 +212,7 @@ impl OwnedWriteHalf {
     /// 
     /// This function is usually paired with writable().
     pub fn try_write(&self, buf: &[u8]) -> Result<usize> {
-        IOContext::with_current(|ctx| {
-            if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                if let Err(rem) = handle.outgoing.write(buf) {
-                    Ok(buf.len() - rem.len())
-                } else {
-                    Ok(buf.len())
-                }
-            } else {
-                Err(Error::new(ErrorKind::Other, "Simulation context has lost TcpStream"))
-            }
-        })
+        IOContext::with_current(|ctx| ctx.tcp_write(self.inner.key(), &[IoSlice::new(buf)]))
     }
 
     
@@ -236,28 +230,25 @@ impl AsyncRead for OwnedReadHalf {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>
     ) -> Poll<Result<()>> {
+        let this = self.get_mut();
+
         // Whenever polled -- try to fill the buffer first
-        IOContext::with_current(|ctx| {
-            if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                let old = buf.remaining();
-                let remaining = handle.incoming.read_buf(buf);
-                if old != remaining {
-                    Poll::Ready(Ok(()))
+        let (poll, timeout) = IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp_streams.get_mut(&(this.inner.local_addr, this.inner.peer_addr)) {
+                if handle.incoming.read_buf(buf).is_some() {
+                    // Reading nothing after the peer closed the stream signals EOF.
+                    (Poll::Ready(Ok(())), None)
                 } else {
-                    let interest = IOInterest::TcpRead((self.inner.local_addr, self.inner.peer_addr));
-                    handle.interests.push(IOInterestGuard {
-                        interest: interest.clone(),
-                        waker: cx.waker().clone(),
-                    });
-                    Poll::Pending
+                    let interest = IOInterest::TcpRead((this.inner.local_addr, this.inner.peer_addr));
+                    register_interest(&mut handle.interests, interest, cx.waker());
+                    (Poll::Pending, handle.config.read_timeout)
                 }
             } else {
-                Poll::Ready(Err(Error::new(
-                    ErrorKind::Other,
-                    "Simulation context has dropped TcpStream",
-                )))
+                (Poll::Ready(Err(ctx.tcp_stream_error(this.inner.key()))), None)
             }
-        })
+        });
+
+        poll_with_timeout(&mut this.read_timer, poll, timeout, cx)
     }
 }
 
@@ -267,23 +258,13 @@ impl AsyncWrite for OwnedWriteHalf {
         cx: &mut Context<'_>,
         buf: &[u8]
     ) -> Poll<Result<usize>> {
-        IOContext::with_current(|ctx| {
-            if let Some(handle) = ctx.tcp_streams.get_mut(&(self.inner.local_addr, self.inner.peer_addr)) {
-                if let Err(rem) = handle.outgoing.write(buf) {
-                    if buf.len() == rem.len() {
-                        // must be exceeded buffer size
-                        ctx.tick_wakeups.push(cx.waker().clone());
-                        Poll::Pending
-                    } else {
-                        Poll::Ready(Ok(buf.len() - rem.len()))
-                    }
-                } else {
-                    Poll::Ready(Ok(buf.len()))
-                }
-            } else {
-                Poll::Ready(Err(Error::new(ErrorKind::Other, "Simulation context has lost TcpStream"))) 
-            }
-        })
+        let this = self.get_mut();
+        let (poll, timeout, stall) = IOContext::with_current(|ctx| {
+            ctx.poll_tcp_write(cx, this.inner.key(), &[IoSlice::new(buf)])
+        });
+
+        poll_stall_timer(&mut this.stall_timer, stall, cx);
+        poll_with_timeout(&mut this.write_timer, poll, timeout, cx)
     }
     fn poll_flush(
         self: Pin<&mut Self>,
@@ -294,9 +275,14 @@ impl AsyncWrite for OwnedWriteHalf {
     }
     fn poll_shutdown(
         self: Pin<&mut Self>,
-        _: &mut Context<'_>
+        cx: &mut Context<'_>
     ) -> Poll<Result<()>> {
-        Poll::Ready(Ok(()))
+        // With linger set, wait until all buffered data was sent.
+        let this = self.get_mut();
+        let (poll, linger) = IOContext::with_current(|ctx| {
+            ctx.poll_tcp_shutdown_write(cx, this.inner.key())
+        });
+        poll_with_timeout(&mut this.linger_timer, poll, linger, cx)
     }
 }
 
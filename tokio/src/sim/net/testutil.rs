@@ -0,0 +1,309 @@
+//! Utilities for writing concise scenario tests.
+//!
+//! These helpers only provide the node-local part of a scenario. Packets
+//! must still be routed by the simulation core, by forwarding the yielded
+//! [`IOIntent`](super::IOIntent)s to the receiving node.
+
+use super::{IOContext, Result, SimNetError, TcpListener, TcpStream, UdpSocket};
+use crate::io::{AsyncReadExt, AsyncWriteExt};
+use crate::task::JoinHandle;
+use crate::time::{Duration, SimTime};
+use std::io::{Error, ErrorKind};
+use std::net::SocketAddr;
+
+#[cfg(test)]
+use super::IOIntent;
+#[cfg(test)]
+use crate::runtime::{Builder, Runtime};
+#[cfg(test)]
+use crate::sim::SimContext;
+#[cfg(test)]
+use std::net::Ipv4Addr;
+
+/// Sends `payload` to `to` and waits for the echo, returning the measured
+/// round trip time in simulation time.
+///
+/// Datagrams from other sources, or with a different content, are discarded
+/// while waiting for the echo.
+pub async fn ping(from: &UdpSocket, to: SocketAddr, payload: &[u8]) -> Result<Duration> {
+    let start = SimTime::now();
+    from.send_to(payload, to).await?;
+
+    let mut buf = vec![0u8; payload.len()];
+    loop {
+        let (n, src) = from.recv_from(&mut buf).await?;
+        if src == to && &buf[..n] == payload {
+            return Ok(SimTime::now() - start);
+        }
+    }
+}
+
+/// Spawns a task that binds a [`UdpSocket`] to `addr` and echos all
+/// received datagrams back to their sender.
+///
+/// The task only completes if the socket fails.
+pub fn spawn_echo_server(addr: SocketAddr) -> JoinHandle<Result<()>> {
+    crate::spawn(async move {
+        let socket = UdpSocket::bind(addr).await?;
+        let mut buf = vec![0u8; u16::MAX as usize];
+        loop {
+            let (n, from) = socket.recv_from(&mut buf).await?;
+            socket.send_to(&buf[..n], from).await?;
+        }
+    })
+}
+
+/// Returns the bytes queued in the receive buffer of `stream`, without
+/// consuming them.
+///
+/// This allows asserting on data that arrived but was not yet read.
+pub fn peek_recv_buffer(stream: &TcpStream) -> Result<Vec<u8>> {
+    IOContext::with_current(|ctx| {
+        ctx.tcp_streams
+            .get(&(stream.inner.local_addr, stream.inner.peer_addr))
+            .map(|handle| handle.incoming.peek_all())
+            .ok_or_else(|| SimNetError::StreamDropped.into())
+    })
+}
+
+/// The end of the header section of a HTTP message.
+const HTTP_HEADER_END: &[u8] = b"\r\n\r\n";
+
+fn find_header_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(HTTP_HEADER_END.len())
+        .position(|w| w == HTTP_HEADER_END)
+        .map(|i| i + HTTP_HEADER_END.len())
+}
+
+/// Requests `path` from the HTTP/1.0 server at `addr`, returning the
+/// body of the response.
+///
+/// The response is read until the server closes the connection, so this
+/// exercises the connection handshake, writes, reads and the shutdown of
+/// a [`TcpStream`] together. Responses without a complete header section,
+/// or with a status other than `200`, fail with [`ErrorKind::InvalidData`].
+pub async fn http_get(addr: SocketAddr, path: &str) -> Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let request = format!("GET {} HTTP/1.0\r\nHost: {}\r\n\r\n", path, addr);
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+
+    let invalid = || Error::new(ErrorKind::InvalidData, "invalid http response");
+    let body = find_header_end(&response).ok_or_else(invalid)?;
+    if !response.starts_with(b"HTTP/1.0 200 ") {
+        return Err(invalid());
+    }
+    Ok(response.split_off(body))
+}
+
+/// Spawns a task that binds a [`TcpListener`] to `addr` and answers all
+/// HTTP/1.0 requests with `body`, closing each connection afterwards.
+///
+/// Requests are served one at a time. The task only completes if the
+/// listener fails.
+pub fn spawn_http_responder(addr: SocketAddr, body: Vec<u8>) -> JoinHandle<Result<()>> {
+    crate::spawn(async move {
+        let listener = TcpListener::bind(addr).await?;
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+
+            // Only the header section is read, requests have no body.
+            let mut request = Vec::new();
+            let mut buf = [0u8; 512];
+            while find_header_end(&request).is_none() {
+                let n = stream.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+
+            let header = format!("HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+            stream.write_all(header.as_bytes()).await?;
+            stream.write_all(&body).await?;
+            AsyncWriteExt::shutdown(&mut stream).await?;
+        }
+    })
+}
+
+/// Creates a runtime and the context of the node `192.168.2.1`, with a
+/// time context named `ident`.
+#[cfg(test)]
+pub(crate) fn node(ident: &str) -> (Runtime, SimContext) {
+    let rt = Builder::new_current_thread().enable_time().build().unwrap();
+    let ctx = SimContext::new([1, 2, 3, 4, 5, 6], Ipv4Addr::new(192, 168, 2, 1))
+        .with_time(String::from(ident));
+    (rt, ctx)
+}
+
+/// Creates a runtime and the context of the node `v4`, for scenarios with
+/// multiple nodes.
+#[cfg(test)]
+pub(crate) fn node_at(v4: Ipv4Addr) -> (Runtime, SimContext) {
+    let rt = Builder::new_current_thread().enable_time().build().unwrap();
+    let ctx = SimContext::new([1, 2, 3, 4, 5, v4.octets()[3]], v4).with_time(v4.to_string());
+    (rt, ctx)
+}
+
+/// Routes the TCP packets of the entered node back to itself, until no
+/// more intents are produced.
+#[cfg(test)]
+pub(crate) fn route(rt: &Runtime) {
+    for _ in 0..64 {
+        rt.poll_until_idle();
+        let intents = rt.yield_intents();
+        if intents.is_empty() {
+            break;
+        }
+
+        for intent in intents {
+            match intent {
+                IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+                IOIntent::TcpSendPacket(msg, _) => rt.process_tcp_packet(msg).unwrap(),
+                IOIntent::IoTick(_) => rt.io_tick(),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Polls a node that is not entered until it is idle, returning the
+/// intents it produced.
+#[cfg(test)]
+pub(crate) fn poll(rt: &Runtime, ctx: SimContext) -> (SimContext, Vec<IOIntent>) {
+    let guard = rt.enter_context(ctx);
+    rt.poll_until_idle();
+    let intents = rt.yield_intents();
+    (guard.leave(), intents)
+}
+
+/// Delivers the packets produced by another node to a node that is not
+/// entered, returning the packets that found no receiver.
+#[cfg(test)]
+pub(crate) fn deliver(
+    rt: &Runtime,
+    ctx: SimContext,
+    intents: Vec<IOIntent>,
+) -> (SimContext, Vec<IOIntent>) {
+    let guard = rt.enter_context(ctx);
+    let mut lost = Vec::new();
+    for intent in intents {
+        match intent {
+            IOIntent::UdpSendPacket(msg) => {
+                let msg = rt.process_udp(msg).err();
+                lost.extend(msg.map(IOIntent::UdpSendPacket));
+            }
+            IOIntent::TcpConnect(msg) => rt.process_tcp_connect(msg).unwrap(),
+            IOIntent::TcpSendPacket(msg, delay) => {
+                let msg = rt.process_tcp_packet(msg).err();
+                lost.extend(msg.map(|msg| IOIntent::TcpSendPacket(msg, delay)));
+            }
+            _ => {}
+        }
+    }
+    rt.poll_until_idle();
+    (guard.leave(), lost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::net::{IOIntent, TcpListener};
+
+    #[test]
+    fn ping_over_loopback() {
+        let (rt, ctx) = node("testutil");
+        let guard = rt.enter_context(ctx);
+
+        let server: SocketAddr = "127.0.0.1:7".parse().unwrap();
+        let _echo = {
+            let _enter = rt.enter();
+            spawn_echo_server(server)
+        };
+        let client = rt.spawn(async move {
+            let socket = UdpSocket::bind("127.0.0.1:0").await?;
+            ping(&socket, server, b"ping").await
+        });
+
+        for _ in 0..4 {
+            rt.poll_until_idle();
+            SimTime::set_now(SimTime::now() + Duration::from_millis(10));
+            for intent in rt.yield_intents() {
+                if let IOIntent::UdpSendPacket(msg) = intent {
+                    rt.process_udp(msg).unwrap();
+                }
+            }
+        }
+
+        let rtt = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(rtt, Duration::from_millis(20));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn peek_recv_buffer_keeps_data() {
+        let (rt, ctx) = node("testutil");
+        let guard = rt.enter_context(ctx);
+
+        let server = rt.spawn(async {
+            let listener = TcpListener::bind("127.0.0.1:80").await?;
+            let (stream, _) = listener.accept().await?;
+            Result::Ok(stream)
+        });
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async {
+            let mut stream = TcpStream::connect("127.0.0.1:80").await?;
+            stream.write_all(b"hello").await?;
+            stream.flush().await?;
+            Result::Ok(stream)
+        });
+
+        route(&rt);
+        let mut client = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        rt.block_on(client.write_all(b" world")).unwrap();
+        route(&rt);
+
+        let mut server = rt.block_or_idle_on(server).unwrap().unwrap().unwrap();
+        assert_eq!(peek_recv_buffer(&server).unwrap(), b"hello world");
+        assert_eq!(peek_recv_buffer(&server).unwrap(), b"hello world");
+
+        let mut buf = [0u8; 7];
+        rt.block_on(server.read_exact(&mut buf)).unwrap();
+        assert_eq!(&buf, b"hello w");
+        assert_eq!(peek_recv_buffer(&server).unwrap(), b"orld");
+
+        guard.leave();
+    }
+
+    #[test]
+    fn http_get_over_loopback() {
+        let (rt, ctx) = node("testutil");
+        let guard = rt.enter_context(ctx);
+
+        let server: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        let body = (0..4096u32).map(|i| i as u8).collect::<Vec<_>>();
+        let _responder = {
+            let _enter = rt.enter();
+            spawn_http_responder(server, body.clone())
+        };
+        rt.poll_until_idle();
+
+        let client = rt.spawn(async move {
+            let first = http_get(server, "/").await?;
+            let second = http_get(server, "/index.html").await?;
+            Result::Ok((first, second))
+        });
+
+        route(&rt);
+
+        let (first, second) = rt.block_or_idle_on(client).unwrap().unwrap().unwrap();
+        assert_eq!(first, body);
+        assert_eq!(second, body);
+
+        guard.leave();
+    }
+}
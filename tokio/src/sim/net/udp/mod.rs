@@ -1,5 +1,6 @@
+use crate::future::poll_fn;
 use crate::io::{ReadBuf, Ready, Interest};
-use super::{addr::*, Result, IOContext, IOInterest, IOInterestGuard};
+use super::{addr::*, Result, IOContext, IOInterest, register_interest, SimNetError};
 use std::io::{Error, ErrorKind};
 use std::net::SocketAddr;
 use std::task::*;
@@ -50,7 +51,7 @@ impl UdpSocket {
         let addrs = to_socket_addrs(addr).await?;
       
         // Get the current context
-        IOContext::with_current(|ctx| {
+        IOContext::with_current_or_err(|ctx| {
             let mut last_err = None;
 
             for addr in addrs {
@@ -90,17 +91,32 @@ impl UdpSocket {
             if let Some(peer) = ctx.udp_peer(self.addr) {
                 Ok(peer)
             } else {
-                return Err(Error::new(ErrorKind::Other, "No Peer"))
+                return Err(SimNetError::NoPeer.into())
             }
         )
     }
 
+    /// Returns the name of the interface this socket is bound to.
+    /// 
+    /// The interface is the one providing the local address of the socket,
+    /// thus the interface that egressing datagrams are send from.
+    /// Fails if no interface provides the address, or no IO Context is bound.
+    pub fn device(&self) -> Result<String> {
+        IOContext::with_current_or_err(|ctx| {
+            ctx.interfaces
+                .iter()
+                .find(|interface| interface.addrs.iter().any(|iaddr| iaddr.matches_ip(self.addr.ip())))
+                .map(|interface| interface.name.clone())
+                .ok_or_else(|| SimNetError::InterfaceNotFound.into())
+        })
+    }
+
     /// Connects the UDP socket setting the default destination for send() and 
     /// limiting packets that are read via recv from the address specified in `addr`.
     pub async fn connect<A: ToSocketAddrs>(&self, addr: A) -> Result<()> {
         let addrs = to_socket_addrs(addr).await?;
 
-        IOContext::with_current(|ctx| {
+        IOContext::with_current_or_err(|ctx| {
             let mut last_err = None;
             for peer in addrs {
                 match ctx.udp_connect(self.addr, peer) {
@@ -111,7 +127,9 @@ impl UdpSocket {
                 }
             }
 
-            Err(last_err.unwrap())
+            Err(last_err.unwrap_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, "could not resolve to any address")
+            }))
         })
     }
 
@@ -153,16 +171,15 @@ impl UdpSocket {
     /// The [connect](UdpSocket::connect) method will connect this socket to a remote address. 
     /// This method will fail if the socket is not connected.
     pub async fn send(&self, buf: &[u8]) -> Result<usize> {
-        IOContext::with_current(|ctx| {
+        poll_fn(|cx| IOContext::with_current(|ctx| {
             let peer = if let Some(peer) = ctx.udp_peer(self.addr) {
                 peer
             } else {
-                return Err(Error::new(ErrorKind::Other, "No Peer"))
+                return Poll::Ready(Err(SimNetError::NoPeer.into()))
             };
 
-            ctx.udp_send(self.addr, peer, Vec::from(buf))?;
-            Ok(buf.len())
-        })
+            ctx.poll_udp_send(cx, self.addr, peer, buf).map_ok(|()| buf.len())
+        })).await
     }
 
     /// DEPRECATED
@@ -181,7 +198,7 @@ impl UdpSocket {
             let peer = if let Some(peer) = ctx.udp_peer(self.addr) {
                 peer
             } else {
-                return Err(Error::new(ErrorKind::Other, "No Peer"))
+                return Err(SimNetError::NoPeer.into())
             };
 
             ctx.udp_send(self.addr, peer, Vec::from(buf))?;
@@ -209,57 +226,73 @@ impl UdpSocket {
 
     /// Receives a single datagram message on the socket from the remote address to 
     /// which it is connected. On success, returns the number of bytes read.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe. If `recv` is used as the event in a
+    /// [`tokio::select!`](crate::select) statement and some other branch
+    /// completes first, it is guaranteed that no messages were received on this
+    /// socket. Datagrams that arrived in the meantime remain queued for the next call.
+    ///
+    /// If a datagram send to the peer was reported as unreachable, the next call
+    /// fails once with [`ErrorKind::ConnectionRefused`].
     pub async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
         let peer = IOContext::with_current(|ctx| 
             if let Some(peer) = ctx.udp_peer(self.addr) {
                 Ok(peer)
             } else {
-                return Err(Error::new(ErrorKind::Other, "No Peer"))
+                return Err(SimNetError::NoPeer)
             }
         )?;
 
-        loop {
-            let interest = IOInterest::UdpRead(self.addr);
-            interest.await?;
-
-            let r = IOContext::with_current(|ctx| {
-                if let Some(handle) = ctx
-                    .udp_sockets
-                    .get_mut(&self.addr)   
-                {
-                    handle.incoming.pop_front()
-                } else {
-                    panic!("SimContext lost socket")
-                }
-            });
-           
-            match r {
-                Some(msg) => {
-                    if msg.src_addr != peer {
-                        continue;
-                    }
-
-                    let wrt = msg.content.len().min(buf.len());
-                    for i in 0..wrt {
-                        buf[i] = msg.content[i];
-                    }
-
-                    return Ok(wrt);
-                }
-                None => {}
-            }
-        }
+        let (n, _, _) = self.recv_cancellable(buf, Some(peer)).await?;
+        Ok(n)
     }
 
-    /// DEPRECATED
-    #[deprecated(note = "Not implemented in simulation context")]
-    #[allow(unused)]
+    /// Attempts to receive a single datagram message on the socket from the remote address
+    /// to which it is connected.
+    ///
+    /// The datagram is written directly into `buf`. If the datagram is larger than the
+    /// remaining capacity of `buf`, excess bytes are discarded. Datagrams from other
+    /// sources than the connected peer are discarded.
+    ///
+    /// Note that on multiple calls to a `poll_*` method in the recv direction, only the
+    /// `Waker` from the `Context` passed to the most recent call will be scheduled to
+    /// receive a wakeup.
     pub fn poll_recv(
         &self,
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>
     ) -> Poll<Result<()>> {
-        unimplemented!()
+        IOContext::with_current(|ctx| {
+            let peer = match ctx.udp_sockets.get(&self.addr) {
+                Some(handle) => handle.state.peer(),
+                None => return Poll::Ready(Err(SimNetError::SocketDropped.into()))
+            };
+            let peer = match peer {
+                Some(peer) => peer,
+                None => return Poll::Ready(Err(SimNetError::NoPeer.into()))
+            };
+
+            let handle = ctx.udp_sockets.get_mut(&self.addr).expect("socket was found");
+            if let Some(err) = handle.error.take() {
+                return Poll::Ready(Err(err.into()))
+            }
+
+            while let Some(msg) = handle.incoming.pop_front() {
+                if msg.src_addr != peer {
+                    continue;
+                }
+
+                let wrt = msg.content.len().min(buf.remaining());
+                buf.put_slice(&msg.content[..wrt]);
+                return Poll::Ready(Ok(()))
+            }
+
+            let interest = IOInterest::UdpRead(self.addr);
+            register_interest(&mut handle.interests, interest, cx.waker());
+            Poll::Pending
+        })
     }
 
     /// Tries to receive a single datagram message on the socket from the remote address to which it is connected. 
@@ -273,16 +306,19 @@ impl UdpSocket {
                 let peer = if let Some(peer) = ctx.udp_peer(self.addr) {
                     peer
                 } else {
-                    return Err(Error::new(ErrorKind::Other, "No Peer"))
+                    return Err(SimNetError::NoPeer)
                 };
 
                 if let Some(handle) = ctx
                     .udp_sockets
                     .get_mut(&self.addr)   
                 {
+                    if let Some(err) = handle.error.take() {
+                        return Err(err)
+                    }
                     Ok((peer, handle.incoming.pop_front()))
                 } else {
-                    panic!("SimContext lost socket")
+                    Err(SimNetError::SocketDropped)
                 }
             })?;
             
@@ -293,9 +329,7 @@ impl UdpSocket {
                     }
 
                     let wrt = msg.content.len().min(buf.len());
-                    for i in 0..wrt {
-                        buf[i] = msg.content[i];
-                    }
+                    buf[..wrt].copy_from_slice(&msg.content[..wrt]);
 
                     return Ok(wrt);
                 }
@@ -306,19 +340,64 @@ impl UdpSocket {
         }
     }
 
+    /// Receives a single datagram from the connected address without removing it from the queue.
+    /// On success, returns the number of bytes read.
+    ///
+    /// Datagrams from other sources than the connected peer are skipped, but remain
+    /// queued for [`recv_from`](UdpSocket::recv_from). Calling `peek` again returns
+    /// the same datagram until it is received.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe, since no datagram is removed from the socket.
+    pub async fn peek(&self, buf: &mut [u8]) -> Result<usize> {
+        let peer = IOContext::with_current(|ctx|
+            if let Some(peer) = ctx.udp_peer(self.addr) {
+                Ok(peer)
+            } else {
+                Err(SimNetError::NoPeer)
+            }
+        )?;
+
+        poll_fn(|cx| IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.udp_sockets.get_mut(&self.addr) {
+                if let Some(err) = handle.error.take() {
+                    return Poll::Ready(Err(err.into()))
+                }
+
+                if let Some(msg) = handle.incoming.iter().find(|msg| msg.src_addr == peer) {
+                    let wrt = msg.content.len().min(buf.len());
+                    buf[..wrt].copy_from_slice(&msg.content[..wrt]);
+                    Poll::Ready(Ok(wrt))
+                } else {
+                    let interest = IOInterest::UdpRead(self.addr);
+                    register_interest(&mut handle.interests, interest, cx.waker());
+                    Poll::Pending
+                }
+            } else {
+                Poll::Ready(Err(SimNetError::SocketDropped.into()))
+            }
+        })).await
+    }
+
     /// Sends data on the socket to the given address. On success, returns the number of bytes written.
     /// 
     /// Address type can be any implementor of [ToSocketAddrs] trait. See its documentation for concrete examples.
     ///
     /// It is possible for `addr` to yield multiple addresses, 
     /// but `send_to` will only send data to the first address yielded by `addr`.
+    /// If `addr` yields no addresses, an error of kind `InvalidInput` is returned.
+    /// Datagrams larger than the [maximum datagram size](IOContext::max_datagram_size)
+    /// of the node are rejected with [`SimNetError::MessageSize`].
     pub async fn send_to(&self, buf: &[u8], target: impl ToSocketAddrs) -> Result<usize> {
-        let addr = to_socket_addrs(target).await;
-        let first = addr.unwrap().next().unwrap();
+        let first = match to_socket_addrs(target).await?.next() {
+            Some(addr) => addr,
+            None => return Err(Error::new(ErrorKind::InvalidInput, "could not resolve to any address")),
+        };
 
-        IOContext::with_current(|ctx| {
-            ctx.udp_send(self.addr, first, Vec::from(buf))
-        })?;
+        poll_fn(|cx| IOContext::with_current(|ctx| {
+            ctx.poll_udp_send(cx, self.addr, first, buf)
+        })).await?;
 
         Ok(buf.len())
     }
@@ -327,11 +406,13 @@ impl UdpSocket {
     /// DIRTY IMPL
     pub fn poll_send_to(
         &self,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &[u8],
         target: SocketAddr
     ) -> Poll<Result<usize>> {
-        Poll::Ready(self.try_send_to(buf, target))
+        IOContext::with_current(|ctx| {
+            ctx.poll_udp_send(cx, self.addr, target, buf).map_ok(|()| buf.len())
+        })
     }
 
     /// Tries to send data on the socket to the given address, 
@@ -351,37 +432,101 @@ impl UdpSocket {
     /// 
     /// The function must be called with valid byte array buf of sufficient size to hold the message bytes. 
     /// If a message is too long to fit in the supplied buffer, excess bytes may be discarded.
+    /// 
+    /// If the socket is connected, datagrams from other sources than the
+    /// connected peer are discarded. If a datagram send to the peer was reported
+    /// as unreachable, the next call fails once with [`ErrorKind::ConnectionRefused`].
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe. If `recv_from` is used as the event in a
+    /// [`tokio::select!`](crate::select) statement and some other branch
+    /// completes first, it is guaranteed that no messages were received on this
+    /// socket. Datagrams that arrived in the meantime remain queued for the next call.
     pub async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
-        loop {
-            let interest = IOInterest::UdpRead(self.addr);
-            interest.await?;
+        let (n, addr, _) = self.recv_from_full(buf).await?;
+        Ok((n, addr))
+    }
 
-            let r = IOContext::with_current(|ctx| {
-                if let Some(handle) = ctx
-                    .udp_sockets
-                    .get_mut(&self.addr)   
-                {
-                    handle.incoming.pop_front()
-                } else {
-                    panic!("SimContext lost socket")
-                }
-            });
+    /// Receives a single datagram message on the socket, reporting whether it was truncated.
+    /// On success, returns the number of bytes read, the origin and whether the datagram
+    /// was longer than `buf`, like `MSG_TRUNC` of `recvmsg`.
+    ///
+    /// In contrast to [recv_from](UdpSocket::recv_from), this allows asserting that a
+    /// datagram fit into the supplied buffer. The excess bytes are discarded either way.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe, with the same guarantees as [recv_from](UdpSocket::recv_from).
+    pub async fn recv_from_full(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr, bool)> {
+        self.recv_cancellable(buf, None).await
+    }
 
-            match r {
-                Some(msg) => {
-                    let wrt = msg.content.len().min(buf.len());
-                    for i in 0..wrt {
-                        buf[i] = msg.content[i];
-                    }
+    /// Receives a single datagram, discarding datagrams from other sources than
+    /// `peer`, or the connected peer if `peer` is `None`.
+    ///
+    /// If the future is dropped while pending, its read interest is removed.
+    async fn recv_cancellable(
+        &self,
+        buf: &mut [u8],
+        peer: Option<SocketAddr>
+    ) -> Result<(usize, SocketAddr, bool)> {
+        let mut guard = CancelOnDrop {
+            interest: IOInterest::UdpRead(self.addr),
+            waker: None,
+        };
 
-                    return Ok((wrt, msg.src_addr));
+        poll_fn(|cx| {
+            let poll = self.poll_recv_full(cx, buf, peer);
+            guard.waker = match poll {
+                Poll::Pending => Some(cx.waker().clone()),
+                Poll::Ready(_) => None,
+            };
+            poll
+        })
+        .await
+    }
+
+    fn poll_recv_full(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+        peer: Option<SocketAddr>
+    ) -> Poll<Result<(usize, SocketAddr, bool)>> {
+        IOContext::with_current(|ctx| {
+            let handle = match ctx.udp_sockets.get_mut(&self.addr) {
+                Some(handle) => handle,
+                None => return Poll::Ready(Err(SimNetError::SocketDropped.into()))
+            };
+            if let Some(err) = handle.error.take() {
+                return Poll::Ready(Err(err.into()))
+            }
+
+            let peer = peer.or_else(|| handle.state.peer());
+            while let Some(msg) = handle.incoming.pop_front() {
+                if peer.map_or(false, |peer| msg.src_addr != peer) {
+                    continue;
                 }
-                None => {return Err(Error::new(ErrorKind::WouldBlock, "Would block"))}
+
+                let wrt = msg.content.len().min(buf.len());
+                buf[..wrt].copy_from_slice(&msg.content[..wrt]);
+                return Poll::Ready(Ok((wrt, msg.src_addr, wrt < msg.content.len())))
             }
-        }
+
+            let interest = IOInterest::UdpRead(self.addr);
+            register_interest(&mut handle.interests, interest, cx.waker());
+            Poll::Pending
+        })
     }
 
-    /// UNSURE IMPL
+    /// Attempts to receive a single datagram on the socket.
+    ///
+    /// The datagram is written directly into `buf`. If the datagram is larger than the
+    /// remaining capacity of `buf`, excess bytes are discarded. On success, returns
+    /// the origin of the datagram.
+    ///
+    /// If the socket is connected, datagrams from other sources than the
+    /// connected peer are discarded.
     pub fn poll_recv_from(
         &self,
         cx: &mut Context<'_>,
@@ -389,26 +534,26 @@ impl UdpSocket {
     ) -> Poll<Result<SocketAddr>> {
         IOContext::with_current(|ctx| {
             if let Some(handle) = ctx.udp_sockets.get_mut(&self.addr) {
-                if let Some(front) = handle.incoming.front_mut() {
-                    let n = buf.remaining().min(front.content.len());
-                    for _ in 0..n {
-                        buf.put_slice(&[front.content.remove(0)])
-                    }
-                    if front.content.is_empty() {
-                        let p = handle.incoming.pop_front().unwrap();
-                        Poll::Ready(Ok(p.src_addr))
-                    } else {
-                        let interest = IOInterest::UdpRead(self.addr);
-                        handle.interests.push(IOInterestGuard { interest, waker: cx.waker().clone() });
-                        Poll::Pending
+                if let Some(err) = handle.error.take() {
+                    return Poll::Ready(Err(err.into()))
+                }
+
+                let peer = handle.state.peer();
+                while let Some(msg) = handle.incoming.pop_front() {
+                    if peer.map_or(false, |peer| msg.src_addr != peer) {
+                        continue;
                     }
-                } else {
-                    let interest = IOInterest::UdpRead(self.addr);
-                    handle.interests.push(IOInterestGuard { interest, waker: cx.waker().clone() });
-                    Poll::Pending
+
+                    let wrt = msg.content.len().min(buf.remaining());
+                    buf.put_slice(&msg.content[..wrt]);
+                    return Poll::Ready(Ok(msg.src_addr))
                 }
+
+                let interest = IOInterest::UdpRead(self.addr);
+                register_interest(&mut handle.interests, interest, cx.waker());
+                Poll::Pending
             } else {
-                Poll::Ready(Err(Error::new(ErrorKind::Other, "SimContext lost UdpSocket")))
+                Poll::Ready(Err(SimNetError::SocketDropped.into()))
             }
         })
     }
@@ -419,29 +564,39 @@ impl UdpSocket {
     /// The function must be called with valid byte array buf of sufficient size 
     /// to hold the message bytes. If a message is too long to fit in the supplied buffer, 
     /// excess bytes may be discarded.
+    /// 
+    /// If the socket is connected, datagrams from other sources than the
+    /// connected peer are discarded.
     pub fn try_recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
         loop {
-            let r = IOContext::with_current(|ctx| {
+            let (peer, r) = IOContext::with_current(|ctx| {
                 if let Some(handle) = ctx
                     .udp_sockets
                     .get_mut(&self.addr)   
                 {
-                    handle.incoming.pop_front()
+                    if let Some(err) = handle.error.take() {
+                        return Err(err)
+                    }
+                    Ok((handle.state.peer(), handle.incoming.pop_front()))
                 } else {
-                    panic!("SimContext lost socket")
+                    Err(SimNetError::SocketDropped)
                 }
-            });
+            })?;
 
             match r {
                 Some(msg) => {
-                    let wrt = msg.content.len().min(buf.len());
-                    for i in 0..wrt {
-                        buf[i] = msg.content[i];
+                    if peer.map_or(false, |peer| msg.src_addr != peer) {
+                        continue;
                     }
 
+                    let wrt = msg.content.len().min(buf.len());
+                    buf[..wrt].copy_from_slice(&msg.content[..wrt]);
+
                     return Ok((wrt, msg.src_addr));
                 }
-                None => {}
+                None => {
+                    return Err(Error::new(ErrorKind::WouldBlock, "Would block"))
+                }
             }
         }
     }
@@ -464,7 +619,7 @@ impl UdpSocket {
         IOContext::with_current(|ctx| {
             match ctx.udp_sockets.get(&self.addr) {
                 Some(ref sock) => Ok(sock.broadcast),
-                None => Err(Error::new(ErrorKind::Other, "SimContext lost socket handle"))
+                None => Err(SimNetError::SocketDropped.into())
             }
         })
     }
@@ -479,7 +634,37 @@ impl UdpSocket {
                     sock.broadcast = on;
                     Ok(())
                 },
-                None => Err(Error::new(ErrorKind::Other, "SimContext lost socket handle"))
+                None => Err(SimNetError::SocketDropped.into())
+            }
+        })
+    }
+
+    /// Gets whether this socket receives broadcast datagrams.
+    ///
+    /// For more information about this option, see [set_broadcast_recv](UdpSocket::set_broadcast_recv).
+    pub fn broadcast_recv(&self) -> Result<bool> {
+        IOContext::with_current(|ctx| {
+            match ctx.udp_sockets.get(&self.addr) {
+                Some(sock) => Ok(sock.broadcast_recv),
+                None => Err(SimNetError::SocketDropped.into())
+            }
+        })
+    }
+
+    /// Sets whether this socket receives broadcast datagrams.
+    ///
+    /// This option is independent of [set_broadcast](UdpSocket::set_broadcast), which
+    /// only controls sending. By default it is enabled, so that broadcasts are delivered
+    /// to all sockets bound to the destination port. When disabled, the socket only
+    /// receives datagrams addressed to it directly.
+    pub fn set_broadcast_recv(&self, on: bool) -> Result<()> {
+        IOContext::with_current(|ctx| {
+            match ctx.udp_sockets.get_mut(&self.addr) {
+                Some(sock) => {
+                    sock.broadcast_recv = on;
+                    Ok(())
+                },
+                None => Err(SimNetError::SocketDropped.into())
             }
         })
     }
@@ -492,7 +677,7 @@ impl UdpSocket {
         IOContext::with_current(|ctx| {
             match ctx.udp_sockets.get(&self.addr) {
                 Some(ref sock) => Ok(sock.ttl),
-                None => Err(Error::new(ErrorKind::Other, "SimContext lost socket handle"))
+                None => Err(SimNetError::SocketDropped.into())
             }
         })
     }
@@ -507,7 +692,37 @@ impl UdpSocket {
                     sock.ttl = ttl;
                     Ok(())
                 },
-                None => Err(Error::new(ErrorKind::Other, "SimContext lost socket handle"))
+                None => Err(SimNetError::SocketDropped.into())
+            }
+        })
+    }
+
+    /// Gets the value of the IP_MULTICAST_TTL option for this socket.
+    ///
+    /// For more information about this option, see [set_multicast_ttl_v4](UdpSocket::set_multicast_ttl_v4).
+    pub fn multicast_ttl_v4(&self) -> Result<u32> {
+        IOContext::with_current(|ctx| {
+            match ctx.udp_sockets.get(&self.addr) {
+                Some(sock) => Ok(sock.multicast_ttl_v4),
+                None => Err(SimNetError::SocketDropped.into())
+            }
+        })
+    }
+
+    /// Sets the value of the IP_MULTICAST_TTL option for this socket.
+    ///
+    /// This value is used as the time-to-live of datagrams sent to an IPv4 multicast
+    /// address, instead of the value set by [set_ttl](UdpSocket::set_ttl). Each
+    /// [`Router`](crate::sim::net::Router) a datagram passes consumes one hop. A value
+    /// of 0 keeps the datagrams on the local node.
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> Result<()> {
+        IOContext::with_current(|ctx| {
+            match ctx.udp_sockets.get_mut(&self.addr) {
+                Some(sock) => {
+                    sock.multicast_ttl_v4 = ttl;
+                    Ok(())
+                },
+                None => Err(SimNetError::SocketDropped.into())
             }
         })
     }
@@ -519,4 +734,609 @@ impl Drop for UdpSocket {
             ctx.udp_drop(self.addr)
         });
     }
-}
\ No newline at end of file
+}
+
+/// Removes the interest of a pending receive, once the receive is dropped.
+struct CancelOnDrop {
+    interest: IOInterest,
+    waker: Option<Waker>,
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            self.interest.cancel(&waker);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sim::net::{testutil, IOIntent, UdpMessage};
+    use crate::sim::SimContext;
+    use crate::time::{timeout, Duration, SimTime};
+    use std::future::Future;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    #[test]
+    fn device_of_bound_socket() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let en0 = UdpSocket::bind("192.168.2.1:0").await.unwrap();
+            let lo0 = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            (en0.device().unwrap(), lo0.device().unwrap(), en0)
+        });
+
+        let (en0, lo0, socket) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(en0, "en0");
+        assert_eq!(lo0, "lo0");
+
+        guard.leave();
+        assert!(socket.device().is_err());
+    }
+
+    #[test]
+    fn connected_recv_from_filters_peer() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let peer: SocketAddr = "192.168.2.2:200".parse().unwrap();
+        let handle = rt.spawn(async move {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.connect(peer).await.unwrap();
+
+            let mut buf = [0u8; 8];
+            let (n, from) = socket.recv_from(&mut buf).await.unwrap();
+            let next = socket.try_recv_from(&mut buf).map_err(|e| e.kind());
+            (buf[..n].to_vec(), from, next)
+        });
+        rt.poll_until_idle();
+
+        let sources = [
+            (1, "192.168.2.3:200"),
+            (2, "192.168.2.2:200"),
+            (3, "192.168.2.3:200"),
+        ];
+        for (content, src_addr) in sources {
+            rt.process_udp(UdpMessage {
+                content: vec![content],
+                src_addr: src_addr.parse().unwrap(),
+                dest_addr: "192.168.2.1:100".parse().unwrap(),
+                ttl: 64,
+            })
+            .unwrap();
+        }
+
+        let (content, from, next) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(content, vec![2]);
+        assert_eq!(from, peer);
+        assert_eq!(next, Err(ErrorKind::WouldBlock));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn recv_from_is_cancel_safe() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("0.0.0.0:100").await.unwrap();
+            let mut buf = [0u8; 8];
+            let mut received = Vec::new();
+            let mut cancelled = 0;
+            while received.len() < 5 {
+                // Races the receive against a sleep, dropping it on timeout.
+                match timeout(Duration::from_millis(1), socket.recv_from(&mut buf)).await {
+                    Ok(r) => received.push(buf[..r.unwrap().0].to_vec()),
+                    Err(_) => {
+                        cancelled += 1;
+                        // The dropped receive must not leave its interest behind.
+                        let interests = IOContext::with_current(|ctx| {
+                            ctx.udp_sockets[&socket.addr].interests.len()
+                        });
+                        assert_eq!(interests, 0);
+                    }
+                }
+            }
+            (received, cancelled)
+        });
+
+        let mut sent = 0u8;
+        for step in 0..50 {
+            rt.poll_until_idle();
+            if step % 3 == 0 && sent < 5 {
+                rt.process_udp(UdpMessage {
+                    content: vec![sent],
+                    src_addr: "192.168.2.2:200".parse().unwrap(),
+                    dest_addr: "192.168.2.1:100".parse().unwrap(),
+                    ttl: 64,
+                })
+                .unwrap();
+                sent += 1;
+            }
+            SimTime::set_now(SimTime::now() + Duration::from_millis(1));
+            SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().process_now());
+        }
+
+        let (received, cancelled) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(received, (0..5).map(|i| vec![i]).collect::<Vec<_>>());
+        assert!(cancelled > 0);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn peek_connected_peer_twice() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            let mut buf = [0u8; 8];
+            let err = socket.peek(&mut buf).await.unwrap_err();
+            assert_eq!(SimNetError::from_io(&err), Some(SimNetError::NoPeer));
+
+            socket.connect("192.168.2.2:200").await.unwrap();
+            let n = socket.peek(&mut buf).await.unwrap();
+            let first = buf[..n].to_vec();
+            let n = socket.peek(&mut buf).await.unwrap();
+            let second = buf[..n].to_vec();
+
+            let queued = IOContext::with_current(|ctx| {
+                ctx.udp_sockets
+                    .get(&socket.local_addr().unwrap())
+                    .map(|handle| handle.incoming.len())
+            });
+            (first, second, queued)
+        });
+        rt.poll_until_idle();
+
+        for (content, src_addr) in [(1, "192.168.2.3:200"), (2, "192.168.2.2:200")] {
+            rt.process_udp(UdpMessage {
+                content: vec![content; 3],
+                src_addr: src_addr.parse().unwrap(),
+                dest_addr: "192.168.2.1:100".parse().unwrap(),
+                ttl: 64,
+            })
+            .unwrap();
+        }
+
+        let (first, second, queued) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(first, vec![2; 3]);
+        assert_eq!(second, vec![2; 3]);
+        assert_eq!(queued, Some(2));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn broadcast_recv_disabled() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let listening = UdpSocket::bind("192.168.2.1:67").await.unwrap();
+            let ignoring = UdpSocket::bind("127.0.0.1:67").await.unwrap();
+            assert!(ignoring.broadcast_recv().unwrap());
+            ignoring.set_broadcast_recv(false).unwrap();
+
+            let mut buf = [0u8; 8];
+            let (n, from) = listening.recv_from(&mut buf).await.unwrap();
+            let ignored = ignoring.try_recv_from(&mut buf).map_err(|e| e.kind());
+            (buf[..n].to_vec(), from, ignored)
+        });
+        rt.poll_until_idle();
+
+        rt.process_udp(UdpMessage {
+            content: vec![1, 2, 3],
+            src_addr: "192.168.2.2:68".parse().unwrap(),
+            dest_addr: "255.255.255.255:67".parse().unwrap(),
+            ttl: 64,
+        })
+        .unwrap();
+
+        let (content, from, ignored) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(content, vec![1, 2, 3]);
+        assert_eq!(from, "192.168.2.2:68".parse().unwrap());
+        assert_eq!(ignored, Err(ErrorKind::WouldBlock));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn send_on_downed_interface() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async { UdpSocket::bind("192.168.2.1:100").await.unwrap() });
+        let socket = rt.block_or_idle_on(handle).unwrap().unwrap();
+        IOContext::with_current(|ctx| ctx.set_interface_up("en0", false)).unwrap();
+
+        let handle = rt.spawn(async move {
+            let down = socket.send_to(b"lost", "192.168.2.2:100").await;
+            IOContext::with_current(|ctx| ctx.set_interface_up("en0", true)).unwrap();
+            let up = socket.send_to(b"sent", "192.168.2.2:100").await;
+            (down.map_err(|e| SimNetError::from_io(&e)), up.is_ok())
+        });
+        let (down, up) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(down, Err(Some(SimNetError::NetworkUnreachable)));
+        assert!(up);
+        assert_eq!(rt.yield_intents().len(), 1);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn unreachable_peer_refuses_recv() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.connect("192.168.2.1:101").await.unwrap();
+            socket.send(b"ping").await.unwrap();
+
+            let mut buf = [0u8; 8];
+            let err = socket.recv(&mut buf).await.unwrap_err();
+            let next = socket.try_recv(&mut buf).map_err(|e| e.kind());
+            (err.kind(), next)
+        });
+        rt.poll_until_idle();
+
+        for intent in rt.yield_intents() {
+            if let IOIntent::UdpSendPacket(msg) = intent {
+                let msg = rt.process_udp(msg).unwrap_err();
+                rt.process_udp_unreachable(msg).unwrap();
+            }
+        }
+
+        let (err, next) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(err, ErrorKind::ConnectionRefused);
+        assert_eq!(next, Err(ErrorKind::WouldBlock));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn unreachable_peer_refuses_recv_from() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.connect("192.168.2.1:101").await.unwrap();
+            socket.send(b"ping").await.unwrap();
+
+            let mut buf = [0u8; 8];
+            let err = socket.recv_from(&mut buf).await.unwrap_err();
+            let next = socket.try_recv_from(&mut buf).map_err(|e| e.kind());
+            (err.kind(), next)
+        });
+        rt.poll_until_idle();
+
+        for intent in rt.yield_intents() {
+            if let IOIntent::UdpSendPacket(msg) = intent {
+                let msg = rt.process_udp(msg).unwrap_err();
+                rt.process_udp_unreachable(msg).unwrap();
+            }
+        }
+
+        let (err, next) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(err, ErrorKind::ConnectionRefused);
+        assert_eq!(next, Err(ErrorKind::WouldBlock));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn repeated_polls_register_once() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.connect("192.168.2.1:101").await.unwrap();
+
+            let mut buf = [0u8; 8];
+            let mut peek = Box::pin(socket.peek(&mut buf));
+            crate::future::poll_fn(|cx| {
+                let mut storage = [0u8; 8];
+                let mut read = ReadBuf::new(&mut storage);
+                for _ in 0..3 {
+                    assert!(peek.as_mut().poll(cx).is_pending());
+                    assert!(socket.poll_recv(cx, &mut read).is_pending());
+                    assert!(socket.poll_recv_from(cx, &mut read).is_pending());
+                }
+                Poll::Ready(())
+            })
+            .await;
+
+            IOContext::with_current(|ctx| ctx.udp_sockets[&socket.addr].interests.len())
+        });
+
+        assert_eq!(rt.block_or_idle_on(handle).unwrap().unwrap(), 1);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn recv_large_datagram() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let peer: SocketAddr = "192.168.2.2:200".parse().unwrap();
+        let handle = rt.spawn(async move {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.connect(peer).await.unwrap();
+
+            let mut buf = vec![0u8; 64 * 1024];
+            let n = socket.recv(&mut buf).await.unwrap();
+            let first = buf[..n].to_vec();
+
+            let mut buf = vec![0u8; 64 * 1024];
+            let mut read_buf = ReadBuf::new(&mut buf);
+            poll_fn(|cx| socket.poll_recv(cx, &mut read_buf))
+                .await
+                .unwrap();
+            let second = read_buf.filled().to_vec();
+
+            let mut buf = vec![0u8; 1024];
+            let mut read_buf = ReadBuf::new(&mut buf);
+            let from = poll_fn(|cx| socket.poll_recv_from(cx, &mut read_buf))
+                .await
+                .unwrap();
+            let truncated = read_buf.filled().to_vec();
+
+            (first, second, truncated, from)
+        });
+        rt.poll_until_idle();
+
+        let content = (0..60 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        for _ in 0..3 {
+            rt.process_udp(UdpMessage {
+                content: content.clone(),
+                src_addr: peer,
+                dest_addr: "192.168.2.1:100".parse().unwrap(),
+                ttl: 64,
+            })
+            .unwrap();
+        }
+
+        let (first, second, truncated, from) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(first, content);
+        assert_eq!(second, content);
+        assert_eq!(truncated, &content[..1024]);
+        assert_eq!(from, peer);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn tx_queue_limit_applies_backpressure() {
+        let (rt, ctx) = testutil::node("tx_queue_limit");
+        let guard = rt.enter_context(ctx);
+        IOContext::with_current(|ctx| ctx.set_tx_queue_limit(Some(4)));
+
+        let peer: SocketAddr = "192.168.2.2:100".parse().unwrap();
+        let socket = rt.spawn(async move {
+            let socket = UdpSocket::bind("0.0.0.0:100").await.unwrap();
+            let results = (0..6)
+                .map(|_| socket.try_send_to(b"flood", peer).map_err(|e| e.kind()))
+                .collect::<Vec<_>>();
+            (socket, results)
+        });
+        let (socket, results) = rt.block_or_idle_on(socket).unwrap().unwrap();
+        assert_eq!(results[..4], [Ok(5), Ok(5), Ok(5), Ok(5)]);
+        assert_eq!(
+            results[4..],
+            [Err(ErrorKind::WouldBlock), Err(ErrorKind::WouldBlock)]
+        );
+        assert_eq!(rt.yield_intents().len(), 4);
+
+        // Asynchronous sends wait until the queue was drained.
+        let sender = rt.spawn(async move {
+            for _ in 0..10 {
+                socket.send_to(b"flood", peer).await.unwrap();
+            }
+        });
+        let mut yielded = Vec::new();
+        while !sender.is_finished() {
+            rt.poll_until_idle();
+            yielded.push(rt.yield_intents().len());
+        }
+        assert_eq!(yielded, vec![4, 4, 2]);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn broadcast_shares_large_payload() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let netmask = Ipv4Addr::new(255, 255, 0, 0);
+        let io = (0..100u8).fold(IOContext::builder().mac([1, 2, 3, 4, 5, 6]), |b, i| {
+            b.ipv4(Ipv4Addr::new(10, 0, 1, i), netmask)
+        });
+        let mut ctx = SimContext::empty().with_time(String::from("broadcast"));
+        ctx.io = Some(io.build());
+        let guard = rt.enter_context(ctx);
+
+        let sockets = rt.spawn(async {
+            let mut sockets = Vec::new();
+            for i in 0..100u8 {
+                let addr = SocketAddr::from((Ipv4Addr::new(10, 0, 1, i), 9000));
+                sockets.push(UdpSocket::bind(addr).await.unwrap());
+            }
+            sockets
+        });
+        let sockets = rt.block_or_idle_on(sockets).unwrap().unwrap();
+
+        let payload = vec![0xab; 1024 * 1024];
+        let msg = UdpMessage::new(
+            "10.0.2.1:9000".parse().unwrap(),
+            "255.255.255.255:9000".parse().unwrap(),
+            payload.clone(),
+        );
+        rt.process_udp(msg).unwrap();
+
+        // All sockets share the content of the broadcast.
+        let contents = IOContext::with_current(|ctx| {
+            sockets
+                .iter()
+                .map(|socket| ctx.udp_sockets[&socket.addr].incoming[0].content.clone())
+                .collect::<Vec<_>>()
+        });
+        assert!(contents.iter().all(|c| Arc::ptr_eq(c, &contents[0])));
+        assert_eq!(Arc::strong_count(&contents[0]), 200);
+        drop(contents);
+
+        let mut buf = vec![0u8; payload.len()];
+        for socket in &sockets {
+            let (n, _) = socket.try_recv_from(&mut buf).unwrap();
+            assert_eq!(n, payload.len());
+        }
+        assert_eq!(buf, payload);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn send_to_empty_resolution() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            let targets: &[SocketAddr] = &[];
+            let send = socket.send_to(b"lost", targets).await.map_err(|e| e.kind());
+            let connect = socket.connect(targets).await.map_err(|e| e.kind());
+            (send, connect)
+        });
+        let (send, connect) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(send, Err(ErrorKind::InvalidInput));
+        assert_eq!(connect, Err(ErrorKind::InvalidInput));
+        assert!(rt.yield_intents().is_empty());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn send_to_hostname() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+        IOContext::with_current(|ctx| ctx.add_host("server", Ipv4Addr::new(192, 168, 2, 2).into()));
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            socket.send_to(b"ping", "server:200").await.unwrap();
+            socket.send_to(b"pong", ("server", 201)).await.unwrap()
+        });
+        assert_eq!(rt.block_or_idle_on(handle).unwrap().unwrap(), 4);
+
+        let targets = rt
+            .yield_intents()
+            .into_iter()
+            .filter_map(|intent| match intent {
+                IOIntent::UdpSendPacket(msg) => Some(msg.dest_addr),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                "192.168.2.2:200".parse::<SocketAddr>().unwrap(),
+                "192.168.2.2:201".parse().unwrap()
+            ]
+        );
+
+        guard.leave();
+    }
+
+    #[test]
+    fn oversized_datagram_rejected() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            let payload = vec![0u8; 70 * 1024];
+            let err = socket
+                .send_to(&payload, "192.168.2.2:100")
+                .await
+                .unwrap_err();
+
+            IOContext::with_current(|ctx| ctx.set_max_datagram_size(1024));
+            let sent = socket.send_to(&payload[..1024], "192.168.2.2:100").await;
+            let small = socket.try_send_to(&payload[..1025], "192.168.2.2:100".parse().unwrap());
+            (
+                SimNetError::from_io(&err),
+                sent.unwrap(),
+                small.map_err(|e| SimNetError::from_io(&e)),
+            )
+        });
+        let (err, sent, small) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(err, Some(SimNetError::MessageSize));
+        assert_eq!(sent, 1024);
+        assert_eq!(small, Err(Some(SimNetError::MessageSize)));
+        assert_eq!(rt.yield_intents().len(), 1);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn blocked_interests_list_pending_read() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let addr: SocketAddr = "192.168.2.1:100".parse().unwrap();
+        let handle = rt.spawn(async move {
+            let socket = UdpSocket::bind(addr).await.unwrap();
+            let mut buf = [0u8; 16];
+            socket.recv_from(&mut buf).await.unwrap()
+        });
+        rt.poll_until_idle();
+        assert!(rt.is_quiescent());
+
+        let blocked = IOContext::with_current(|ctx| ctx.blocked_interests());
+        assert_eq!(blocked, vec![(addr, IOInterest::UdpRead(addr))]);
+
+        handle.abort();
+        rt.poll_until_idle();
+        assert!(IOContext::with_current(|ctx| ctx.blocked_interests()).is_empty());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn recv_from_full_reports_truncation() {
+        let (rt, ctx) = testutil::node("udp");
+        let guard = rt.enter_context(ctx);
+
+        let handle = rt.spawn(async {
+            let socket = UdpSocket::bind("192.168.2.1:100").await.unwrap();
+            let mut buf = [0u8; 10];
+            let long = socket.recv_from_full(&mut buf).await.unwrap();
+            let short = socket.recv_from_full(&mut buf).await.unwrap();
+            (long, short, buf)
+        });
+        rt.poll_until_idle();
+
+        let from: SocketAddr = "192.168.2.2:200".parse().unwrap();
+        for content in [vec![7u8; 20], vec![1u8; 10]].iter() {
+            let msg = UdpMessage::new(from, "192.168.2.1:100".parse().unwrap(), content.clone());
+            rt.process_udp(msg).unwrap();
+        }
+
+        let (long, short, buf) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(long, (10, from, true));
+        assert_eq!(short, (10, from, false));
+        assert_eq!(buf, [1u8; 10]);
+
+        guard.leave();
+    }
+}
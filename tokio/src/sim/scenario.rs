@@ -0,0 +1,200 @@
+use super::time::SimTime;
+use super::{SimContext, SimScheduler};
+use crate::runtime::Runtime;
+use crate::task::JoinHandle;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::panic;
+use std::rc::Rc;
+
+/// A declarative description of a simulation, built from timed steps.
+///
+/// Each step is scheduled with [`at`](Scenario::at), either spawning a task
+/// or asserting on the current [`SimContext`]. Running the scenario drives
+/// the runtime with a [`SimScheduler`], so steps and timers are processed
+/// in simulation time order. Steps scheduled for the same point in time
+/// run in the order they were added.
+///
+/// The [`SimContext`] must be entered on the runtime before running the scenario.
+///
+/// ```
+/// use tokio::sim::{Scenario, SimContext};
+/// use tokio::time::SimTime;
+/// # use std::net::Ipv4Addr;
+///
+/// # async fn server() {}
+/// # async fn client() {}
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// # let ctx = SimContext::new([1, 2, 3, 4, 5, 6], Ipv4Addr::new(192, 168, 2, 1))
+/// #     .with_time(String::from("scenario"));
+/// let guard = rt.enter_context(ctx);
+///
+/// Scenario::new(&rt)
+///     .at(SimTime::ZERO)
+///     .spawn(server())
+///     .at(SimTime::from_millis(100))
+///     .spawn(client())
+///     .at(SimTime::from_millis(200))
+///     .assert(|ctx| assert!(ctx.io.is_some()))
+///     .run();
+///
+/// guard.leave();
+/// ```
+pub struct Scenario<'a> {
+    rt: &'a Runtime,
+    scheduler: SimScheduler<'a>,
+    tasks: Rc<RefCell<Vec<JoinHandle<()>>>>,
+    end: Option<SimTime>,
+}
+
+/// A step of a [`Scenario`] at a fixed point in time.
+///
+/// Created by [`Scenario::at`].
+#[derive(Debug)]
+pub struct ScenarioStep<'a> {
+    scenario: Scenario<'a>,
+    at: SimTime,
+}
+
+impl<'a> Scenario<'a> {
+    /// Creates a new scenario without any steps, driving `rt`.
+    pub fn new(rt: &'a Runtime) -> Self {
+        Self {
+            rt,
+            scheduler: SimScheduler::new(rt),
+            tasks: Rc::new(RefCell::new(Vec::new())),
+            end: None,
+        }
+    }
+
+    /// Starts a new step at `at`.
+    pub fn at(self, at: SimTime) -> ScenarioStep<'a> {
+        ScenarioStep { scenario: self, at }
+    }
+
+    /// Ends the scenario at `end`.
+    ///
+    /// The simulation time is advanced to `end`, even if all steps and
+    /// timers completed earlier. Steps and timers after `end` are not run.
+    pub fn advance_to(mut self, end: SimTime) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Runs all steps of the scenario.
+    ///
+    /// Without an end set by [`advance_to`](Scenario::advance_to), the
+    /// scenario runs until no steps or timers remain. If a spawned task
+    /// panicked, the panic is propagated to the caller.
+    pub fn run(mut self) {
+        match self.end {
+            Some(end) => self.scheduler.run_until(end),
+            None => self.scheduler.run(),
+        }
+
+        let tasks = self.tasks.borrow_mut().split_off(0);
+        for task in tasks {
+            // Tasks that are still pending are detached.
+            if let Ok(Err(e)) = self.rt.block_or_idle_on(task) {
+                if e.is_panic() {
+                    panic::resume_unwind(e.into_panic());
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Scenario<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scenario")
+            .field("scheduler", &self.scheduler)
+            .field("tasks", &self.tasks.borrow().len())
+            .field("end", &self.end)
+            .finish()
+    }
+}
+
+impl<'a> ScenarioStep<'a> {
+    /// Spawns `task` onto the runtime.
+    pub fn spawn<F>(mut self, task: F) -> Scenario<'a>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let tasks = self.scenario.tasks.clone();
+        self.scenario.scheduler.schedule(self.at, move |rt| {
+            let handle = rt.spawn(async move {
+                task.await;
+            });
+            tasks.borrow_mut().push(handle);
+        });
+        self.scenario
+    }
+
+    /// Invokes `f` with the current [`SimContext`].
+    ///
+    /// The runtime is polled until idle before the assertion, so all tasks
+    /// woken by earlier steps have made progress.
+    pub fn assert(mut self, f: impl FnOnce(&mut SimContext) + 'a) -> Scenario<'a> {
+        self.scenario.scheduler.schedule(self.at, move |rt| {
+            rt.poll_until_idle();
+            SimContext::with_current(f);
+        });
+        self.scenario
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sync::{mpsc, oneshot};
+    use crate::time::{sleep, Duration};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn request_response() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("scenario")));
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<(u32, oneshot::Sender<u32>)>();
+        let responses = Arc::new(Mutex::new(Vec::new()));
+        let client_responses = responses.clone();
+
+        Scenario::new(&rt)
+            .at(SimTime::ZERO)
+            .spawn(async move {
+                while let Some((req, reply)) = rx.recv().await {
+                    sleep(Duration::from_millis(10)).await;
+                    reply.send(req + 1).unwrap();
+                }
+            })
+            .at(SimTime::from_millis(100))
+            .spawn(async move {
+                let (reply, response) = oneshot::channel();
+                tx.send((41, reply)).unwrap();
+                let response = response.await.unwrap();
+                client_responses
+                    .lock()
+                    .unwrap()
+                    .push((response, SimTime::now()));
+            })
+            .at(SimTime::from_millis(105))
+            .assert(|_| assert!(responses.lock().unwrap().is_empty()))
+            .at(SimTime::from_millis(110))
+            .assert(|ctx| {
+                let pending = ctx.time.as_ref().unwrap().next_time_poll();
+                assert_eq!(pending, None);
+                assert_eq!(
+                    *responses.lock().unwrap(),
+                    vec![(42, SimTime::from_millis(110))]
+                );
+            })
+            .advance_to(SimTime::from_millis(500))
+            .run();
+
+        assert_eq!(SimTime::now(), SimTime::from_millis(500));
+        guard.leave();
+    }
+}
@@ -0,0 +1,228 @@
+use super::time::SimTime;
+use super::SimContext;
+use crate::runtime::Runtime;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+/// A driver for a simulation on a single runtime.
+///
+/// The scheduler owns a queue of timed events. Running the scheduler advances
+/// the simulation time from event to event, firing all timers of the current
+/// [`SimContext`] in between, and polls the runtime until idle after each step.
+/// Pending timers are treated like scheduled events, so the simulation keeps
+/// running as long as either events or timers remain.
+///
+/// The [`SimContext`] must be entered on the runtime before running the scheduler.
+///
+/// ```
+/// use tokio::sim::{SimContext, SimScheduler};
+/// use tokio::time::SimTime;
+///
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// # let ctx = SimContext::empty().with_time(String::from("scheduler"));
+/// let guard = rt.enter_context(ctx);
+///
+/// let mut scheduler = SimScheduler::new(&rt);
+/// scheduler.schedule(SimTime::from_millis(100), |rt| {
+///     rt.spawn(async { /* ... */ });
+/// });
+/// scheduler.run();
+///
+/// guard.leave();
+/// ```
+pub struct SimScheduler<'a> {
+    rt: &'a Runtime,
+    events: BinaryHeap<Event<'a>>,
+    seq: usize,
+}
+
+impl<'a> SimScheduler<'a> {
+    /// Creates a new scheduler without any events, driving `rt`.
+    pub fn new(rt: &'a Runtime) -> Self {
+        Self {
+            rt,
+            events: BinaryHeap::new(),
+            seq: 0,
+        }
+    }
+
+    /// Schedules `f` to be invoked at `at`.
+    ///
+    /// Events scheduled for the same point in time are invoked in the
+    /// order they were scheduled. Events in the past are invoked on the
+    /// next step, without rewinding the simulation time.
+    pub fn schedule(&mut self, at: SimTime, f: impl FnOnce(&Runtime) + 'a) {
+        self.events.push(Event {
+            at,
+            seq: self.seq,
+            f: Box::new(f),
+        });
+        self.seq += 1;
+    }
+
+    /// Returns the number of scheduled events that were not yet invoked.
+    pub fn pending(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Runs the simulation until no events or timers remain.
+    pub fn run(&mut self) {
+        self.run_inner(SimTime::MAX)
+    }
+
+    /// Runs the simulation until no events or timers remain before `deadline`,
+    /// advancing the simulation time to `deadline` afterwards.
+    ///
+    /// Events and timers after the deadline remain pending.
+    pub fn run_until(&mut self, deadline: SimTime) {
+        self.run_inner(deadline);
+        if SimTime::now() < deadline {
+            self.rt.advance_to(deadline);
+        }
+    }
+
+    fn run_inner(&mut self, deadline: SimTime) {
+        loop {
+            self.rt.poll_until_idle();
+
+            let next_timer = SimContext::with_current(|ctx| {
+                ctx.time.as_ref().and_then(|time| time.next_time_poll())
+            });
+            let next_event = self.events.peek().map(|event| event.at);
+            let next = match (next_event, next_timer) {
+                (Some(event), Some(timer)) => event.min(timer),
+                (event, timer) => match event.or(timer) {
+                    Some(next) => next,
+                    None => return,
+                },
+            };
+
+            if next > deadline {
+                return;
+            }
+
+            self.rt.advance_to(next.max(SimTime::now()));
+            while self
+                .events
+                .peek()
+                .map_or(false, |event| event.at <= SimTime::now())
+            {
+                let event = self.events.pop().expect("event was peeked");
+                (event.f)(self.rt);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SimScheduler<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimScheduler")
+            .field("rt", &self.rt)
+            .field("events", &self.events.len())
+            .finish()
+    }
+}
+
+struct Event<'a> {
+    at: SimTime,
+    seq: usize,
+    f: Box<dyn FnOnce(&Runtime) + 'a>,
+}
+
+// BinaryHeap is a max-heap, so the order is reversed to pop the
+// earliest event first.
+impl Ord for Event<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.at.cmp(&self.at).then(other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Event<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Event<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.at == other.at && self.seq == other.seq
+    }
+}
+
+impl Eq for Event<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sync::mpsc;
+    use crate::time::{sleep, Duration};
+    use std::cell::RefCell;
+
+    #[test]
+    fn channel_pipeline() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("scheduler")));
+
+        let (up_tx, mut up_rx) = mpsc::unbounded_channel::<usize>();
+        let (down_tx, mut down_rx) = mpsc::unbounded_channel();
+        rt.spawn(async move {
+            while let Some(v) = up_rx.recv().await {
+                sleep(Duration::from_millis(10)).await;
+                down_tx.send((v * 2, SimTime::now())).unwrap();
+            }
+        });
+
+        let mut scheduler = SimScheduler::new(&rt);
+        for (at, v) in [(100, 1), (200, 2), (205, 3)] {
+            let up_tx = up_tx.clone();
+            scheduler.schedule(SimTime::from_millis(at), move |_| up_tx.send(v).unwrap());
+        }
+        scheduler.run();
+
+        assert_eq!(scheduler.pending(), 0);
+        assert_eq!(
+            rt.drain(&mut down_rx),
+            vec![
+                (2, SimTime::from_millis(110)),
+                (4, SimTime::from_millis(210)),
+                (6, SimTime::from_millis(220)),
+            ]
+        );
+
+        guard.leave();
+    }
+
+    #[test]
+    fn run_until_keeps_later_events() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("scheduler")));
+
+        let fired = RefCell::new(Vec::new());
+        let mut scheduler = SimScheduler::new(&rt);
+        for (at, id) in [(300, 0), (100, 1), (200, 2), (100, 3)] {
+            let fired = &fired;
+            scheduler.schedule(SimTime::from_millis(at), move |_| {
+                fired.borrow_mut().push((id, SimTime::now()))
+            });
+        }
+
+        scheduler.run_until(SimTime::from_millis(250));
+        assert_eq!(scheduler.pending(), 1);
+        assert_eq!(SimTime::now(), SimTime::from_millis(250));
+        assert_eq!(
+            *fired.borrow(),
+            vec![
+                (1, SimTime::from_millis(100)),
+                (3, SimTime::from_millis(100)),
+                (2, SimTime::from_millis(200)),
+            ]
+        );
+
+        scheduler.run();
+        assert_eq!(fired.borrow().last(), Some(&(0, SimTime::from_millis(300))));
+
+        guard.leave();
+    }
+}
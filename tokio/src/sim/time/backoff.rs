@@ -0,0 +1,128 @@
+use super::{sleep, Duration};
+use crate::util::FastRand;
+
+/// A jittered exponential backoff, driven by the simulation time.
+///
+/// Each call to [`next_delay`](Backoff::next_delay) grows the upper bound of
+/// the delay by `factor`, starting at `base` and capped at `max`. The returned
+/// delay is drawn uniformly from the upper half of this bound. Since the
+/// jitter is sourced from a seeded generator, the sequence of delays is
+/// reproducible across runs.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tokio::net::TcpStream;
+/// use tokio::time::{Backoff, Duration};
+///
+/// # async fn dox() {
+/// let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(10), 2.0, 42);
+/// while TcpStream::connect("10.0.0.1:80").await.is_err() {
+///     backoff.sleep_backoff().await;
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Backoff {
+    /// The upper bound of the first delay.
+    pub base: Duration,
+    /// The upper bound of all delays.
+    pub max: Duration,
+    /// The growth factor of the upper bound per attempt.
+    pub factor: f64,
+
+    attempt: u32,
+    rng: FastRand,
+}
+
+impl Backoff {
+    /// Creates a new backoff, using `seed` to initialize the jitter.
+    pub fn new(base: Duration, max: Duration, factor: f64, seed: u64) -> Self {
+        Self {
+            base,
+            max,
+            factor,
+            attempt: 0,
+            rng: FastRand::new(seed),
+        }
+    }
+
+    /// Returns the number of delays produced since the last reset.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Restarts the backoff at `base`, e.g. after a successful attempt.
+    ///
+    /// The jitter sequence is not reset.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Returns the delay before the next attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        let exp = self.attempt.min(i32::MAX as u32) as i32;
+        let bound = (self.base.as_secs_f64() * self.factor.powi(exp)).min(self.max.as_secs_f64());
+        self.attempt = self.attempt.saturating_add(1);
+
+        let jitter = f64::from(self.rng.fastrand_n(u32::MAX)) / f64::from(u32::MAX);
+        Duration::from_secs_f64(bound * (1.0 + jitter) / 2.0).min(self.max)
+    }
+
+    /// Sleeps for the next delay.
+    pub async fn sleep_backoff(&mut self) {
+        sleep(self.next_delay()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sim::SimContext;
+    use crate::time::SimTime;
+
+    fn delays(seed: u64) -> Vec<Duration> {
+        let mut backoff = Backoff::new(
+            Duration::from_millis(100),
+            Duration::from_secs(5),
+            2.0,
+            seed,
+        );
+        (0..16).map(|_| backoff.next_delay()).collect()
+    }
+
+    #[test]
+    fn deterministic_and_bounded() {
+        let a = delays(42);
+        assert_eq!(a, delays(42));
+        assert_ne!(a, delays(7));
+
+        assert!(a[0] >= Duration::from_millis(50) && a[0] <= Duration::from_millis(100));
+        assert!(a[3] >= Duration::from_millis(400) && a[3] <= Duration::from_millis(800));
+        assert!(a.iter().all(|d| *d <= Duration::from_secs(5)));
+        assert!(a[10..].iter().all(|d| *d >= Duration::from_millis(2500)));
+    }
+
+    #[test]
+    fn sleep_backoff_advances_sim_time() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("backoff")));
+
+        let expected = delays(42)[..3].iter().sum::<Duration>();
+        let handle = rt.spawn(async {
+            let mut backoff =
+                Backoff::new(Duration::from_millis(100), Duration::from_secs(5), 2.0, 42);
+            for _ in 0..3 {
+                backoff.sleep_backoff().await;
+            }
+            SimTime::now()
+        });
+        rt.advance(Duration::from_secs(2));
+
+        let done = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(done, SimTime::from_duration(expected));
+
+        guard.leave();
+    }
+}
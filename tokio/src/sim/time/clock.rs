@@ -4,10 +4,14 @@
 //! `test-util` feature flag is enabled, the values returned for `now()` are
 //! configurable.
 //!
-use crate::time::SimTime;
+use crate::loom::sync::{Arc, Mutex};
+use crate::time::{Duration, SimTime};
 
 #[derive(Debug, Clone)]
-pub(crate) struct Clock {}
+pub(crate) struct Clock {
+    /// The rate at which the driver advances the simulation time.
+    rate: Arc<Mutex<f64>>,
+}
 
 pub(crate) fn now() -> SimTime {
     SimTime::now()
@@ -15,10 +19,82 @@ pub(crate) fn now() -> SimTime {
 
 impl Clock {
     pub(crate) fn new(_enable_pausing: bool, _start_paused: bool) -> Clock {
-        Clock {}
+        Clock {
+            rate: Arc::new(Mutex::new(1.0)),
+        }
     }
 
     pub(crate) fn now(&self) -> SimTime {
         now()
     }
+
+    /// Sets the rate at which the driver advances the simulation time.
+    ///
+    /// A rate of `2.0` advances the simulation time twice as far as
+    /// requested. Explicitly set times, like with [`SimTime::set_now`],
+    /// are not scaled.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `rate` is not a positive, finite number.
+    pub(crate) fn set_rate(&self, rate: f64) {
+        assert!(
+            rate.is_finite() && rate > 0.0,
+            "clock rate must be positive and finite"
+        );
+        *self.rate.lock() = rate;
+    }
+
+    /// Returns the rate at which the driver advances the simulation time.
+    pub(crate) fn rate(&self) -> f64 {
+        *self.rate.lock()
+    }
+
+    /// Scales a duration of driver-internal advancement by the rate.
+    pub(crate) fn scale(&self, duration: Duration) -> Duration {
+        duration.mul_f64(self.rate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::Builder;
+    use crate::sim::SimContext;
+    use crate::time::{interval, Duration, SimTime};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn interval_at_scaled_rate() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("rate")));
+
+        let ticks = Arc::new(Mutex::new(Vec::new()));
+        let log = ticks.clone();
+        rt.spawn(async move {
+            let mut interval = interval(Duration::from_millis(500));
+            loop {
+                interval.tick().await;
+                log.lock().unwrap().push(SimTime::now());
+            }
+        });
+
+        rt.set_clock_rate(2.0);
+        rt.advance(Duration::from_secs(1));
+        let ms = SimTime::from_millis;
+        assert_eq!(
+            *ticks.lock().unwrap(),
+            vec![ms(0), ms(500), ms(1000), ms(1500), ms(2000)]
+        );
+
+        // Explicitly set times are not scaled.
+        SimTime::set_now(SimTime::now() + Duration::from_millis(500));
+        assert_eq!(SimTime::now(), ms(2500));
+
+        rt.set_clock_rate(0.5);
+        rt.advance(Duration::from_secs(2));
+        assert_eq!(SimTime::now(), ms(3500));
+        assert_eq!(ticks.lock().unwrap().len(), 8);
+
+        guard.leave();
+    }
 }
@@ -1,11 +1,11 @@
 use crate::loom::sync::Arc;
 use crate::time::driver::ClockTime;
+use crate::time::{Duration, SimTime};
 use std::fmt;
 
 /// Handle to time driver instance.
 #[derive(Clone)]
 pub(crate) struct Handle {
-    #[allow(unused)]
     time_source: ClockTime,
     inner: Arc<super::Inner>,
 }
@@ -26,6 +26,19 @@ impl Handle {
     pub(super) fn is_shutdown(&self) -> bool {
         self.inner.is_shutdown()
     }
+
+    /// Returns the simulation time at which the driver was created.
+    pub(crate) fn start_time(&self) -> SimTime {
+        self.time_source.start_time
+    }
+
+    /// Returns the simulation time elapsed since the driver was created.
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.time_source
+            .clock
+            .now()
+            .saturating_duration_since(self.time_source.start_time)
+    }
 }
 
 cfg_rt! {
@@ -23,18 +23,29 @@ use std::{convert::TryInto, future::Future};
 /// # Examples
 ///
 /// ```
-/// use tokio::time::{self, Duration};
+/// use tokio::runtime::Builder;
+/// use tokio::sim::SimContext;
+/// use tokio::time::{self, Duration, SimTime};
+///
+/// let rt = Builder::new_current_thread().enable_time().build().unwrap();
+/// let guard = rt.enter_context(SimContext::empty().with_time(String::from("interval")));
 ///
-/// #[tokio::main]
-/// async fn main() {
+/// let task = rt.spawn(async {
 ///     let mut interval = time::interval(Duration::from_millis(10));
 ///
 ///     interval.tick().await; // ticks immediately
 ///     interval.tick().await; // ticks after 10ms
 ///     interval.tick().await; // ticks after 10ms
 ///
-///     // approximately 20ms have elapsed.
-/// }
+///     // exactly 20ms have elapsed.
+///     SimTime::now()
+/// });
+///
+/// rt.advance(Duration::from_millis(20));
+/// let now = rt.block_or_idle_on(task).unwrap().unwrap();
+/// assert_eq!(now, SimTime::from_millis(20));
+///
+/// guard.leave();
 /// ```
 ///
 /// A simple example using `interval` to execute a task every two seconds.
@@ -49,6 +60,8 @@ use std::{convert::TryInto, future::Future};
 /// seconds.
 ///
 /// ```
+/// use tokio::runtime::Builder;
+/// use tokio::sim::SimContext;
 /// use tokio::time;
 ///
 /// async fn task_that_takes_a_second() {
@@ -56,14 +69,21 @@ use std::{convert::TryInto, future::Future};
 ///     time::sleep(time::Duration::from_secs(1)).await
 /// }
 ///
-/// #[tokio::main]
-/// async fn main() {
+/// let rt = Builder::new_current_thread().enable_time().build().unwrap();
+/// let guard = rt.enter_context(SimContext::empty().with_time(String::from("interval")));
+///
+/// let task = rt.spawn(async {
 ///     let mut interval = time::interval(time::Duration::from_secs(2));
 ///     for _i in 0..5 {
 ///         interval.tick().await;
 ///         task_that_takes_a_second().await;
 ///     }
-/// }
+/// });
+///
+/// rt.advance(time::Duration::from_secs(9));
+/// rt.block_or_idle_on(task).unwrap().unwrap();
+///
+/// guard.leave();
 /// ```
 ///
 /// [`sleep`]: crate::time::sleep()
@@ -89,19 +109,30 @@ pub fn interval(period: Duration) -> Interval {
 /// # Examples
 ///
 /// ```
-/// use tokio::time::{interval_at, Duration, Instant};
+/// use tokio::runtime::Builder;
+/// use tokio::sim::SimContext;
+/// use tokio::time::{interval_at, Duration, SimTime};
 ///
-/// #[tokio::main]
-/// async fn main() {
-///     let start = Instant::now() + Duration::from_millis(50);
+/// let rt = Builder::new_current_thread().enable_time().build().unwrap();
+/// let guard = rt.enter_context(SimContext::empty().with_time(String::from("interval_at")));
+///
+/// let task = rt.spawn(async {
+///     let start = SimTime::now() + Duration::from_millis(50);
 ///     let mut interval = interval_at(start, Duration::from_millis(10));
 ///
 ///     interval.tick().await; // ticks after 50ms
 ///     interval.tick().await; // ticks after 10ms
 ///     interval.tick().await; // ticks after 10ms
 ///
-///     // approximately 70ms have elapsed.
-/// }
+///     // exactly 70ms have elapsed.
+///     SimTime::now()
+/// });
+///
+/// rt.advance(Duration::from_millis(70));
+/// let now = rt.block_or_idle_on(task).unwrap().unwrap();
+/// assert_eq!(now, SimTime::from_millis(70));
+///
+/// guard.leave();
 /// ```
 #[track_caller]
 pub fn interval_at(start: SimTime, period: Duration) -> Interval {
@@ -117,6 +148,7 @@ fn internal_interval_at(start: SimTime, period: Duration) -> Interval {
         delay,
         period,
         missed_tick_behavior: Default::default(),
+        skipped: 0,
     }
 }
 
@@ -126,11 +158,17 @@ fn internal_interval_at(start: SimTime, period: Duration) -> Interval {
 /// following:
 ///
 /// ```
+/// use tokio::runtime::Builder;
+/// use tokio::sim::SimContext;
 /// use tokio::time::{self, Duration};
-/// # async fn task_that_takes_one_to_three_millis() {}
+/// # async fn task_that_takes_one_to_three_millis() {
+/// #     time::sleep(Duration::from_millis(3)).await
+/// # }
+///
+/// let rt = Builder::new_current_thread().enable_time().build().unwrap();
+/// let guard = rt.enter_context(SimContext::empty().with_time(String::from("interval")));
 ///
-/// #[tokio::main]
-/// async fn main() {
+/// let task = rt.spawn(async {
 ///     // ticks every 2 milliseconds
 ///     let mut interval = time::interval(Duration::from_millis(2));
 ///     for _ in 0..5 {
@@ -138,7 +176,12 @@ fn internal_interval_at(start: SimTime, period: Duration) -> Interval {
 ///         // if this takes more than 2 milliseconds, a tick will be delayed
 ///         task_that_takes_one_to_three_millis().await;
 ///     }
-/// }
+/// });
+///
+/// rt.advance(Duration::from_millis(15));
+/// rt.block_or_idle_on(task).unwrap().unwrap();
+///
+/// guard.leave();
 /// ```
 ///
 /// Generally, a tick is missed if too much time is spent without calling
@@ -175,10 +218,14 @@ pub enum MissedTickBehavior {
     ///
     /// ```
     /// use tokio::time::{interval, Duration};
-    /// # async fn task_that_takes_200_millis() {}
+    /// # use tokio::sim::SimContext;
+    /// # async fn task_that_takes_200_millis() {
+    /// #     tokio::time::sleep(Duration::from_millis(200)).await
+    /// # }
     ///
-    /// # #[tokio::main(flavor = "current_thread")]
-    /// # async fn main() {
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    /// # let guard = rt.enter_context(SimContext::empty().with_time(String::from("burst")));
+    /// # let task = rt.spawn(async {
     /// let mut interval = interval(Duration::from_millis(50));
     ///
     /// // First tick resolves immediately after creation
@@ -204,7 +251,10 @@ pub enum MissedTickBehavior {
     /// // Since we have gotten to 200ms after the start of `interval`, this
     /// // will resolve after 50ms
     /// interval.tick().await;
-    /// # }
+    /// # });
+    /// # rt.advance(Duration::from_millis(250));
+    /// # rt.block_or_idle_on(task).unwrap().unwrap();
+    /// # guard.leave();
     /// ```
     ///
     /// This is the default behavior when [`Interval`] is created with
@@ -234,10 +284,14 @@ pub enum MissedTickBehavior {
     ///
     /// ```
     /// use tokio::time::{interval, Duration, MissedTickBehavior};
-    /// # async fn task_that_takes_more_than_50_millis() {}
+    /// # use tokio::sim::SimContext;
+    /// # async fn task_that_takes_more_than_50_millis() {
+    /// #     tokio::time::sleep(Duration::from_millis(60)).await
+    /// # }
     ///
-    /// # #[tokio::main(flavor = "current_thread")]
-    /// # async fn main() {
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    /// # let guard = rt.enter_context(SimContext::empty().with_time(String::from("delay")));
+    /// # let task = rt.spawn(async {
     /// let mut interval = interval(Duration::from_millis(50));
     /// interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
     ///
@@ -254,7 +308,10 @@ pub enum MissedTickBehavior {
     /// // 50ms (or whatever the `period` is) from right then, not from when
     /// // were were *supposed* to tick
     /// interval.tick().await;
-    /// # }
+    /// # });
+    /// # rt.advance(Duration::from_millis(110));
+    /// # rt.block_or_idle_on(task).unwrap().unwrap();
+    /// # guard.leave();
     /// ```
     ///
     /// [`Burst`]: MissedTickBehavior::Burst
@@ -285,10 +342,14 @@ pub enum MissedTickBehavior {
     ///
     /// ```
     /// use tokio::time::{interval, Duration, MissedTickBehavior};
-    /// # async fn task_that_takes_75_millis() {}
+    /// # use tokio::sim::SimContext;
+    /// # async fn task_that_takes_75_millis() {
+    /// #     tokio::time::sleep(Duration::from_millis(75)).await
+    /// # }
     ///
-    /// # #[tokio::main(flavor = "current_thread")]
-    /// # async fn main() {
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    /// # let guard = rt.enter_context(SimContext::empty().with_time(String::from("skip")));
+    /// # let task = rt.spawn(async {
     /// let mut interval = interval(Duration::from_millis(50));
     /// interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
     ///
@@ -302,7 +363,10 @@ pub enum MissedTickBehavior {
     /// // `interval`, which is the closest multiple of `period` from the start
     /// // of `interval` after the call to `tick` up above.
     /// interval.tick().await;
-    /// # }
+    /// # });
+    /// # rt.advance(Duration::from_millis(100));
+    /// # rt.block_or_idle_on(task).unwrap().unwrap();
+    /// # guard.leave();
     /// ```
     ///
     /// [`Burst`]: MissedTickBehavior::Burst
@@ -372,11 +436,17 @@ pub struct Interval {
 
     /// The strategy `Interval` should use when a tick is missed.
     missed_tick_behavior: MissedTickBehavior,
+
+    /// The number of periods that were skipped due to missed ticks.
+    skipped: u64,
 }
 
 impl Interval {
     /// Completes when the next instant in the interval has been reached.
     ///
+    /// Returns the instant the tick was scheduled for, which may lie in the
+    /// past if the tick was missed.
+    ///
     /// # Cancel safety
     ///
     /// This method is cancellation safe. If `tick` is used as the branch in a `tokio::select!` and
@@ -385,20 +455,29 @@ impl Interval {
     /// # Examples
     ///
     /// ```
-    /// use tokio::time;
+    /// use tokio::runtime::Builder;
+    /// use tokio::sim::SimContext;
+    /// use tokio::time::{self, SimTime};
     ///
     /// use std::time::Duration;
     ///
-    /// #[tokio::main]
-    /// async fn main() {
+    /// let rt = Builder::new_current_thread().enable_time().build().unwrap();
+    /// let guard = rt.enter_context(SimContext::empty().with_time(String::from("tick")));
+    ///
+    /// let task = rt.spawn(async {
     ///     let mut interval = time::interval(Duration::from_millis(10));
     ///
     ///     interval.tick().await;
     ///     interval.tick().await;
-    ///     interval.tick().await;
+    ///     interval.tick().await
+    /// });
     ///
-    ///     // approximately 20ms have elapsed.
-    /// }
+    /// // The third tick was scheduled 20ms after the first one.
+    /// rt.advance(Duration::from_millis(20));
+    /// let scheduled = rt.block_or_idle_on(task).unwrap().unwrap();
+    /// assert_eq!(scheduled, SimTime::from_millis(20));
+    ///
+    /// guard.leave();
     /// ```
     pub async fn tick(&mut self) -> SimTime {
         let instant = poll_fn(|cx| self.poll_tick(cx));
@@ -434,6 +513,13 @@ impl Interval {
         // schedule the next tick according to how the user specified with
         // `MissedTickBehavior`
         let next = if now > timeout + Duration::from_millis(5) {
+            if self.missed_tick_behavior != MissedTickBehavior::Burst {
+                let missed = (now - timeout).as_nanos() / self.period.as_nanos();
+                self.skipped = self
+                    .skipped
+                    .saturating_add(missed.try_into().unwrap_or(u64::MAX));
+            }
+
             self.missed_tick_behavior
                 .next_timeout(timeout, now, self.period)
         } else {
@@ -453,12 +539,16 @@ impl Interval {
     /// # Examples
     ///
     /// ```
-    /// use tokio::time;
+    /// use tokio::runtime::Builder;
+    /// use tokio::sim::SimContext;
+    /// use tokio::time::{self, SimTime};
     ///
     /// use std::time::Duration;
     ///
-    /// #[tokio::main]
-    /// async fn main() {
+    /// let rt = Builder::new_current_thread().enable_time().build().unwrap();
+    /// let guard = rt.enter_context(SimContext::empty().with_time(String::from("reset")));
+    ///
+    /// let task = rt.spawn(async {
     ///     let mut interval = time::interval(Duration::from_millis(100));
     ///
     ///     interval.tick().await;
@@ -469,8 +559,15 @@ impl Interval {
     ///     interval.tick().await;
     ///     interval.tick().await;
     ///
-    ///     // approximately 250ms have elapsed.
-    /// }
+    ///     // exactly 250ms have elapsed.
+    ///     SimTime::now()
+    /// });
+    ///
+    /// rt.advance(Duration::from_millis(250));
+    /// let now = rt.block_or_idle_on(task).unwrap().unwrap();
+    /// assert_eq!(now, SimTime::from_millis(250));
+    ///
+    /// guard.leave();
     /// ```
     pub fn reset(&mut self) {
         self.delay.as_mut().reset(SimTime::now() + self.period);
@@ -490,4 +587,62 @@ impl Interval {
     pub fn period(&self) -> Duration {
         self.period
     }
+
+    /// Returns the number of periods that were skipped, since the interval
+    /// was created.
+    ///
+    /// A period is skipped if a tick was missed and the [`MissedTickBehavior`]
+    /// schedules the next tick without firing for it. Thus the count only
+    /// increases with [`Delay`](MissedTickBehavior::Delay) or
+    /// [`Skip`](MissedTickBehavior::Skip), since [`Burst`](MissedTickBehavior::Burst)
+    /// fires all missed ticks.
+    pub fn skipped_count(&self) -> u64 {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sim::SimContext;
+
+    #[test]
+    fn skipped_count_after_jump() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("interval")));
+        let jump = |ms| {
+            SimTime::set_now(SimTime::from_millis(ms));
+            SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().process_now());
+        };
+
+        let mut skip = interval(Duration::from_millis(100));
+        skip.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut burst = interval(Duration::from_millis(100));
+        for ticker in [&mut skip, &mut burst] {
+            let deadline = rt.block_or_idle_on(ticker.tick()).unwrap();
+            assert_eq!(deadline, SimTime::ZERO);
+        }
+
+        // The ticks at 200ms and 300ms were missed.
+        jump(350);
+        let deadline = rt.block_or_idle_on(skip.tick()).unwrap();
+        assert_eq!(deadline, SimTime::from_millis(100));
+        assert_eq!(skip.skipped_count(), 2);
+        assert!(rt.block_or_idle_on(skip.tick()).is_err());
+
+        // Burst fires all missed ticks instead.
+        for ms in &[100, 200, 300] {
+            let deadline = rt.block_or_idle_on(burst.tick()).unwrap();
+            assert_eq!(deadline, SimTime::from_millis(*ms));
+        }
+        assert_eq!(burst.skipped_count(), 0);
+
+        jump(400);
+        let deadline = rt.block_or_idle_on(skip.tick()).unwrap();
+        assert_eq!(deadline, SimTime::from_millis(400));
+        assert_eq!(skip.skipped_count(), 2);
+
+        guard.leave();
+    }
 }
@@ -18,6 +18,7 @@ pub use timeout::*;
 
 mod queue;
 use queue::*;
+pub(crate) use queue::TimerQueueSnapshot;
 
 #[derive(Debug)]
 pub(crate) struct Driver<P: Park + 'static> {
@@ -76,7 +77,6 @@ impl Handle {
 }
 
 #[derive(Debug, Clone)]
-#[allow(unused)]
 pub(self) struct ClockTime {
     clock: super::clock::Clock,
     start_time: SimTime,
@@ -233,11 +233,36 @@ impl TimeContext {
         self.ident.clone()
     }
 
+    /// Sets the granularity used to coalesce timer deadlines.
+    ///
+    /// Deadlines registered afterwards are rounded up to the next multiple
+    /// of `granularity`, so that nearby timers share a slot and are woken
+    /// together. A zero granularity (the default) disables coalescing.
+    pub fn set_granularity(&self, granularity: Duration) {
+        self.queue.set_granularity(granularity);
+    }
+
     /// next_time_poll
     pub fn next_time_poll(&self) -> Option<SimTime> {
         self.queue.next_wakeup()
     }
 
+    /// Returns the next `n` distinct deadlines in ascending order,
+    /// without processing them.
+    pub fn upcoming(&self, n: usize) -> Vec<SimTime> {
+        self.queue.upcoming(n)
+    }
+
+    /// Returns the number of distinct pending deadlines.
+    pub fn pending_slots(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns the total number of timers fired by this context.
+    pub fn timers_fired(&self) -> usize {
+        self.queue.fired()
+    }
+
     /// process_now
     pub fn process_now(&self) {
         let now = SimTime::now();
@@ -252,9 +277,233 @@ impl TimeContext {
         }
     }
 
+    /// Processes only the earliest deadline that is due at `now`.
+    ///
+    /// In contrast to [`process_at`](TimeContext::process_at), which wakes
+    /// all due timers at once, this advances [`SimTime::now`] to the
+    /// processed deadline and returns it, so that the caller can poll
+    /// woken tasks before processing the next deadline. The clock is never
+    /// moved backwards, if the deadline already passed. Returns `None`
+    /// if no deadline is due at `now`.
+    pub fn process_up_to(&self, now: SimTime) -> Option<SimTime> {
+        let time_slot = self.queue.pop_next(now)?;
+        let deadline = time_slot.slot;
+
+        // Deadlines that passed unprocessed must not move the clock backwards.
+        SimTime::set_now(deadline.max(SimTime::now()));
+        time_slot.wake_all();
+        Some(deadline)
+    }
+
+    pub(crate) fn snapshot(&self) -> TimerQueueSnapshot {
+        self.queue.snapshot()
+    }
+
+    pub(crate) fn restore(&self, snapshot: &TimerQueueSnapshot) {
+        self.queue.restore(snapshot)
+    }
+
     /// swap
     pub fn swap(&mut self, other: &mut TimeContext) {
         std::mem::swap(&mut self.ident, &mut other.ident);
         self.queue.swap(&other.queue)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::Builder;
+    use crate::sim::SimContext;
+    use crate::time::{sleep, Duration, SimTime};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn advance_polls_between_deadlines() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("advance")));
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let log1 = log.clone();
+        rt.spawn(async move {
+            sleep(Duration::from_secs(1)).await;
+            log1.lock().unwrap().push(("1s", SimTime::now()));
+
+            let log1 = log1.clone();
+            crate::spawn(async move {
+                log1.lock().unwrap().push(("spawned", SimTime::now()));
+            });
+        });
+
+        let log2 = log.clone();
+        rt.spawn(async move {
+            sleep(Duration::from_secs(2)).await;
+            log2.lock().unwrap().push(("2s", SimTime::now()));
+        });
+
+        rt.advance(Duration::from_secs(3));
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("1s", SimTime::from(1.0)),
+                ("spawned", SimTime::from(1.0)),
+                ("2s", SimTime::from(2.0)),
+            ]
+        );
+        assert_eq!(SimTime::now(), SimTime::from(3.0));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn process_up_to_keeps_time_monotonic() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("monotonic")));
+        let start = SimTime::now();
+
+        let handle = rt.spawn(sleep(Duration::from_secs(1)));
+        rt.poll_until_idle();
+
+        // The clock passed the deadline, without processing the timer.
+        let now = start + Duration::from_secs(5);
+        SimTime::set_now(now);
+        let deadline =
+            SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().process_up_to(now));
+        assert_eq!(deadline, Some(start + Duration::from_secs(1)));
+        assert_eq!(SimTime::now(), now);
+
+        rt.block_or_idle_on(handle).unwrap().unwrap();
+        guard.leave();
+    }
+
+    #[test]
+    fn sleep_ids_restart_per_runtime() {
+        let run = || {
+            let rt = Builder::new_current_thread().enable_time().build().unwrap();
+            let guard = rt.enter_context(SimContext::empty().with_time(String::from("ids")));
+            let start = SimTime::now();
+
+            let log = Arc::new(Mutex::new(Vec::new()));
+            for secs in [2, 1, 1] {
+                let log = log.clone();
+                rt.spawn(async move {
+                    let timer = sleep(Duration::from_secs(secs));
+                    let id = timer.id;
+                    timer.await;
+                    log.lock().unwrap().push((id, SimTime::now() - start));
+                });
+            }
+            rt.advance(Duration::from_secs(3));
+
+            guard.leave();
+
+            let log = log.lock().unwrap().clone();
+            log
+        };
+
+        let first = run();
+        let ids = first.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 0]);
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn equal_deadlines_wake_in_id_order() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("ties")));
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let sleeps = (0..3)
+            .map(|_| sleep(Duration::from_millis(10)))
+            .collect::<Vec<_>>();
+        let ids = sleeps.iter().map(|timer| timer.id).collect::<Vec<_>>();
+
+        // The timers are registered in reverse order of their ids.
+        for timer in sleeps.into_iter().rev() {
+            let log = log.clone();
+            rt.spawn(async move {
+                let id = timer.id;
+                timer.await;
+                log.lock().unwrap().push(id);
+            });
+        }
+        rt.advance(Duration::from_millis(10));
+
+        assert_eq!(*log.lock().unwrap(), ids);
+        guard.leave();
+    }
+
+    #[test]
+    fn elapsed_since_driver_start() {
+        SimTime::set_now(SimTime::from_secs(10));
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("elapsed")));
+        assert_eq!(rt.start_time(), SimTime::from_secs(10));
+        assert_eq!(rt.elapsed(), Duration::ZERO);
+
+        rt.spawn(async { sleep(Duration::from_millis(100)).await });
+        rt.advance(Duration::from_millis(250));
+        assert_eq!(rt.elapsed(), Duration::from_millis(250));
+
+        // An external scheduler may set the time directly.
+        SimTime::set_now(SimTime::now() + Duration::from_millis(50));
+        assert_eq!(rt.elapsed(), Duration::from_millis(300));
+        assert_eq!(rt.start_time(), SimTime::from_secs(10));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn run_all_timers_to_last_deadline() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("collapse")));
+        let start = SimTime::now();
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for ms in [300u64, 100, 200].iter().copied() {
+            let log = log.clone();
+            rt.spawn(async move {
+                sleep(Duration::from_millis(ms)).await;
+                log.lock().unwrap().push(ms);
+                // A final timer, registered by the shutdown logic.
+                if ms == 300 {
+                    sleep(Duration::from_millis(50)).await;
+                    log.lock().unwrap().push(350);
+                }
+            });
+        }
+
+        let end = rt.run_all_timers();
+        assert_eq!(*log.lock().unwrap(), vec![100, 200, 300, 350]);
+        assert_eq!(end, start + Duration::from_millis(350));
+
+        let ctx = guard.leave();
+        assert_eq!(ctx.time.as_ref().unwrap().next_time_poll(), None);
+    }
+
+    #[test]
+    fn run_all_timers_until_limit_with_interval() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("bounded")));
+        let start = SimTime::now();
+        assert!(!rt.run_all_timers_until(start));
+
+        let ticks = Arc::new(Mutex::new(0));
+        let log = ticks.clone();
+        rt.spawn(async move {
+            let mut interval = crate::time::interval(Duration::from_millis(100));
+            loop {
+                interval.tick().await;
+                *log.lock().unwrap() += 1;
+            }
+        });
+
+        // The interval never runs out of timers.
+        assert!(rt.run_all_timers_until(start + Duration::from_millis(350)));
+        assert_eq!(*ticks.lock().unwrap(), 4);
+        assert_eq!(SimTime::now(), start + Duration::from_millis(300));
+
+        guard.leave();
+    }
+}
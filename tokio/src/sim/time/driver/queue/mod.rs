@@ -5,31 +5,64 @@ use std::collections::VecDeque;
 use std::task::Waker;
 
 use crate::loom::sync::{Arc, Weak};
-use crate::time::SimTime;
+use crate::time::{Duration, SimTime};
 
 #[derive(Debug)]
 pub(super) struct TimerQueue {
     current: Cell<SimTime>,
+    granularity: Cell<Duration>,
     pending: RefCell<VecDeque<Arc<TimeSlot>>>,
+    fired: Cell<usize>,
 }
 
 impl TimerQueue {
     pub(crate) fn new(time: SimTime) -> Self {
         Self {
             current: Cell::new(time),
+            granularity: Cell::new(Duration::new(0, 0)),
             pending: RefCell::new(VecDeque::new()),
+            fired: Cell::new(0),
         }
     }
     pub(crate) fn reset(&self) {
         self.pending.borrow_mut().clear();
     }
 
+    /// Sets the granularity used to coalesce deadlines.
+    ///
+    /// Deadlines pushed after this call are rounded up to the next multiple
+    /// of `granularity`, so that nearby timers share a slot and fire together.
+    /// A zero granularity disables coalescing.
+    pub(crate) fn set_granularity(&self, granularity: Duration) {
+        self.granularity.set(granularity);
+    }
+
+    fn coalesce(&self, time: SimTime) -> SimTime {
+        let granularity = self.granularity.get();
+        let slot = time.truncate_to(granularity);
+        if slot < time {
+            slot.checked_add(granularity).unwrap_or(SimTime::MAX)
+        } else {
+            slot
+        }
+    }
+
     pub(crate) fn swap(&self, other: &TimerQueue) {
         // current
         let tmp = self.current.get();
         self.current.set(other.current.get());
         other.current.set(tmp);
 
+        // granularity
+        let tmp = self.granularity.get();
+        self.granularity.set(other.granularity.get());
+        other.granularity.set(tmp);
+
+        // fired
+        let tmp = self.fired.get();
+        self.fired.set(other.fired.get());
+        other.fired.set(tmp);
+
         // pending
         let mut lhs = self.pending.borrow_mut();
         let mut rhs = other.pending.borrow_mut();
@@ -39,25 +72,68 @@ impl TimerQueue {
         }
     }
 
+    pub(crate) fn snapshot(&self) -> TimerQueueSnapshot {
+        TimerQueueSnapshot {
+            current: self.current.get(),
+            granularity: self.granularity.get(),
+            fired: self.fired.get(),
+            deadlines: self.pending.borrow().iter().map(|slot| slot.slot).collect(),
+        }
+    }
+
+    /// Replaces the state of the queue with a snapshot.
+    ///
+    /// The restored slots contain no entries, so all timers of the current
+    /// state are woken to register themselves again once polled.
+    pub(crate) fn restore(self: &Arc<TimerQueue>, snapshot: &TimerQueueSnapshot) {
+        self.current.set(snapshot.current);
+        self.granularity.set(snapshot.granularity);
+        self.fired.set(snapshot.fired);
+
+        let slots = snapshot
+            .deadlines
+            .iter()
+            .map(|&slot| {
+                Arc::new(TimeSlot {
+                    slot,
+                    entries: RefCell::new(Vec::new()),
+
+                    queue: self.clone(),
+                })
+            })
+            .collect();
+        let previous = std::mem::replace(&mut *self.pending.borrow_mut(), slots);
+
+        for slot in previous {
+            for entry in slot.entries.borrow().iter() {
+                entry.waker.wake_by_ref();
+            }
+        }
+    }
+
     pub(crate) fn push(
         self: &Arc<TimerQueue>,
         entry: TimeSlotEntry,
         time: SimTime,
     ) -> TimeSlotEntryHandle {
         assert!(time >= self.current.get());
+        let time = self.coalesce(time);
         let mut pending = self.pending.borrow_mut();
         let id = entry.id;
 
         // Binary search for slot
         match pending.binary_search_by(|slot| slot.slot.cmp(&time)) {
             Ok(found) => {
+                let waker = entry.waker.clone();
                 pending[found].push(entry);
                 TimeSlotEntryHandle {
                     id,
+                    waker,
                     handle: Arc::downgrade(&pending[found]),
                 }
             }
             Err(insert_at) => {
+                let waker = entry.waker.clone();
                 pending.insert(
                     insert_at,
                     Arc::new(TimeSlot {
@@ -69,33 +145,91 @@ impl TimerQueue {
                 );
                 TimeSlotEntryHandle {
                     id,
+                    waker,
                     handle: Arc::downgrade(&pending[insert_at]),
                 }
             }
         }
     }
 
+    /// Removes the slot at `time`, if it has no entries left.
+    fn remove_if_empty(&self, time: SimTime) {
+        let mut pending = self.pending.borrow_mut();
+        if let Some(found) = pending.iter().position(|slot| slot.slot == time) {
+            if pending[found].entries.borrow().is_empty() {
+                pending.remove(found);
+            }
+        }
+    }
+
     pub(crate) fn next_wakeup(&self) -> Option<SimTime> {
         Some(self.pending.borrow().front()?.slot)
     }
 
+    pub(crate) fn upcoming(&self, n: usize) -> Vec<SimTime> {
+        self.pending
+            .borrow()
+            .iter()
+            .take(n)
+            .map(|slot| slot.slot)
+            .collect()
+    }
+
     pub(crate) fn pop(&self, now: SimTime) -> Vec<TimeSlot> {
         assert!(now >= self.current.get());
         self.current.set(now);
-        let front = match self.pending.borrow().front() {
-            Some(v) => v.slot,
-            None => return Vec::new(),
-        };
-        if front <= now {
-            let mut buffer = Vec::new();
-            while let Some(Ok(v)) = self.pending.borrow_mut().pop_front().map(Arc::try_unwrap) {
+
+        let mut pending = self.pending.borrow_mut();
+        let mut buffer = Vec::new();
+        while pending.front().map_or(false, |slot| slot.slot <= now) {
+            if let Some(Ok(v)) = pending.pop_front().map(Arc::try_unwrap) {
+                self.record_fired(&v);
                 buffer.push(v)
             }
-            buffer
-        } else {
-            Vec::new()
         }
+        buffer
     }
+
+    /// Pops only the earliest slot, if it is due at `now`.
+    ///
+    /// The current time of the queue is set to the deadline of the popped
+    /// slot, so later slots can still be registered before `now`.
+    pub(crate) fn pop_next(&self, now: SimTime) -> Option<TimeSlot> {
+        assert!(now >= self.current.get());
+
+        let mut pending = self.pending.borrow_mut();
+        while pending.front().map_or(false, |slot| slot.slot <= now) {
+            let slot = pending.pop_front()?;
+            self.current.set(slot.slot);
+            if let Ok(slot) = Arc::try_unwrap(slot) {
+                self.record_fired(&slot);
+                return Some(slot);
+            }
+        }
+        None
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.pending.borrow().len()
+    }
+
+    /// Returns the number of timers woken by popped slots.
+    pub(crate) fn fired(&self) -> usize {
+        self.fired.get()
+    }
+
+    fn record_fired(&self, slot: &TimeSlot) {
+        self.fired.set(self.fired.get() + slot.entries.borrow().len());
+    }
+}
+
+/// The state of a [`TimerQueue`], without the wakers of its entries.
+#[derive(Debug, Clone)]
+pub(crate) struct TimerQueueSnapshot {
+    current: SimTime,
+    granularity: Duration,
+    fired: usize,
+    deadlines: Vec<SimTime>,
 }
 
 // SAFTEY
@@ -136,11 +270,14 @@ impl TimeSlot {
         None
     }
 
+    /// Wakes all entries of the slot.
+    ///
+    /// Entries are woken in ascending order of their timer ids, independent
+    /// of the order in which they were registered or reset.
     pub(crate) fn wake_all(self) {
-        self.entries
-            .into_inner()
-            .into_iter()
-            .for_each(|entry| entry.waker.wake())
+        let mut entries = self.entries.into_inner();
+        entries.sort_unstable_by_key(|entry| entry.id);
+        entries.into_iter().for_each(|entry| entry.waker.wake())
     }
 }
 
@@ -193,14 +330,87 @@ impl Eq for TimeSlotEntry {}
 #[derive(Debug)]
 pub(super) struct TimeSlotEntryHandle {
     pub(super) id: usize,
+    pub(super) waker: Waker,
     pub(super) handle: Weak<TimeSlot>,
 }
 
 impl TimeSlotEntryHandle {
-    pub(crate) fn reset(self, new_deadline: SimTime) -> Option<TimeSlotEntryHandle> {
-        let handle = self.handle.upgrade()?;
-        let entry = handle.remove(self.id)?;
+    /// Moves the entry into the slot of `new_deadline`.
+    ///
+    /// If the original slot was already popped, the entry is returned
+    /// so that the caller can register it with a fresh slot.
+    pub(crate) fn reset(self, new_deadline: SimTime) -> Result<TimeSlotEntryHandle, TimeSlotEntry> {
+        let handle = match self.handle.upgrade() {
+            Some(handle) => handle,
+            None => return Err(self.into_entry()),
+        };
+        let entry = match handle.remove(self.id) {
+            Some(entry) => entry,
+            None => return Err(self.into_entry()),
+        };
+        handle.queue.remove_if_empty(handle.slot);
+
+        Ok(handle.queue.push(entry, new_deadline))
+    }
+
+    /// Removes the entry from its slot, if the slot is still pending.
+    pub(crate) fn cancel(self) -> TimeSlotEntry {
+        if let Some(handle) = self.handle.upgrade() {
+            handle.remove(self.id);
+            // An empty slot would still be reported as pending wakeup.
+            handle.queue.remove_if_empty(handle.slot);
+        }
+        self.into_entry()
+    }
+
+    fn into_entry(self) -> TimeSlotEntry {
+        TimeSlotEntry {
+            waker: self.waker,
+            id: self.id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coalesce_nearby_deadlines() {
+        let queue = Arc::new(TimerQueue::new(SimTime::ZERO));
+        queue.set_granularity(Duration::from_millis(1));
+
+        for id in 0..10_000 {
+            let entry = TimeSlotEntry {
+                waker: futures::task::noop_waker(),
+                id,
+            };
+            let deadline = SimTime::from_duration(Duration::from_micros(id as u64 + 1));
+            queue.push(entry, deadline);
+        }
+
+        assert_eq!(queue.len(), 10);
+        let ms = |ms| SimTime::from_duration(Duration::from_millis(ms));
+        assert_eq!(queue.upcoming(3), vec![ms(1), ms(2), ms(3)]);
+        assert_eq!(queue.upcoming(20).len(), 10);
+        assert_eq!(
+            queue.next_wakeup(),
+            Some(SimTime::from_duration(Duration::from_millis(1)))
+        );
+
+        let now = SimTime::from_duration(Duration::from_micros(1500));
+        let slots = queue.pop(now);
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].entries.borrow().len(), 1000);
+        assert_eq!(queue.len(), 9);
 
-        Some(handle.queue.push(entry, new_deadline))
+        let now = SimTime::from_duration(Duration::from_millis(10));
+        let entries: usize = queue
+            .pop(now)
+            .iter()
+            .map(|slot| slot.entries.borrow().len())
+            .sum();
+        assert_eq!(entries, 9000);
+        assert_eq!(queue.len(), 0);
     }
 }
@@ -1,7 +1,6 @@
 use std::cmp::Ordering;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::atomic::AtomicUsize;
 use std::task::{Context, Poll};
 
 use super::queue::{TimeSlotEntry, TimeSlotEntryHandle};
@@ -124,7 +123,34 @@ pub fn sleep(duration: Duration) -> Sleep {
         None => Sleep::far_future(),
     }
 }
-static SLEEP_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Waits until the simulation time reaches `deadline`, returning the
+/// simulation time at which the future resolved.
+///
+/// The future resolves the first time the time context processes a deadline
+/// at or after `deadline`, no matter whether the time was advanced by the
+/// runtime, by [`TimeContext::process_up_to`](super::TimeContext::process_up_to)
+/// or by an external [`SimTime::set_now`] followed by processing. If
+/// `deadline` has already passed, the future resolves immediately.
+///
+/// ```
+/// use tokio::sim::SimContext;
+/// use tokio::time::{Duration, SimTime};
+///
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// # let guard = rt.enter_context(SimContext::empty().with_time(String::from("at")));
+/// # let task = rt.spawn(async {
+/// let reached = tokio::time::at(SimTime::from_secs(5)).await;
+/// assert!(reached >= SimTime::from_secs(5));
+/// # });
+/// # rt.advance(Duration::from_secs(5));
+/// # rt.block_or_idle_on(task).unwrap().unwrap();
+/// # guard.leave();
+/// ```
+pub async fn at(deadline: SimTime) -> SimTime {
+    sleep_until(deadline).await;
+    SimTime::now()
+}
 
 pin_project! {
     /// Future returned by [`sleep`](sleep) and [`sleep_until`](sleep_until).
@@ -227,10 +253,9 @@ pin_project! {
 
 impl Sleep {
     pub(crate) fn new_timeout(deadline: SimTime) -> Sleep {
-        let next = SLEEP_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         Sleep {
             deadline,
-            id: next,
+            id: SimContext::next_id(),
             handle: None,
         }
     }
@@ -284,16 +309,48 @@ impl Sleep {
         self.reset_inner(deadline)
     }
 
+    /// Removes the timer of this sleep from the timer queue, if registered.
+    ///
+    /// The sleep registers again once polled.
+    pub(crate) fn unregister(self: Pin<&mut Self>) {
+        if let Some(handle) = self.project().handle.take() {
+            handle.cancel();
+        }
+    }
+
     fn reset_inner(self: Pin<&mut Self>, deadline: SimTime) {
         let me = self.project();
+        *me.deadline = deadline;
+
         if let Some(handle) = me.handle.take() {
-            // Reogranize timer calls.
-            handle.reset(deadline);
-            *me.deadline = deadline;
+            // A deadline that is already due cannot be scheduled,
+            // so wake the task to let it observe the elapsed sleep.
+            if deadline <= SimTime::now() {
+                handle.cancel().waker.wake();
+                return;
+            }
+
+            // Reogranize timer calls. If the old slot was already popped,
+            // the entry must be registered with a fresh slot, since the task
+            // may not be polled again before the new deadline.
+            *me.handle = Some(match handle.reset(deadline) {
+                Ok(handle) => handle,
+                Err(entry) => register(entry, deadline),
+            });
         }
     }
 }
 
+fn register(entry: TimeSlotEntry, deadline: SimTime) -> TimeSlotEntryHandle {
+    SimContext::with_current(|ctx| {
+        let Some(tctx) = ctx.time.as_ref() else {
+            panic!("Missing TimeContext for IO operations")
+        };
+
+        tctx.queue.push(entry, deadline)
+    })
+}
+
 impl Future for Sleep {
     type Output = ();
 
@@ -305,19 +362,19 @@ impl Future for Sleep {
                 // Setup waker
                 // TimeDriver::with_current(|mut driver| driver.wake_sleeper(&self, cx));
 
-                let handle = SimContext::with_current(|ctx| {
-                    let Some(tctx) = ctx.time.as_ref() else {
-                        panic!("Missing TimeContext for IO operations")
-                    };
+                // Replace the previous registration, so that the entry is
+                // neither registered twice nor woken with an outdated waker.
+                if let Some(handle) = me.handle.take() {
+                    handle.cancel();
+                }
 
-                    tctx.queue.push(
-                        TimeSlotEntry {
-                            id: *me.id,
-                            waker: cx.waker().clone(),
-                        },
-                        *me.deadline,
-                    )
-                });
+                let handle = register(
+                    TimeSlotEntry {
+                        id: *me.id,
+                        waker: cx.waker().clone(),
+                    },
+                    *me.deadline,
+                );
 
                 *me.handle = Some(handle);
                 Poll::Pending
@@ -327,3 +384,202 @@ impl Future for Sleep {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use futures::task::{waker, ArcWake};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct WakeCounter(AtomicUsize);
+
+    impl ArcWake for WakeCounter {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn at_resolves_once_after_jumps() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("at")));
+        let process_now =
+            || SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().process_now());
+
+        let resolved = Arc::new(std::sync::Mutex::new(Vec::new()));
+        for deadline in [5, 20] {
+            let resolved = resolved.clone();
+            rt.spawn(async move {
+                let t = at(SimTime::from_secs(deadline)).await;
+                resolved.lock().unwrap().push((deadline, t));
+            });
+        }
+        rt.poll_until_idle();
+
+        // A jump before the deadline does not resolve.
+        SimTime::set_now(SimTime::from_secs(3));
+        process_now();
+        rt.poll_until_idle();
+        assert!(resolved.lock().unwrap().is_empty());
+
+        // Advancing past the deadline resolves exactly at the deadline.
+        rt.advance(Duration::from_secs(10));
+        assert_eq!(*resolved.lock().unwrap(), vec![(5, SimTime::from_secs(5))]);
+
+        // An external jump past the deadline resolves after the deadline.
+        SimTime::set_now(SimTime::from_secs(30));
+        process_now();
+        rt.poll_until_idle();
+        rt.advance(Duration::from_secs(100));
+        assert_eq!(
+            *resolved.lock().unwrap(),
+            vec![(5, SimTime::from_secs(5)), (20, SimTime::from_secs(30))]
+        );
+
+        // A deadline in the past resolves immediately.
+        let t = rt.block_or_idle_on(rt.spawn(at(SimTime::from_secs(1))));
+        assert_eq!(t.unwrap().unwrap(), SimTime::from_secs(130));
+
+        guard.leave();
+    }
+
+    #[test]
+    fn reset_prunes_empty_slot() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("prune")));
+        let start = SimTime::now();
+
+        let counter = Arc::new(WakeCounter::default());
+        let waker = waker(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut sleep = Box::pin(sleep(Duration::from_secs(1)));
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+        sleep.as_mut().reset(start + Duration::from_secs(3));
+
+        let upcoming = SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().upcoming(2));
+        assert_eq!(upcoming, vec![start + Duration::from_secs(3)]);
+
+        guard.leave();
+    }
+
+    #[test]
+    fn reset_after_slot_was_popped() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("reset")));
+        let process_now =
+            || SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().process_now());
+
+        let counter = Arc::new(WakeCounter::default());
+        let waker = waker(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut sleep = Box::pin(sleep(Duration::from_secs(1)));
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+
+        // Pop the slot, but do not poll the sleep.
+        SimTime::set_now(SimTime::from(1.0));
+        process_now();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+        sleep.as_mut().reset(SimTime::from(2.0));
+        let next = SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().next_time_poll());
+        assert_eq!(next, Some(SimTime::from(2.0)));
+
+        SimTime::set_now(SimTime::from(2.0));
+        process_now();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+        assert!(sleep.as_mut().poll(&mut cx).is_ready());
+
+        guard.leave();
+    }
+
+    #[test]
+    fn repoll_updates_waker() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("repoll")));
+        let process_now =
+            || SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().process_now());
+
+        let first = Arc::new(WakeCounter::default());
+        let second = Arc::new(WakeCounter::default());
+        let mut sleep = Box::pin(sleep(Duration::from_secs(1)));
+
+        let waker_a = waker(first.clone());
+        let mut cx = Context::from_waker(&waker_a);
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+        let waker_b = waker(second.clone());
+        let mut cx = Context::from_waker(&waker_b);
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+
+        SimTime::set_now(SimTime::from(1.0));
+        process_now();
+        assert_eq!(first.0.load(Ordering::SeqCst), 0);
+        assert_eq!(second.0.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().timers_fired()),
+            1
+        );
+
+        guard.leave();
+    }
+
+    #[test]
+    fn reset_in_select_loop() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("select")));
+
+        let fired = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (tx, mut rx) = crate::sync::mpsc::unbounded_channel::<()>();
+        let log = fired.clone();
+        rt.spawn(async move {
+            let sleep = sleep(Duration::from_millis(10));
+            crate::pin!(sleep);
+
+            // The `select!` loop of the `Sleep` docs, expanded by hand
+            // since the macro cannot be used within the crate.
+            loop {
+                let elapsed = crate::future::poll_fn(|cx| {
+                    if sleep.as_mut().poll(cx).is_ready() {
+                        return Poll::Ready(true);
+                    }
+                    match rx.poll_recv(cx) {
+                        Poll::Ready(Some(())) => Poll::Ready(false),
+                        _ => Poll::Pending,
+                    }
+                })
+                .await;
+
+                if elapsed {
+                    log.lock().unwrap().push(SimTime::now());
+                    sleep
+                        .as_mut()
+                        .reset(SimTime::now() + Duration::from_millis(50));
+                }
+            }
+        });
+        rt.poll_until_idle();
+
+        // Messages re-poll the sleep between its deadlines.
+        for _ in 0..21 {
+            tx.send(()).unwrap();
+            rt.advance(Duration::from_millis(10));
+        }
+
+        let ms = |ms| SimTime::from_duration(Duration::from_millis(ms));
+        assert_eq!(
+            *fired.lock().unwrap(),
+            vec![ms(10), ms(60), ms(110), ms(160), ms(210)]
+        );
+        let time = SimContext::with_current(|ctx| {
+            let time = ctx.time.as_ref().unwrap();
+            (time.upcoming(2), time.timers_fired())
+        });
+        assert_eq!(time, (vec![ms(260)], 5));
+
+        guard.leave();
+    }
+}
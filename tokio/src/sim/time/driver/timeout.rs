@@ -116,10 +116,7 @@ where
 {
     let delay = sleep_until(deadline);
 
-    Timeout {
-        value: future,
-        delay,
-    }
+    Timeout::new_with_delay(future, delay)
 }
 
 pin_project! {
@@ -131,12 +128,34 @@ pin_project! {
         value: T,
         #[pin]
         delay: Sleep,
+        armed: bool,
     }
 }
 
 impl<T> Timeout<T> {
     pub(crate) fn new_with_delay(value: T, delay: Sleep) -> Timeout<T> {
-        Timeout { value, delay }
+        Timeout {
+            value,
+            delay,
+            armed: true,
+        }
+    }
+
+    /// Disarms the timeout, so that the underlying future may run
+    /// without a deadline.
+    ///
+    /// The timer of the timeout is removed, so a disarmed timeout never
+    /// elapses. This is useful if the timeout should only cover the first
+    /// phase of a longer running future, like a handshake.
+    pub fn disarm(self: Pin<&mut Self>) {
+        let me = self.project();
+        *me.armed = false;
+        me.delay.unregister();
+    }
+
+    /// Returns whether the timeout can still elapse.
+    pub fn is_armed(&self) -> bool {
+        self.armed
     }
 
     /// Gets a reference to the underlying value in this timeout.
@@ -169,6 +188,10 @@ where
             return Poll::Ready(Ok(v));
         }
 
+        if !*me.armed {
+            return Poll::Pending;
+        }
+
         let delay = me.delay;
 
         let poll_delay = || -> Poll<Self::Output> {
@@ -181,3 +204,54 @@ where
         poll_delay()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::future::poll_fn;
+    use crate::runtime::Builder;
+    use crate::sim::SimContext;
+    use crate::sync::oneshot;
+    use crate::time::sleep;
+
+    #[test]
+    fn disarm_after_handshake() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("disarm")));
+
+        let handle = rt.spawn(async {
+            let (handshake_tx, mut handshake_rx) = oneshot::channel();
+            let session = async move {
+                sleep(Duration::from_millis(500)).await;
+                handshake_tx.send(()).unwrap();
+                sleep(Duration::from_secs(10)).await;
+                SimTime::now()
+            };
+            let timeout = timeout(Duration::from_secs(1), session);
+            crate::pin!(timeout);
+
+            // Only the handshake is bounded by the timeout.
+            poll_fn(|cx| {
+                assert!(timeout.as_mut().poll(cx).is_pending());
+                Pin::new(&mut handshake_rx).poll(cx)
+            })
+            .await
+            .unwrap();
+            timeout.as_mut().disarm();
+            assert!(!timeout.is_armed());
+
+            timeout.await
+        });
+        rt.poll_until_idle();
+
+        rt.advance(Duration::from_millis(500));
+        let pending = SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().upcoming(2));
+        assert_eq!(pending, vec![SimTime::from_millis(10_500)]);
+
+        rt.advance(Duration::from_secs(10));
+        let finished = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(finished, Ok(SimTime::from_millis(10_500)));
+
+        guard.leave();
+    }
+}
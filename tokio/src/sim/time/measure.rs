@@ -0,0 +1,55 @@
+use super::{Duration, SimTime};
+use std::future::Future;
+
+/// Awaits the future `f`, returning its output together with the amount of
+/// simulation time that elapsed while awaiting it.
+///
+/// The measurement only reflects the advancement of [`SimTime`], not the
+/// wall-clock time spent executing the future.
+///
+/// # Examples
+///
+/// ```
+/// use tokio::time::{measure, sleep, Duration};
+/// # use tokio::sim::SimContext;
+///
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// # let guard = rt.enter_context(SimContext::empty().with_time(String::from("measure")));
+/// # let task = rt.spawn(async {
+/// let ((), elapsed) = measure(sleep(Duration::from_secs(3))).await;
+/// assert_eq!(elapsed, Duration::from_secs(3));
+/// # });
+/// # rt.advance(Duration::from_secs(3));
+/// # rt.block_or_idle_on(task).unwrap().unwrap();
+/// # guard.leave();
+/// ```
+pub async fn measure<F: Future>(f: F) -> (F::Output, Duration) {
+    let start = SimTime::now();
+    let output = f.await;
+    (output, SimTime::now() - start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Builder;
+    use crate::sim::SimContext;
+    use crate::time::sleep;
+
+    #[test]
+    fn measure_sleep() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let guard = rt.enter_context(SimContext::empty().with_time(String::from("measure")));
+
+        let handle = rt.spawn(measure(sleep(Duration::from_secs(3))));
+        rt.poll_until_idle();
+
+        SimTime::set_now(SimTime::from_duration(Duration::from_secs(3)));
+        SimContext::with_current(|ctx| ctx.time.as_ref().unwrap().process_now());
+
+        let ((), elapsed) = rt.block_or_idle_on(handle).unwrap().unwrap();
+        assert_eq!(elapsed, Duration::from_secs(3));
+
+        guard.leave();
+    }
+}
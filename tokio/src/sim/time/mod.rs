@@ -11,8 +11,15 @@ pub use sim_time::*;
 mod duration;
 pub use duration::*;
 
+mod measure;
+pub use measure::measure;
+
+mod backoff;
+pub use backoff::Backoff;
+
 pub(crate) mod driver;
 
+pub use driver::at;
 pub use driver::sleep;
 pub use driver::sleep_until;
 pub use driver::Sleep;
@@ -1,4 +1,5 @@
 use std::cell::Cell;
+use std::convert::TryFrom;
 use std::f64::EPSILON;
 use std::fmt::*;
 use std::ops::*;
@@ -21,6 +22,29 @@ impl SimTime {
         Self(dur)
     }
 
+    /// Creates a simtime from the specified number of whole seconds.
+    ///
+    /// Unlike the conversion from `f64`, the integer constructors
+    /// are exact for all values.
+    pub const fn from_secs(secs: u64) -> Self {
+        Self(Duration::from_secs(secs))
+    }
+
+    /// Creates a simtime from the specified number of milliseconds.
+    pub const fn from_millis(millis: u64) -> Self {
+        Self(Duration::from_millis(millis))
+    }
+
+    /// Creates a simtime from the specified number of microseconds.
+    pub const fn from_micros(micros: u64) -> Self {
+        Self(Duration::from_micros(micros))
+    }
+
+    /// Creates a simtime from the specified number of nanoseconds.
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Self(Duration::from_nanos(nanos))
+    }
+
     /// Returns an instant corresponding to "now" in the simulation context.
     ///
     /// # Examples
@@ -35,6 +59,17 @@ impl SimTime {
         SIMTIME.with(|s| s.get())
     }
 
+    /// Returns "now", rounded down to the clock resolution of the current
+    /// [`SimContext`](crate::sim::SimContext).
+    ///
+    /// This models hardware clocks with a coarse resolution. Without a
+    /// configured resolution, this is the same as [`now`](SimTime::now).
+    #[must_use]
+    pub fn quantized_now() -> Self {
+        let resolution = crate::sim::SimContext::with_current(|ctx| ctx.clock_resolution());
+        Self::now().truncate_to(resolution)
+    }
+
     ///
     /// Sets the current clock
     ///
@@ -103,6 +138,50 @@ impl SimTime {
     pub fn checked_sub(&self, duration: Duration) -> Option<SimTime> {
         self.0.checked_sub(duration).map(SimTime)
     }
+
+    /// Snaps the instant to the closest multiple of `grid`, rounding
+    /// halfway cases up.
+    ///
+    /// If `grid` is zero, or rounding up would overflow, the instant is
+    /// truncated instead.
+    #[must_use]
+    pub fn round_to(&self, grid: Duration) -> SimTime {
+        let grid_nanos = grid.as_nanos();
+        if grid_nanos == 0 {
+            return *self;
+        }
+
+        let nanos = self.0.as_nanos();
+        let rem = nanos % grid_nanos;
+        if rem * 2 >= grid_nanos {
+            Self::checked_from_nanos(nanos - rem + grid_nanos).unwrap_or_else(|| self.truncate_to(grid))
+        } else {
+            self.truncate_to(grid)
+        }
+    }
+
+    /// Snaps the instant to the greatest multiple of `grid` that is
+    /// not later than the instant.
+    ///
+    /// If `grid` is zero, the instant is returned unchanged.
+    #[must_use]
+    pub fn truncate_to(&self, grid: Duration) -> SimTime {
+        let grid_nanos = grid.as_nanos();
+        if grid_nanos == 0 {
+            return *self;
+        }
+
+        let nanos = self.0.as_nanos();
+        Self::checked_from_nanos(nanos - nanos % grid_nanos).expect("truncation cannot overflow")
+    }
+
+    fn checked_from_nanos(nanos: u128) -> Option<SimTime> {
+        const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+        let secs = u64::try_from(nanos / NANOS_PER_SEC).ok()?;
+        let subsec = (nanos % NANOS_PER_SEC) as u32;
+        Some(SimTime(Duration::new(secs, subsec)))
+    }
 }
 
 // # Custom Additions
@@ -127,10 +206,19 @@ impl Deref for SimTime {
 
 // CMP
 
+/// Compares a [SimTime] against a point in time given in seconds.
+///
+/// Since a [SimTime] has nanosecond resolution and `f64` values lose
+/// precision with growing magnitude, the comparison tolerates an absolute
+/// error of one nanosecond plus a relative error of a few ulps.
+/// Use [`SimTime::eq_approx`] for comparisons with a custom error margin.
 impl PartialEq<f64> for SimTime {
     fn eq(&self, other: &f64) -> bool {
-        let diff = (self.0.as_secs_f64() - *other).abs();
-        diff < EPSILON
+        const NANO: f64 = 1e-9;
+
+        let this = self.0.as_secs_f64();
+        let diff = (this - *other).abs();
+        diff <= NANO + 4.0 * EPSILON * this.abs().max(other.abs())
     }
 }
 
@@ -238,3 +326,55 @@ impl Default for SimTime {
         Self::from(0.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eq_f64() {
+        assert_eq!(SimTime::from(1.0), 1.0);
+        assert_eq!(SimTime::from(1000.0), 1000.0);
+        assert_eq!(SimTime::from(1.234_567_891_234_5), 1.234_567_891_234_5);
+        assert_eq!(SimTime::from(123_456.789_012_345), 123_456.789_012_345);
+
+        assert_ne!(SimTime::from(1.0), 1.000_001);
+        assert_ne!(SimTime::from(1000.0), 1000.000_001);
+    }
+
+    #[test]
+    fn round_and_truncate() {
+        let grid = Duration::from_millis(100);
+        let ms = |ms| SimTime::from_duration(Duration::from_millis(ms));
+
+        let t = SimTime::from(1.234);
+        assert_eq!(t.round_to(grid), ms(1200));
+        assert_eq!(t.truncate_to(grid), ms(1200));
+
+        let t = SimTime::from(1.25);
+        assert_eq!(t.round_to(grid), ms(1300));
+        assert_eq!(t.truncate_to(grid), ms(1200));
+
+        let t = SimTime::from(1.299);
+        assert_eq!(t.round_to(grid), ms(1300));
+        assert_eq!(t.truncate_to(grid), ms(1200));
+
+        assert_eq!(t.round_to(Duration::ZERO), t);
+
+        let grid = Duration::from_secs(7);
+        assert_eq!(SimTime::MAX.round_to(grid), SimTime::MAX.truncate_to(grid));
+    }
+
+    #[test]
+    fn integer_constructors() {
+        assert_eq!(SimTime::from_nanos(1).as_nanos(), 1);
+        assert_eq!(SimTime::from_micros(1), SimTime::from_nanos(1_000));
+        assert_eq!(SimTime::from_millis(1), SimTime::from_micros(1_000));
+        assert_eq!(SimTime::from_secs(1), SimTime::from_millis(1_000));
+
+        // An f64 cannot represent nanoseconds at this magnitude.
+        let exact = SimTime::from_secs(123_456_789) + SimTime::from_nanos(1);
+        assert_eq!(exact.as_nanos(), 123_456_789_000_000_001);
+        assert_ne!(SimTime::from(123_456_789.000_000_001), exact);
+    }
+}
@@ -189,6 +189,18 @@ impl Barrier {
 
         BarrierWaitResult(false)
     }
+
+    cfg_sim! {
+        /// Returns the number of tasks waiting in the current generation.
+        pub(crate) fn arrived(&self) -> usize {
+            self.state.lock().arrived
+        }
+
+        /// Returns the number of completed rendezvous.
+        pub(crate) fn completed(&self) -> usize {
+            self.state.lock().generation - 1
+        }
+    }
 }
 
 /// A `BarrierWaitResult` is returned by `wait` when all tasks in the `Barrier` have rendezvoused.
@@ -330,6 +330,16 @@ impl<T> Receiver<T> {
         self.chan.close();
     }
 
+    cfg_sim! {
+        /// Returns the number of messages queued in the channel.
+        ///
+        /// Messages are counted once they are sent, so permits that
+        /// were reserved but not yet used are not included.
+        pub fn sim_len(&self) -> usize {
+            self.chan.len()
+        }
+    }
+
     /// Polls to receive the next message on this channel.
     ///
     /// This method returns:
@@ -988,6 +998,17 @@ impl<T> Sender<T> {
     pub fn capacity(&self) -> usize {
         self.chan.semaphore().0.available_permits()
     }
+
+    cfg_sim! {
+        /// Returns the number of permits that can still be acquired
+        /// before the channel applies backpressure.
+        ///
+        /// This is equivalent to [`capacity`](Sender::capacity), so a value
+        /// of zero means that further sends will wait.
+        pub fn sim_capacity(&self) -> usize {
+            self.capacity()
+        }
+    }
 }
 
 impl<T> Clone for Sender<T> {
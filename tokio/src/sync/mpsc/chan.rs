@@ -64,6 +64,10 @@ struct Chan<T, S> {
     /// When this drops to zero, the send half of the channel is closed.
     tx_count: AtomicUsize,
 
+    /// Tracks the number of values queued in the channel.
+    #[cfg(feature = "sim")]
+    len: AtomicUsize,
+
     /// Only accessed by `Rx` handle.
     rx_fields: UnsafeCell<RxFields<T>>,
 }
@@ -113,6 +117,8 @@ pub(crate) fn channel<T, S: Semaphore>(semaphore: S) -> (Tx<T, S>, Rx<T, S>) {
         semaphore,
         rx_waker: AtomicWaker::new(),
         tx_count: AtomicUsize::new(1),
+        #[cfg(feature = "sim")]
+        len: AtomicUsize::new(0),
         rx_fields: UnsafeCell::new(RxFields {
             list: rx,
             rx_closed: false,
@@ -215,6 +221,13 @@ impl<T, S: Semaphore> Rx<T, S> {
         self.inner.notify_rx_closed.notify_waiters();
     }
 
+    cfg_sim! {
+        /// Returns the number of values queued in the channel.
+        pub(crate) fn len(&self) -> usize {
+            self.inner.len.load(Acquire)
+        }
+    }
+
     /// Receive the next value
     pub(crate) fn recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
         use super::block::Read::*;
@@ -229,6 +242,8 @@ impl<T, S: Semaphore> Rx<T, S> {
                 () => {
                     match rx_fields.list.pop(&self.inner.tx) {
                         Some(Value(value)) => {
+                            #[cfg(feature = "sim")]
+                            self.inner.len.fetch_sub(1, AcqRel);
                             self.inner.semaphore.add_permit();
                             coop.made_progress();
                             return Ready(Some(value));
@@ -278,6 +293,8 @@ impl<T, S: Semaphore> Rx<T, S> {
                 () => {
                     match rx_fields.list.try_pop(&self.inner.tx) {
                         TryPopResult::Ok(value) => {
+                            #[cfg(feature = "sim")]
+                            self.inner.len.fetch_sub(1, AcqRel);
                             self.inner.semaphore.add_permit();
                             return Ok(value);
                         }
@@ -333,6 +350,9 @@ impl<T, S: Semaphore> Drop for Rx<T, S> {
 
 impl<T, S> Chan<T, S> {
     fn send(&self, value: T) {
+        #[cfg(feature = "sim")]
+        self.len.fetch_add(1, AcqRel);
+
         // Push the value
         self.tx.push(value);
 
@@ -222,6 +222,13 @@ impl<T> UnboundedReceiver<T> {
         self.chan.close();
     }
 
+    cfg_sim! {
+        /// Returns the number of messages queued in the channel.
+        pub fn sim_len(&self) -> usize {
+            self.chan.len()
+        }
+    }
+
     /// Polls to receive the next message on this channel.
     ///
     /// This method returns:
@@ -2,6 +2,9 @@ cfg_not_loom! {
     mod atomic_waker;
     mod notify;
     mod semaphore_batch;
+
+    #[cfg(feature = "sim")]
+    mod mpsc_sim;
 }
 
 cfg_loom! {
@@ -0,0 +1,37 @@
+use crate::sync::mpsc;
+
+#[test]
+fn bounded_capacity_and_len() {
+    let (tx, mut rx) = mpsc::channel(3);
+    assert_eq!(tx.sim_capacity(), 3);
+    assert_eq!(rx.sim_len(), 0);
+
+    let permit = tx.try_reserve().unwrap();
+    assert_eq!(tx.sim_capacity(), 2);
+    assert_eq!(rx.sim_len(), 0);
+
+    tx.try_send(1).unwrap();
+    tx.try_send(2).unwrap();
+    assert_eq!(tx.sim_capacity(), 0);
+    assert_eq!(rx.sim_len(), 2);
+    assert!(tx.try_send(3).is_err());
+
+    permit.send(3);
+    assert_eq!(rx.sim_len(), 3);
+
+    assert_eq!(rx.try_recv().unwrap(), 1);
+    assert_eq!(tx.sim_capacity(), 1);
+    assert_eq!(rx.sim_len(), 2);
+}
+
+#[test]
+fn unbounded_len() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    for i in 0..10 {
+        tx.send(i).unwrap();
+    }
+    assert_eq!(rx.sim_len(), 10);
+
+    while rx.try_recv().is_ok() {}
+    assert_eq!(rx.sim_len(), 0);
+}
@@ -112,6 +112,12 @@ cfg_rt! {
     /// still spawn additional threads for blocking operations. The basic
     /// scheduler's single thread is only used for asynchronous code.
     ///
+    /// With feature "sim", no threads are spawned while a simulation context is
+    /// entered on a current thread runtime. The closure runs as an ordinary task
+    /// of the runtime, so its result is available within the same call of
+    /// `poll_until_idle`, without advancing the simulation time. Other runtimes
+    /// still use the blocking pool.
+    ///
     /// # Related APIs and patterns for bridging asynchronous and blocking code
     ///
     /// In simple cases, it is sufficient to have the closure accept input
@@ -194,6 +200,6 @@ cfg_rt! {
         F: FnOnce() -> R + Send + 'static,
         R: Send + 'static,
     {
-        crate::runtime::spawn_blocking(f)
+        crate::runtime::context::current().spawn_blocking(f)
     }
 }
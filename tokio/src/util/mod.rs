@@ -60,9 +60,10 @@ cfg_rt! {
     pub(crate) use vec_deque_cell::VecDequeCell;
 }
 
-cfg_rt_multi_thread! {
-    pub(crate) use self::rand::FastRand;
+#[cfg(any(feature = "rt-multi-thread", feature = "sim"))]
+pub(crate) use self::rand::FastRand;
 
+cfg_rt_multi_thread! {
     mod try_lock;
     pub(crate) use try_lock::TryLock;
 }
@@ -7,7 +7,7 @@ use std::cell::Cell;
 /// Xorshift paper: <https://www.jstatsoft.org/article/view/v008i14/xorshift.pdf>
 /// This generator passes the SmallCrush suite, part of TestU01 framework:
 /// <http://simul.iro.umontreal.ca/testu01/tu01.html>
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct FastRand {
     one: Cell<u32>,
     two: Cell<u32>,